@@ -0,0 +1,265 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, Signal, System};
+use tauri::{Emitter, Manager, Runtime};
+use crate::profile::{GLOBAL_STORAGE_DIR, WORKSPACE_STORAGE_DIR};
+use crate::{is_profile_process, ArchiveOptions, Config, CursorManager};
+
+pub const SESSION_IDLE_EVENT: &str = "session-idle";
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+// Summed CPU usage (percent of one core) across a session's processes that counts as activity
+const CPU_ACTIVITY_THRESHOLD: f32 = 2.0;
+// How long a graceful stop may take before auto-archive is skipped
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(30);
+const MAX_LOGGED_EVENTS: usize = 200;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum IdleAction {
+    #[default]
+    Notify,
+    Stop,
+    StopAndArchive,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionActivity {
+    pub session: String,
+    pub pid: Option<u32>,
+    pub launched_at: DateTime<Local>,
+    pub last_activity: DateTime<Local>,
+    // What last counted as activity: "launch", "cpu", "profile" or "touch"
+    pub last_signal: String,
+    // Set once the idle action ran, cleared by the next activity
+    pub idle_handled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IdleEvent {
+    pub session: String,
+    pub action: IdleAction,
+    pub idle_minutes: i64,
+    pub timestamp: DateTime<Local>,
+    pub success: bool,
+    pub message: String,
+}
+
+// Sessions launched by this app instance, tracked for the idle timeout. Managed as Tauri state
+#[derive(Default)]
+pub struct ProcessRegistry {
+    sessions: Mutex<HashMap<String, SessionActivity>>,
+    events: Mutex<VecDeque<IdleEvent>>,
+}
+
+impl ProcessRegistry {
+    pub fn register(&self, session: &str, pid: Option<u32>) {
+        let now = Local::now();
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.insert(session.to_string(), SessionActivity {
+                session: session.to_string(),
+                pid,
+                launched_at: now,
+                last_activity: now,
+                last_signal: "launch".to_string(),
+                idle_handled: false,
+            });
+        }
+    }
+
+    pub fn unregister(&self, session: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(session);
+        }
+    }
+
+    // Record activity for a session; returns false when the session is not tracked
+    pub fn touch(&self, session: &str, signal: &str) -> bool {
+        match self.sessions.lock() {
+            Ok(mut sessions) => match sessions.get_mut(session) {
+                Some(activity) => {
+                    activity.last_activity = Local::now();
+                    activity.last_signal = signal.to_string();
+                    activity.idle_handled = false;
+                    true
+                }
+                None => false,
+            },
+            Err(_) => false,
+        }
+    }
+
+    pub fn list(&self) -> Vec<SessionActivity> {
+        let mut sessions: Vec<SessionActivity> = self.sessions.lock().map(|s| s.values().cloned().collect()).unwrap_or_default();
+        sessions.sort_by(|a, b| a.session.cmp(&b.session));
+        sessions
+    }
+
+    pub fn events(&self) -> Vec<IdleEvent> {
+        self.events.lock().map(|e| e.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    fn mark_handled(&self, session: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            if let Some(activity) = sessions.get_mut(session) {
+                activity.idle_handled = true;
+            }
+        }
+    }
+
+    fn log_event(&self, event: IdleEvent) {
+        if event.success {
+            tracing::info!("Idle session '{}' ({} min): {:?} - {}", event.session, event.idle_minutes, event.action, event.message);
+        } else {
+            tracing::warn!("Idle session '{}' ({} min): {:?} failed - {}", event.session, event.idle_minutes, event.action, event.message);
+        }
+        if let Ok(mut events) = self.events.lock() {
+            events.push_back(event);
+            while events.len() > MAX_LOGGED_EVENTS {
+                events.pop_front();
+            }
+        }
+    }
+}
+
+// Newest write to the editor's state databases; they change whenever the user does something in a window
+fn latest_profile_write(session_dir: &Path) -> Option<SystemTime> {
+    let mut candidates = vec![session_dir.join(GLOBAL_STORAGE_DIR).join("state.vscdb")];
+    if let Ok(entries) = fs::read_dir(session_dir.join(WORKSPACE_STORAGE_DIR)) {
+        candidates.extend(entries.flatten().map(|entry| entry.path().join("state.vscdb")));
+    }
+    candidates
+        .iter()
+        .filter_map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+fn session_pids(system: &System, session_dir: &str) -> Vec<Pid> {
+    system
+        .processes()
+        .iter()
+        .filter(|(_, process)| is_profile_process(process.cmd(), session_dir))
+        .map(|(pid, _)| *pid)
+        .collect()
+}
+
+// Ask every process of the profile to terminate and wait for them to exit
+fn stop_session_processes(session_dir: &str) -> Result<(), String> {
+    let mut system = System::new();
+    system.refresh_processes();
+    let pids = session_pids(&system, session_dir);
+    if pids.is_empty() {
+        return Ok(());
+    }
+    for pid in &pids {
+        if let Some(process) = system.process(*pid) {
+            if process.kill_with(Signal::Term).is_none() {
+                // Platforms without SIGTERM only offer a hard kill, which is not a graceful stop
+                return Err("graceful termination is not supported on this platform".to_string());
+            }
+        }
+    }
+
+    let deadline = SystemTime::now() + STOP_GRACE_PERIOD;
+    while SystemTime::now() < deadline {
+        std::thread::sleep(Duration::from_secs(1));
+        system.refresh_processes();
+        if session_pids(&system, session_dir).is_empty() {
+            return Ok(());
+        }
+    }
+    Err(format!("still running {}s after the stop request", STOP_GRACE_PERIOD.as_secs()))
+}
+
+fn run_idle_action<R: Runtime>(app: &tauri::AppHandle<R>, manager: &CursorManager, session: &str, action: IdleAction, idle_minutes: i64) -> IdleEvent {
+    let session_dir = manager.session_dir(session).to_string_lossy().to_string();
+    let outcome = match action {
+        IdleAction::Notify => Ok(format!("Session has been idle for {} minutes", idle_minutes)),
+        IdleAction::Stop => stop_session_processes(&session_dir).map(|_| "Session stopped".to_string()),
+        IdleAction::StopAndArchive => stop_session_processes(&session_dir)
+            .and_then(|_| manager.archive_session(session, &ArchiveOptions::default()).map_err(|e| format!("stopped, but archiving failed: {}", e))),
+    };
+
+    let event = IdleEvent {
+        session: session.to_string(),
+        action,
+        idle_minutes,
+        timestamp: Local::now(),
+        success: outcome.is_ok(),
+        message: match outcome {
+            Ok(message) => message,
+            Err(e) => e,
+        },
+    };
+    let _ = app.emit(SESSION_IDLE_EVENT, event.clone());
+    event
+}
+
+// The only config state; update_config writes it and every command and background task reads it
+pub(crate) fn read_config(config_state: &Mutex<Config>) -> Config {
+    config_state.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub(crate) fn current_config<R: Runtime>(app: &tauri::AppHandle<R>) -> Config {
+    read_config(app.state::<Mutex<Config>>().inner())
+}
+
+fn check_idle_sessions<R: Runtime>(app: &tauri::AppHandle<R>, system: &mut System) {
+    let registry = app.state::<ProcessRegistry>();
+    let config = current_config(app);
+    let manager = CursorManager::new(Some(config.clone()));
+    system.refresh_processes();
+
+    for activity in registry.list() {
+        let session_dir = manager.session_dir(&activity.session);
+        let session_dir_str = session_dir.to_string_lossy().to_string();
+        let pids = session_pids(system, &session_dir_str);
+        if pids.is_empty() {
+            tracing::info!("Session '{}' exited, no longer tracking it", activity.session);
+            registry.unregister(&activity.session);
+            continue;
+        }
+
+        // cpu_usage covers the time since the previous refresh, i.e. the last check interval
+        let cpu: f32 = pids.iter().filter_map(|pid| system.process(*pid)).map(|p| p.cpu_usage()).sum();
+        if cpu >= CPU_ACTIVITY_THRESHOLD {
+            registry.touch(&activity.session, "cpu");
+            continue;
+        }
+        let last_activity: SystemTime = activity.last_activity.into();
+        if latest_profile_write(&session_dir).map(|written| written > last_activity).unwrap_or(false) {
+            registry.touch(&activity.session, "profile");
+            continue;
+        }
+
+        if config.session_timeout_minutes == 0 || activity.idle_handled {
+            continue;
+        }
+        let idle_minutes = (Local::now() - activity.last_activity).num_minutes();
+        if idle_minutes < config.session_timeout_minutes as i64 {
+            continue;
+        }
+
+        registry.mark_handled(&activity.session);
+        let event = run_idle_action(app, &manager, &activity.session, config.idle_action, idle_minutes);
+        if event.success && event.action != IdleAction::Notify {
+            registry.unregister(&activity.session);
+        }
+        registry.log_event(event);
+    }
+}
+
+// Background thread that applies Config.session_timeout_minutes to launched sessions
+pub fn start_idle_monitor<R: Runtime>(app: tauri::AppHandle<R>) {
+    std::thread::spawn(move || {
+        let mut system = System::new();
+        loop {
+            std::thread::sleep(CHECK_INTERVAL);
+            check_idle_sessions(&app, &mut system);
+        }
+    });
+}
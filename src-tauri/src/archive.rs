@@ -0,0 +1,782 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::encryption::{decrypting_reader, encrypting_writer, ArchiveKey, EncryptionKind};
+use crate::jobs;
+use crate::profile::{copy_profile_entry, dir_size, path_matches, relative_string, write_file_atomic, CopyStats, SESSION_FILE};
+use crate::restore::{RestoreMode, RestoreOptions};
+use crate::snapshot::{lock_store, SnapshotStats, SNAPSHOT_EXTENSION};
+use crate::staging::{staging_path, sync_dir, PARTIAL_SUFFIX};
+use crate::{split_archive_name, validate_session_name, CursorManager, CursorManagerError, SessionInfo};
+
+pub const ARCHIVE_EXTENSION: &str = ".tar.zst";
+pub const ENCRYPTED_ARCHIVE_EXTENSION: &str = ".tar.zst.age";
+// Sidecar next to each archive file, so listings never have to open the archive
+pub const MANIFEST_EXTENSION: &str = ".manifest.json";
+// 2 added per-file checksums, 3 encryption
+const MANIFEST_VERSION: u32 = 3;
+// Last entry of encrypted archives, holding the manifest fields kept out of the plain text sidecar
+const PRIVATE_MANIFEST_ENTRY: &str = ".archive-manifest.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveFormat {
+    // Plain copy of the profile, written by older versions
+    Directory,
+    TarZst,
+    // Index into the shared chunk store, see snapshot.rs
+    Snapshot,
+    // .tar.zst inside an age envelope, see encryption.rs
+    EncryptedTarZst,
+}
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Directory => "",
+            ArchiveFormat::TarZst => ARCHIVE_EXTENSION,
+            ArchiveFormat::Snapshot => SNAPSHOT_EXTENSION,
+            ArchiveFormat::EncryptedTarZst => ENCRYPTED_ARCHIVE_EXTENSION,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveMode {
+    // Self-contained .tar.zst file
+    #[default]
+    Full,
+    // Deduplicated against earlier snapshots through the chunk store
+    Snapshot,
+}
+
+// Per-archive overrides of the Config defaults
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ArchiveOptions {
+    #[serde(default)]
+    pub mode: Option<ArchiveMode>,
+    #[serde(default)]
+    pub compression_level: Option<i32>,
+    // Replaces Config.archive_exclude for this archive
+    #[serde(default)]
+    pub exclude: Option<Vec<String>>,
+    // Some(false) skips Config.archive_key_file for this archive
+    #[serde(default)]
+    pub encrypt: Option<bool>,
+    // Replaces Config.archive_key_file for this archive
+    #[serde(default)]
+    pub key: Option<ArchiveKey>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchiveManifest {
+    pub manifest_version: u32,
+    pub archive: String,
+    pub session: String,
+    // session.json of the source session at archive time. Encrypted archives keep it, and
+    // `files`, inside the archive instead
+    pub session_info: Option<SessionInfo>,
+    pub created: DateTime<Local>,
+    pub app_version: String,
+    pub file_count: u64,
+    // Uncompressed size of the archived files
+    pub total_bytes: u64,
+    pub archive_bytes: u64,
+    pub compression: String,
+    pub compression_level: i32,
+    // "sha256:<hex>" of the archive file
+    pub content_hash: String,
+    // Empty in version 1 manifests
+    #[serde(default)]
+    pub files: Vec<ArchivedFile>,
+    #[serde(default)]
+    pub encryption: Option<EncryptionKind>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PrivateManifest {
+    pub session_info: Option<SessionInfo>,
+    pub files: Vec<ArchivedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchivedFile {
+    // Relative to the profile root, '/' separated
+    pub path: String,
+    pub size: u64,
+    // Hex SHA-256 of the file contents
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveInfo {
+    pub name: String,
+    pub path: String,
+    pub created: DateTime<Local>,
+    pub original_session: String,
+    #[serde(default)]
+    pub pinned: bool,
+    pub format: ArchiveFormat,
+    // For snapshots only the bytes no other snapshot shares, i.e. what deleting it frees
+    pub size: u64,
+    // Missing for archives written before manifests existed
+    pub manifest: Option<ArchiveManifest>,
+    pub snapshot: Option<SnapshotStats>,
+}
+
+// An archive on disk. `name` is the logical name without the file extension
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub format: ArchiveFormat,
+}
+
+impl ArchiveEntry {
+    pub fn manifest_path(&self) -> PathBuf {
+        self.path.with_file_name(format!("{}{}", self.name, MANIFEST_EXTENSION))
+    }
+
+    pub fn read_manifest(&self) -> Option<ArchiveManifest> {
+        let data = fs::read_to_string(self.manifest_path()).ok()?;
+        match serde_json::from_str(&data) {
+            Ok(manifest) => Some(manifest),
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable manifest of archive '{}': {}", self.name, e);
+                None
+            }
+        }
+    }
+
+    // Session and creation time from the manifest, falling back to the name and file times
+    pub fn origin(&self) -> (String, DateTime<Local>) {
+        if let Some(manifest) = self.read_manifest() {
+            return (manifest.session, manifest.created);
+        }
+        let parsed = split_archive_name(&self.name).map(|(session, timestamp)| (session.to_string(), parse_archive_timestamp(timestamp)));
+        let modified = || {
+            fs::metadata(&self.path)
+                .and_then(|m| m.modified())
+                .map(DateTime::<Local>::from)
+                .unwrap_or_else(|_| Local::now())
+        };
+        match parsed {
+            Some((session, Some(created))) => (session, created),
+            Some((session, None)) => (session, modified()),
+            None => (self.name.clone(), modified()),
+        }
+    }
+}
+
+pub(crate) fn parse_archive_timestamp(timestamp: &str) -> Option<DateTime<Local>> {
+    let naive = NaiveDateTime::parse_from_str(timestamp, "%Y%m%d-%H%M%S").ok()?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+// Passes reads through while hashing them
+pub(crate) struct HashingReader<R: Read> {
+    inner: R,
+    hasher: Sha256,
+    pub read: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, hasher: Sha256::new(), read: 0 }
+    }
+
+    pub fn hex_digest(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        self.read += read as u64;
+        Ok(read)
+    }
+}
+
+// Passes writes through while hashing them
+pub(crate) struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+    pub written: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, hasher: Sha256::new(), written: 0 }
+    }
+
+    pub fn hex_digest(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Result of streaming a profile into an archive file
+pub struct WrittenArchive {
+    pub stats: CopyStats,
+    pub files: Vec<ArchivedFile>,
+    pub archive_bytes: u64,
+    pub content_hash: String,
+}
+
+// Stream one file into the archive under a header that already carries its size. tar pads the entry by
+// what was actually copied, so a file that shrank while being read would misalign every entry after it
+fn append_file<W: Write>(builder: &mut tar::Builder<W>, header: &mut tar::Header, relative: &Path, data: impl Read) -> std::io::Result<ArchivedFile> {
+    let size = header.size()?;
+    // Hashed while it streams into the archive; take() keeps a growing file from overrunning its header size
+    let mut reader = HashingReader::new(data.take(size));
+    builder.append_data(header, relative, &mut reader)?;
+    let path = relative_string(relative);
+    if reader.read < size {
+        return Err(std::io::Error::other(format!("{} changed while it was being archived; close the session and try again", path)));
+    }
+    Ok(ArchivedFile { path, size: reader.read, sha256: reader.hex_digest() })
+}
+
+fn append_tree<W: Write>(builder: &mut tar::Builder<W>, root: &Path, relative: &Path, exclude: &[String], stats: &mut CopyStats, files: &mut Vec<ArchivedFile>) -> std::io::Result<()> {
+    for entry in fs::read_dir(root.join(relative))? {
+        let entry = entry?;
+        let entry_relative = relative.join(entry.file_name());
+        if exclude.iter().any(|pattern| path_matches(&relative_string(&entry_relative), pattern)) {
+            continue;
+        }
+        // Symlinks are skipped, like in clone_session
+        let metadata = fs::symlink_metadata(entry.path())?;
+        if metadata.is_dir() {
+            builder.append_dir(&entry_relative, entry.path())?;
+            append_tree(builder, root, &entry_relative, exclude, stats, files)?;
+        } else if metadata.is_file() {
+            let mut header = tar::Header::new_gnu();
+            header.set_metadata(&metadata);
+            let file = append_file(builder, &mut header, &entry_relative, File::open(entry.path())?)?;
+            jobs::checkpoint(&file.path, file.size, 1)?;
+            files.push(file);
+            stats.files += 1;
+            stats.bytes += metadata.len();
+        }
+    }
+    Ok(())
+}
+
+// A profile streamed into a tar builder that has not been finished yet
+struct ProfileTar<W: Write> {
+    builder: tar::Builder<zstd::Encoder<'static, W>>,
+    stats: CopyStats,
+    files: Vec<ArchivedFile>,
+}
+
+fn tar_profile<W: Write>(output: W, source: &Path, level: i32, exclude: &[String]) -> Result<ProfileTar<W>, Box<dyn Error>> {
+    let mut builder = tar::Builder::new(zstd::Encoder::new(output, level)?);
+    builder.follow_symlinks(false);
+
+    let mut stats = CopyStats::default();
+    let mut files = Vec::new();
+    append_tree(&mut builder, source, Path::new(""), exclude, &mut stats, &mut files)?;
+    Ok(ProfileTar { builder, stats, files })
+}
+
+// Stream a profile into a .tar.zst file without building the archive in memory. With a key the
+// stream is encrypted and `session_info` is embedded, since it cannot go into the plain text manifest
+pub fn write_tar_zst(source: &Path, target: &Path, level: i32, exclude: &[String], key: Option<&ArchiveKey>, session_info: Option<&SessionInfo>) -> Result<WrittenArchive, Box<dyn Error>> {
+    let file = HashingWriter::new(BufWriter::new(File::create(target)?));
+    let (file, stats, files) = match key {
+        None => {
+            let tar = tar_profile(file, source, level, exclude)?;
+            (tar.builder.into_inner()?.finish()?, tar.stats, tar.files)
+        }
+        Some(key) => {
+            let ProfileTar { mut builder, stats, files } = tar_profile(encrypting_writer(key, file)?, source, level, exclude)?;
+            let private = serde_json::to_vec(&PrivateManifest { session_info: session_info.cloned(), files: files.clone() })?;
+            let mut header = tar::Header::new_gnu();
+            header.set_size(private.len() as u64);
+            header.set_mode(0o600);
+            header.set_mtime(Local::now().timestamp().max(0) as u64);
+            builder.append_data(&mut header, PRIVATE_MANIFEST_ENTRY, private.as_slice())?;
+            (builder.into_inner()?.finish()?.finish()?, stats, files)
+        }
+    };
+    file.inner.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    Ok(WrittenArchive {
+        stats,
+        files,
+        archive_bytes: file.written,
+        content_hash: format!("sha256:{:x}", file.hasher.finalize()),
+    })
+}
+
+// Tar stream of a .tar.zst or encrypted archive; encrypted ones need `key`
+pub(crate) fn open_tar_stream(entry: &ArchiveEntry, key: Option<&ArchiveKey>) -> Result<tar::Archive<Box<dyn Read>>, Box<dyn Error>> {
+    let reader: Box<dyn Read> = match entry.format {
+        ArchiveFormat::TarZst => Box::new(zstd::Decoder::new(File::open(&entry.path)?)?),
+        ArchiveFormat::EncryptedTarZst => {
+            let key = key.ok_or_else(|| Box::new(CursorManagerError::ArchiveKeyRequired { name: entry.name.clone() }))?;
+            let decrypted = decrypting_reader(key, BufReader::new(File::open(&entry.path)?), &entry.name)?;
+            Box::new(zstd::Decoder::new(decrypted)?)
+        }
+        _ => return Err(format!("Archive '{}' is not a tar archive", entry.name).into()),
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+pub(crate) fn is_private_manifest(path: &Path) -> bool {
+    path == Path::new(PRIVATE_MANIFEST_ENTRY)
+}
+
+// Unpack into `target` and return the embedded manifest of encrypted archives
+pub fn unpack_tar_zst(entry: &ArchiveEntry, key: Option<&ArchiveKey>, target: &Path) -> Result<Option<PrivateManifest>, Box<dyn Error>> {
+    let mut tar = open_tar_stream(entry, key)?;
+    let mut private = None;
+    for tar_entry in tar.entries()? {
+        let mut tar_entry = tar_entry?;
+        if is_private_manifest(&tar_entry.path()?) {
+            let mut data = Vec::new();
+            tar_entry.read_to_end(&mut data)?;
+            private = serde_json::from_slice(&data).ok();
+            continue;
+        }
+        // unpack_in skips entries that would land outside `target`
+        tar_entry.unpack_in(target)?;
+        if tar_entry.header().entry_type().is_file() {
+            jobs::checkpoint(&relative_string(&tar_entry.path()?), tar_entry.size(), 1)?;
+        }
+    }
+    Ok(private)
+}
+
+// Older versions copied the profile *into* the archive directory, so the files sit one level down
+pub(crate) fn legacy_archive_root(archive_dir: &Path) -> PathBuf {
+    if archive_dir.join(SESSION_FILE).exists() {
+        return archive_dir.to_path_buf();
+    }
+    let dirs: Vec<PathBuf> = fs::read_dir(archive_dir)
+        .map(|entries| entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()).collect())
+        .unwrap_or_default();
+    match dirs.as_slice() {
+        [only] => only.clone(),
+        _ => archive_dir.to_path_buf(),
+    }
+}
+
+impl CursorManager {
+    pub fn archive_entries(&self) -> Result<Vec<ArchiveEntry>, Box<dyn Error>> {
+        let archive_dir = Path::new(&self.config.archive_base);
+        let mut entries = Vec::new();
+        if !archive_dir.exists() {
+            return Ok(entries);
+        }
+
+        for entry in fs::read_dir(archive_dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            // Hidden entries are bookkeeping (pins) or unfinished archives
+            if file_name.starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            if path.is_dir() {
+                entries.push(ArchiveEntry { name: file_name, path, format: ArchiveFormat::Directory });
+                continue;
+            }
+            let file_formats = [ArchiveFormat::TarZst, ArchiveFormat::Snapshot, ArchiveFormat::EncryptedTarZst];
+            if let Some((name, format)) = file_formats.iter().find_map(|format| file_name.strip_suffix(format.extension()).map(|name| (name, *format))) {
+                entries.push(ArchiveEntry { name: name.to_string(), path, format });
+            }
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    // Find an archive by logical name; the file extension may be included
+    pub fn resolve_archive(&self, archive: &str) -> Result<ArchiveEntry, Box<dyn Error>> {
+        let file_formats = [ArchiveFormat::TarZst, ArchiveFormat::Snapshot, ArchiveFormat::EncryptedTarZst];
+        let name = file_formats.iter().find_map(|format| archive.strip_suffix(format.extension())).unwrap_or(archive);
+        validate_session_name(name).map_err(|_| format!("Invalid archive name '{}'", archive))?;
+
+        let base = Path::new(&self.config.archive_base);
+        for format in file_formats {
+            let file = base.join(format!("{}{}", name, format.extension()));
+            if file.is_file() {
+                return Ok(ArchiveEntry { name: name.to_string(), path: file, format });
+            }
+        }
+        let dir = base.join(name);
+        if dir.is_dir() {
+            return Ok(ArchiveEntry { name: name.to_string(), path: dir, format: ArchiveFormat::Directory });
+        }
+        Err(format!("Archive '{}' not found", archive).into())
+    }
+
+    pub fn list_archives(&self) -> Result<Vec<ArchiveInfo>, Box<dyn Error>> {
+        let pins = self.pinned_archives();
+        let mut archives = Vec::new();
+
+        for entry in self.archive_entries()? {
+            let (original_session, created) = entry.origin();
+            let snapshot = match entry.format {
+                ArchiveFormat::Snapshot => self.archive_snapshot_stats(&entry.path),
+                _ => None,
+            };
+            let size = match &snapshot {
+                Some(stats) => stats.unique_bytes,
+                None => self.archive_size(&entry),
+            };
+
+            archives.push(ArchiveInfo {
+                snapshot,
+                pinned: pins.contains(&entry.name),
+                manifest: entry.read_manifest(),
+                path: entry.path.to_string_lossy().to_string(),
+                name: entry.name,
+                created,
+                original_session,
+                format: entry.format,
+                size,
+            });
+        }
+
+        // Newest first
+        archives.sort_by_key(|archive| std::cmp::Reverse(archive.created));
+        Ok(archives)
+    }
+
+    // Bytes on disk that belong to this archive alone
+    pub fn archive_size(&self, entry: &ArchiveEntry) -> u64 {
+        match entry.format {
+            ArchiveFormat::Directory | ArchiveFormat::TarZst | ArchiveFormat::EncryptedTarZst => dir_size(&entry.path),
+            ArchiveFormat::Snapshot => self.archive_snapshot_stats(&entry.path).map(|stats| stats.unique_bytes).unwrap_or(0),
+        }
+    }
+
+    pub fn archive_session(&self, session: &str, options: &ArchiveOptions) -> Result<String, Box<dyn Error>> {
+        let (entry, manifest) = self.create_archive(session, options)?;
+        let stored = match entry.format {
+            ArchiveFormat::Snapshot => "stored as a snapshot adding",
+            ArchiveFormat::EncryptedTarZst => "compressed and encrypted to",
+            _ => "compressed to",
+        };
+        Ok(format!(
+            "Session '{}' archived to '{}' ({} files, {} bytes {} {} bytes)",
+            session, entry.name, manifest.file_count, manifest.total_bytes, stored, manifest.archive_bytes
+        ))
+    }
+
+    pub(crate) fn create_archive(&self, session: &str, options: &ArchiveOptions) -> Result<(ArchiveEntry, ArchiveManifest), Box<dyn Error>> {
+        let session_dir = Path::new(&self.config.profile_base).join(session);
+        if !session_dir.exists() {
+            return Err(format!("Session '{}' not found", session).into());
+        }
+
+        let timestamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let archive_name = format!("{}-{}", session, timestamp);
+        let archive_base = Path::new(&self.config.archive_base);
+        if self.resolve_archive(&archive_name).is_ok() {
+            return Err(format!("Archive destination already exists: {}", archive_name).into());
+        }
+        let mode = options.mode.unwrap_or(self.config.archive_mode);
+        let level = options.compression_level.unwrap_or(self.config.archive_compression_level);
+        if !(1..=22).contains(&level) {
+            return Err(format!("Compression level {} is out of range, use 1 (fast) to 22 (small)", level).into());
+        }
+        fs::create_dir_all(archive_base)?;
+        let exclude = options.exclude.clone().unwrap_or_else(|| self.config.archive_exclude.clone());
+        let session_info = self.read_session_info(session).ok();
+        let key = match (options.encrypt, &options.key) {
+            (Some(false), _) => None,
+            (_, Some(key)) => Some(key.clone()),
+            (encrypt, None) => match &self.config.archive_key_file {
+                Some(key_file) => Some(ArchiveKey::KeyFile(key_file.clone())),
+                None if encrypt == Some(true) => return Err("Encryption needs a passphrase, a key file or Config.archive_key_file".into()),
+                None => None,
+            },
+        };
+        // Chunks are shared between snapshots, so they cannot be encrypted per archive
+        if mode == ArchiveMode::Snapshot && key.is_some() {
+            return Err("Snapshot archives cannot be encrypted; use full mode or turn encryption off for this archive".into());
+        }
+
+        let needed = self.estimate_archive_bytes(session, mode, &exclude);
+        self.ensure_free_space(&[(archive_base, needed)])?;
+
+        // Held until the snapshot's chunks are counted, so gc cannot collect them in between
+        let _store = (mode == ArchiveMode::Snapshot).then(lock_store);
+        let format = match (mode, &key) {
+            (ArchiveMode::Snapshot, _) => ArchiveFormat::Snapshot,
+            (ArchiveMode::Full, None) => ArchiveFormat::TarZst,
+            (ArchiveMode::Full, Some(_)) => ArchiveFormat::EncryptedTarZst,
+        };
+        let entry = ArchiveEntry {
+            name: archive_name.clone(),
+            path: archive_base.join(format!("{}{}", archive_name, format.extension())),
+            format,
+        };
+
+        // Written under a hidden name so listings never show a half written archive
+        let partial = archive_base.join(format!(".{}{}{}", archive_name, format.extension(), PARTIAL_SUFFIX));
+        let written = match mode {
+            ArchiveMode::Full => write_tar_zst(&session_dir, &partial, level, &exclude, key.as_ref(), session_info.as_ref()).map(|written| (None, written)),
+            ArchiveMode::Snapshot => self.write_snapshot(&session_dir, &partial, &archive_name, level, &exclude).map(|(index, written)| (Some(index), written)),
+        };
+        let (index, written) = match written {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = fs::remove_file(&partial);
+                return Err(e);
+            }
+        };
+
+        // Only non-sensitive fields stay in the plain text manifest of encrypted archives
+        let encrypted = key.is_some();
+        let manifest = ArchiveManifest {
+            manifest_version: MANIFEST_VERSION,
+            archive: archive_name.clone(),
+            session: session.to_string(),
+            session_info: if encrypted { None } else { session_info },
+            created: Local::now(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            file_count: written.stats.files,
+            total_bytes: written.stats.bytes,
+            archive_bytes: written.archive_bytes,
+            compression: "zstd".to_string(),
+            compression_level: level,
+            content_hash: written.content_hash,
+            files: if encrypted { Vec::new() } else { written.files },
+            encryption: key.as_ref().map(|key| key.kind()),
+        };
+        // The manifest goes first: an archive file without one is still listed, a manifest without its archive is not
+        let finished = write_file_atomic(&entry.manifest_path(), serde_json::to_string_pretty(&manifest)?.as_bytes())
+            .map_err(|e| e.into())
+            .and_then(|_| match &index {
+                Some(index) => self.retain_snapshot(index),
+                None => Ok(()),
+            });
+        if let Err(e) = finished {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+        fs::rename(&partial, &entry.path)?;
+        sync_dir(archive_base)?;
+        Ok((entry, manifest))
+    }
+
+    pub fn restore_archive(&self, archive: &str, new_session_name: Option<&str>, verify: bool, key: Option<&ArchiveKey>, options: &RestoreOptions) -> Result<String, Box<dyn Error>> {
+        let entry = self.resolve_archive(archive)?;
+        if entry.format == ArchiveFormat::EncryptedTarZst && key.is_none() {
+            return Err(Box::new(CursorManagerError::ArchiveKeyRequired { name: entry.name.clone() }));
+        }
+
+        let manifest = entry.read_manifest();
+        // Archives without a manifest have nothing to check against
+        if verify && manifest.is_some() {
+            let verification = self.verify_archive(&entry.name, key)?;
+            if !verification.ok {
+                return Err(Box::new(CursorManagerError::ArchiveCorrupted { name: entry.name.clone(), problems: verification.summary() }));
+            }
+        }
+
+        // Determine the target session name
+        let session_name = match new_session_name {
+            Some(name) => name.to_string(),
+            None => entry.origin().0,
+        };
+        validate_session_name(&session_name)?;
+
+        // Replace and merge into a session that does not exist yet behave like new
+        let session_dir = Path::new(&self.config.profile_base).join(&session_name);
+        let existing = session_dir.exists();
+        if existing && options.mode == RestoreMode::New {
+            return Err(format!("Session '{}' already exists, cannot restore to this name", session_name).into());
+        }
+        if existing {
+            self.ensure_not_running(&session_name)?;
+        }
+        let _slot = if existing { None } else { Some(self.reserve_session_slot(&session_name)?) };
+
+        // Staging holds the whole archive even when only some categories are kept. Replace keeps the old
+        // profile until the swap, and both modes write a safety archive first
+        let profile_base = Path::new(&self.config.profile_base);
+        let archive_base = Path::new(&self.config.archive_base);
+        let mut needs = vec![(profile_base, self.estimate_restore_bytes(&entry))];
+        if existing {
+            needs.push((archive_base, self.estimate_archive_bytes(&session_name, self.safety_archive_mode(), &self.config.archive_exclude)));
+        }
+        self.ensure_free_space(&needs)?;
+
+        // Unpacked next to the profiles first, so a failed unpack never touches the target
+        let staging = staging_path(profile_base, &session_name);
+        fs::create_dir_all(&staging)?;
+        let restored = match entry.format {
+            ArchiveFormat::Directory => copy_profile_entry(&legacy_archive_root(&entry.path), &staging, Path::new(""), &[], &mut CopyStats::default())
+                .map(|_| None)
+                .map_err(|e| e.into()),
+            ArchiveFormat::TarZst | ArchiveFormat::EncryptedTarZst => unpack_tar_zst(&entry, key, &staging).map(|private| private.and_then(|p| p.session_info)),
+            ArchiveFormat::Snapshot => self.restore_snapshot(&entry.path, &staging).map(|_| None),
+        };
+        let placed = restored.and_then(|embedded_session_info| {
+            // Profiles archived without session.json get it back from the manifest
+            let session_file = staging.join(SESSION_FILE);
+            if let Some(session_info) = manifest.and_then(|m| m.session_info).or(embedded_session_info) {
+                if !session_file.exists() {
+                    fs::write(&session_file, serde_json::to_string_pretty(&session_info)?)?;
+                }
+            }
+            self.retarget_staged_profile(&staging, &session_dir)?;
+            self.place_restored_profile(&staging, &session_name, existing, options)
+        });
+        if staging.exists() {
+            let _ = fs::remove_dir_all(&staging);
+        }
+        let outcome = placed?;
+
+        if let Err(e) = self.adopt_restored_profile(&session_name) {
+            tracing::warn!("Restored '{}' but could not update its session record: {}", session_name, e);
+        }
+        if outcome.is_empty() {
+            Ok(format!("Archive '{}' restored to session '{}'", entry.name, session_name))
+        } else {
+            Ok(format!("Archive '{}' restored to session '{}' ({})", entry.name, session_name, outcome))
+        }
+    }
+
+    // A restored profile still names its original session and location; point it at the new one
+    fn adopt_restored_profile(&self, session: &str) -> Result<(), Box<dyn Error>> {
+        let session_dir = self.session_dir(session);
+        let mut session_info = self.read_session_info(session)?;
+        let old_name = session_info.name.clone();
+
+        // Another session may still carry the same id, e.g. when restoring next to the original
+        if self.list_sessions()?.iter().any(|other| other.name != session && other.id == session_info.id) {
+            session_info.id = uuid::Uuid::new_v4().to_string();
+        }
+        session_info.name = session.to_string();
+        session_info.path = session_dir.to_string_lossy().to_string();
+        session_info.disk_usage = None;
+        self.write_session_info(&session_info)?;
+
+        if old_name != session {
+            let _ = fs::remove_file(session_dir.join(format!("{}.code-workspace", old_name)));
+            if !session_info.projects.is_empty() || !session_info.recommended_extensions.is_empty() {
+                self.write_workspace_file(&session_info)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn delete_archive(&self, archive: &str) -> Result<String, Box<dyn Error>> {
+        let entry = self.resolve_archive(archive)?;
+        match entry.format {
+            ArchiveFormat::Directory => fs::remove_dir_all(&entry.path)?,
+            ArchiveFormat::TarZst | ArchiveFormat::EncryptedTarZst => fs::remove_file(&entry.path)?,
+            ArchiveFormat::Snapshot => {
+                self.delete_snapshot(&entry.path)?;
+            }
+        }
+        let _ = fs::remove_file(entry.manifest_path());
+        self.move_archive_pin(&entry.name, None);
+        Ok(format!("Archive '{}' deleted", entry.name))
+    }
+
+    // Give an archive (and its manifest) a new name after its session was renamed
+    pub(crate) fn rename_archive_entry(&self, entry: &ArchiveEntry, new_session: &str, new_name: &str) -> Result<bool, Box<dyn Error>> {
+        let target = entry.path.with_file_name(format!("{}{}", new_name, entry.format.extension()));
+        if target.exists() {
+            tracing::warn!("Not relinking archive '{}': {} already exists", entry.name, target.to_string_lossy());
+            return Ok(false);
+        }
+
+        let manifest = entry.read_manifest();
+        fs::rename(&entry.path, &target)?;
+        let renamed = ArchiveEntry { name: new_name.to_string(), path: target, format: entry.format };
+        if let Some(mut manifest) = manifest {
+            manifest.archive = new_name.to_string();
+            manifest.session = new_session.to_string();
+            if let Some(session_info) = manifest.session_info.as_mut() {
+                session_info.name = new_session.to_string();
+                session_info.path = self.session_dir(new_session).to_string_lossy().to_string();
+            }
+            write_file_atomic(&renamed.manifest_path(), serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+            let _ = fs::remove_file(entry.manifest_path());
+        }
+        self.move_archive_pin(&entry.name, Some(new_name));
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Truncates the file it reads to `keep` bytes just before the first read, like an editor rewriting it
+    struct ShrinkingFile {
+        file: File,
+        path: PathBuf,
+        keep: Option<u64>,
+    }
+
+    impl Read for ShrinkingFile {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if let Some(keep) = self.keep.take() {
+                fs::OpenOptions::new().write(true).open(&self.path)?.set_len(keep)?;
+            }
+            self.file.read(buf)
+        }
+    }
+
+    #[test]
+    fn a_file_shrinking_during_the_write_fails_the_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.vscdb");
+        fs::write(&path, vec![7u8; 3000]).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(&fs::metadata(&path).unwrap());
+        let mut builder = tar::Builder::new(Vec::new());
+
+        let shrinking = ShrinkingFile { file: File::open(&path).unwrap(), path: path.clone(), keep: Some(100) };
+        let error = append_file(&mut builder, &mut header, Path::new("state.vscdb"), shrinking).unwrap_err();
+        assert!(error.to_string().contains("state.vscdb changed while it was being archived"));
+    }
+
+    #[test]
+    fn tar_zst_round_trip_keeps_every_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("profile");
+        fs::create_dir_all(source.join("User")).unwrap();
+        fs::write(source.join("User/settings.json"), "{}").unwrap();
+        fs::write(source.join("state.vscdb"), vec![1u8; 1500]).unwrap();
+        fs::write(source.join("skip.log"), "x").unwrap();
+        let target = dir.path().join("profile.tar.zst");
+
+        let written = write_tar_zst(&source, &target, 3, &["skip.log".to_string()], None, None).unwrap();
+        assert_eq!(written.stats.files, 2);
+
+        let entry = ArchiveEntry { name: "profile".to_string(), path: target, format: ArchiveFormat::TarZst };
+        let unpacked = dir.path().join("unpacked");
+        fs::create_dir_all(&unpacked).unwrap();
+        unpack_tar_zst(&entry, None, &unpacked).unwrap();
+        assert_eq!(fs::read_to_string(unpacked.join("User/settings.json")).unwrap(), "{}");
+        assert_eq!(fs::read(unpacked.join("state.vscdb")).unwrap(), vec![1u8; 1500]);
+        assert!(!unpacked.join("skip.log").exists());
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use crate::archive::{is_private_manifest, legacy_archive_root, open_tar_stream, ArchiveEntry, ArchiveFormat};
+use crate::profile::relative_string;
+use crate::snapshot::{lock_store, read_snapshot_index, safe_relative, set_file_mode};
+use crate::{ArchiveKey, CursorManager};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchiveContentEntry {
+    // Relative to the profile root, '/' separated
+    pub path: String,
+    pub size: u64,
+    pub is_dir: bool,
+    pub modified: Option<DateTime<Local>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ExtractResult {
+    pub destination: String,
+    pub extracted: Vec<String>,
+    // Already present in the destination; never overwritten
+    pub skipped: Vec<String>,
+    // Requested paths with nothing in the archive under them
+    pub not_found: Vec<String>,
+    pub bytes: u64,
+}
+
+// "" selects everything; anything else must stay inside the profile
+fn normalize_prefix(prefix: &str) -> Result<String, Box<dyn Error>> {
+    let trimmed = prefix.trim_matches('/');
+    if trimmed.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(relative_string(safe_relative(trimmed)?))
+}
+
+// `path` is `prefix` itself or somewhere below it
+fn is_under(path: &str, prefix: &str) -> bool {
+    prefix.is_empty() || path == prefix || (path.starts_with(prefix) && path[prefix.len()..].starts_with('/'))
+}
+
+fn unix_time(secs: i64) -> Option<DateTime<Local>> {
+    Local.timestamp_opt(secs, 0).single()
+}
+
+fn walk_directory(root: &Path, relative: &Path, entries: &mut Vec<ArchiveContentEntry>) -> io::Result<()> {
+    let metadata = match fs::symlink_metadata(root.join(relative)) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if !metadata.is_dir() && !metadata.is_file() {
+        return Ok(());
+    }
+    if relative.components().next().is_some() {
+        entries.push(ArchiveContentEntry {
+            path: relative_string(relative),
+            size: if metadata.is_file() { metadata.len() } else { 0 },
+            is_dir: metadata.is_dir(),
+            modified: metadata.modified().ok().map(DateTime::<Local>::from),
+        });
+    }
+    if metadata.is_dir() {
+        for entry in fs::read_dir(root.join(relative))? {
+            walk_directory(root, &relative.join(entry?.file_name()), entries)?;
+        }
+    }
+    Ok(())
+}
+
+impl CursorManager {
+    // Everything at or below `prefix`, read without unpacking the archive
+    pub fn list_archive_contents(&self, archive: &str, prefix: Option<&str>, key: Option<&ArchiveKey>) -> Result<Vec<ArchiveContentEntry>, Box<dyn Error>> {
+        let entry = self.resolve_archive(archive)?;
+        let prefix = normalize_prefix(prefix.unwrap_or(""))?;
+        let mut entries = self.archive_contents(&entry, &prefix, key)?;
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    fn archive_contents(&self, entry: &ArchiveEntry, prefix: &str, key: Option<&ArchiveKey>) -> Result<Vec<ArchiveContentEntry>, Box<dyn Error>> {
+        let mut entries = Vec::new();
+        match entry.format {
+            // Only the subtree under the prefix is walked
+            ArchiveFormat::Directory => walk_directory(&legacy_archive_root(&entry.path), Path::new(prefix), &mut entries)?,
+            ArchiveFormat::TarZst | ArchiveFormat::EncryptedTarZst => {
+                let mut tar = open_tar_stream(entry, key)?;
+                for tar_entry in tar.entries()? {
+                    let tar_entry = tar_entry?;
+                    let header = tar_entry.header();
+                    let entry_type = header.entry_type();
+                    if !entry_type.is_file() && !entry_type.is_dir() {
+                        continue;
+                    }
+                    let tar_path = tar_entry.path()?;
+                    let path = relative_string(&tar_path);
+                    if path.is_empty() || is_private_manifest(&tar_path) || !is_under(&path, prefix) {
+                        continue;
+                    }
+                    entries.push(ArchiveContentEntry {
+                        path,
+                        size: if entry_type.is_file() { header.size()? } else { 0 },
+                        is_dir: entry_type.is_dir(),
+                        modified: header.mtime().ok().and_then(|secs| unix_time(secs as i64)),
+                    });
+                }
+            }
+            ArchiveFormat::Snapshot => {
+                let index = read_snapshot_index(&entry.path)?;
+                entries.extend(index.dirs.into_iter().filter(|dir| is_under(dir, prefix)).map(|path| ArchiveContentEntry {
+                    path,
+                    size: 0,
+                    is_dir: true,
+                    modified: None,
+                }));
+                entries.extend(index.files.into_iter().filter(|file| is_under(&file.path, prefix)).map(|file| ArchiveContentEntry {
+                    path: file.path,
+                    size: file.size,
+                    is_dir: false,
+                    modified: file.modified.and_then(unix_time),
+                }));
+            }
+        }
+        Ok(entries)
+    }
+
+    // Read a few small files out of an archive, stopping as soon as all of them have been seen.
+    // Paths missing from the archive are absent from the result
+    pub(crate) fn read_archive_files(&self, entry: &ArchiveEntry, paths: &[&str], key: Option<&ArchiveKey>) -> Result<BTreeMap<String, Vec<u8>>, Box<dyn Error>> {
+        let mut contents = BTreeMap::new();
+        match entry.format {
+            ArchiveFormat::Directory => {
+                let root = legacy_archive_root(&entry.path);
+                for path in paths {
+                    let file = root.join(safe_relative(path)?);
+                    if file.is_file() {
+                        contents.insert(path.to_string(), fs::read(file)?);
+                    }
+                }
+            }
+            ArchiveFormat::TarZst | ArchiveFormat::EncryptedTarZst => {
+                let mut tar = open_tar_stream(entry, key)?;
+                for tar_entry in tar.entries()? {
+                    let mut tar_entry = tar_entry?;
+                    if !tar_entry.header().entry_type().is_file() {
+                        continue;
+                    }
+                    let path = relative_string(&tar_entry.path()?);
+                    if paths.contains(&path.as_str()) {
+                        let mut data = Vec::new();
+                        tar_entry.read_to_end(&mut data)?;
+                        contents.insert(path, data);
+                        if contents.len() == paths.len() {
+                            break;
+                        }
+                    }
+                }
+            }
+            ArchiveFormat::Snapshot => {
+                let _store = lock_store();
+                let index = read_snapshot_index(&entry.path)?;
+                for file in index.files.iter().filter(|file| paths.contains(&file.path.as_str())) {
+                    let mut data = Vec::new();
+                    self.read_snapshot_file(file, &mut data)?;
+                    contents.insert(file.path.clone(), data);
+                }
+            }
+        }
+        Ok(contents)
+    }
+
+    // Copy the given files or directories out of an archive into `destination`, keeping their relative
+    // paths. Existing files in the destination are left alone and reported as skipped
+    pub fn extract_from_archive(&self, archive: &str, paths: &[String], destination: &Path, key: Option<&ArchiveKey>) -> Result<ExtractResult, Box<dyn Error>> {
+        let entry = self.resolve_archive(archive)?;
+        let requested = paths.iter().map(|path| normalize_prefix(path)).collect::<Result<Vec<_>, _>>()?;
+        if requested.is_empty() {
+            return Err("No paths to extract".into());
+        }
+        fs::create_dir_all(destination)?;
+
+        let mut result = ExtractResult { destination: destination.to_string_lossy().to_string(), ..Default::default() };
+        let mut matched: BTreeSet<&str> = BTreeSet::new();
+        let mut wanted = |path: &str| {
+            let hits: Vec<&str> = requested.iter().filter(|prefix| is_under(path, prefix)).map(|prefix| prefix.as_str()).collect();
+            matched.extend(hits.iter().copied());
+            !hits.is_empty()
+        };
+
+        match entry.format {
+            ArchiveFormat::Directory => {
+                let root = legacy_archive_root(&entry.path);
+                let mut entries = Vec::new();
+                for prefix in &requested {
+                    walk_directory(&root, Path::new(prefix), &mut entries)?;
+                }
+                // Overlapping requests walk the same files twice
+                entries.sort_by(|a, b| a.path.cmp(&b.path));
+                entries.dedup_by(|a, b| a.path == b.path);
+                for content in entries {
+                    if !wanted(&content.path) {
+                        continue;
+                    }
+                    let target = destination.join(&content.path);
+                    if content.is_dir {
+                        fs::create_dir_all(&target)?;
+                    } else if target.exists() {
+                        result.skipped.push(content.path);
+                    } else {
+                        if let Some(parent) = target.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        result.bytes += fs::copy(root.join(&content.path), &target)?;
+                        result.extracted.push(content.path);
+                    }
+                }
+            }
+            // One pass over the stream, writing only the matching entries
+            ArchiveFormat::TarZst | ArchiveFormat::EncryptedTarZst => {
+                let mut tar = open_tar_stream(&entry, key)?;
+                for tar_entry in tar.entries()? {
+                    let mut tar_entry = tar_entry?;
+                    let entry_type = tar_entry.header().entry_type();
+                    if !entry_type.is_file() && !entry_type.is_dir() {
+                        continue;
+                    }
+                    let tar_path = tar_entry.path()?.to_path_buf();
+                    let path = relative_string(&tar_path);
+                    if path.is_empty() || is_private_manifest(&tar_path) || !wanted(&path) {
+                        continue;
+                    }
+                    if entry_type.is_file() && destination.join(&path).exists() {
+                        result.skipped.push(path);
+                        continue;
+                    }
+                    let size = tar_entry.header().size()?;
+                    // unpack_in skips entries that would land outside `destination`
+                    if tar_entry.unpack_in(destination)? && entry_type.is_file() {
+                        result.bytes += size;
+                        result.extracted.push(path);
+                    }
+                }
+            }
+            ArchiveFormat::Snapshot => {
+                let _store = lock_store();
+                let index = read_snapshot_index(&entry.path)?;
+                for dir in index.dirs.iter().filter(|dir| wanted(dir)) {
+                    fs::create_dir_all(destination.join(safe_relative(dir)?))?;
+                }
+                for file in index.files.iter().filter(|file| wanted(&file.path)) {
+                    let target = destination.join(safe_relative(&file.path)?);
+                    if target.exists() {
+                        result.skipped.push(file.path.clone());
+                        continue;
+                    }
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    let mut output = File::create(&target)?;
+                    if let Err(e) = self.read_snapshot_file(file, &mut output) {
+                        drop(output);
+                        let _ = fs::remove_file(&target);
+                        return Err(e);
+                    }
+                    if let Some(modified) = file.modified.filter(|secs| *secs >= 0) {
+                        let _ = output.set_modified(UNIX_EPOCH + Duration::from_secs(modified as u64));
+                    }
+                    drop(output);
+                    set_file_mode(&target, file.mode)?;
+                    result.bytes += file.size;
+                    result.extracted.push(file.path.clone());
+                }
+            }
+        }
+
+        result.not_found = requested.iter().filter(|prefix| !matched.contains(prefix.as_str())).cloned().collect();
+        tracing::info!(
+            "Extracted {} file(s) from archive '{}' to {} ({} skipped)",
+            result.extracted.len(),
+            entry.name,
+            result.destination,
+            result.skipped.len()
+        );
+        Ok(result)
+    }
+}
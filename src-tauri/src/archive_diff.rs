@@ -0,0 +1,351 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::archive::{is_private_manifest, legacy_archive_root, open_tar_stream, ArchiveFormat, HashingReader};
+use crate::extensions::normalize_extension_id;
+use crate::profile::{path_matches, relative_string, KEYBINDINGS_FILE, SESSION_FILE, SETTINGS_FILE};
+use crate::snapshot::read_snapshot_index;
+use crate::{jsonc, validate_session_name, ArchiveKey, CursorManager, CursorManagerError};
+
+// One side of a diff
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffSource {
+    Archive(String),
+    Session(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiffFile {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChangedFile {
+    pub path: String,
+    pub old_size: u64,
+    pub new_size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KeyChange {
+    pub key: String,
+    pub change: ChangeKind,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+// Key-level changes of a modified settings, keybindings, extensions.json or session.json
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StructuredDiff {
+    pub path: String,
+    pub changes: Vec<KeyChange>,
+    // Set when either side could not be parsed; the file is still listed as modified
+    pub error: Option<String>,
+}
+
+// Everything is reported from `a` to `b`: added files exist only in `b`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchiveDiff {
+    pub a: String,
+    pub b: String,
+    pub added: Vec<DiffFile>,
+    pub removed: Vec<DiffFile>,
+    pub modified: Vec<ChangedFile>,
+    pub unchanged: u64,
+    pub structured: Vec<StructuredDiff>,
+}
+
+// EXTENSIONS_DIR/EXTENSIONS_MANIFEST
+const EXTENSIONS_LIST_FILE: &str = "extensions/extensions.json";
+const STRUCTURED_FILES: [&str; 4] = [SETTINGS_FILE, KEYBINDINGS_FILE, EXTENSIONS_LIST_FILE, SESSION_FILE];
+
+// path -> (size, sha256 hex)
+type FileHashes = BTreeMap<String, (u64, String)>;
+
+fn hash_tree(root: &Path, relative: &Path, exclude: &[String], files: &mut FileHashes) -> io::Result<()> {
+    for entry in fs::read_dir(root.join(relative))? {
+        let entry = entry?;
+        let entry_relative = relative.join(entry.file_name());
+        let path = relative_string(&entry_relative);
+        if exclude.iter().any(|pattern| path_matches(&path, pattern)) {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            hash_tree(root, &entry_relative, exclude, files)?;
+        } else if file_type.is_file() {
+            let mut reader = HashingReader::new(File::open(entry.path())?);
+            io::copy(&mut reader, &mut io::sink())?;
+            files.insert(path, (reader.read, reader.hex_digest()));
+        }
+    }
+    Ok(())
+}
+
+fn read_tree_files(root: &Path, paths: &[&str]) -> BTreeMap<String, Vec<u8>> {
+    paths.iter().filter_map(|path| fs::read(root.join(path)).ok().map(|data| (path.to_string(), data))).collect()
+}
+
+impl DiffSource {
+    fn label(&self) -> String {
+        match self {
+            DiffSource::Archive(name) => format!("archive {}", name),
+            DiffSource::Session(name) => format!("session {}", name),
+        }
+    }
+}
+
+// Entries of a known file keyed so that reordering is not reported as a change
+fn keyed_entries(path: &str, value: Value) -> Result<BTreeMap<String, Value>, String> {
+    match (path, value) {
+        (KEYBINDINGS_FILE, Value::Array(bindings)) => {
+            let mut entries = BTreeMap::new();
+            for binding in bindings {
+                let field = |name: &str| binding.get(name).and_then(Value::as_str).unwrap_or("").to_string();
+                let mut key = format!("{} {}", field("key"), field("command"));
+                let when = field("when");
+                if !when.is_empty() {
+                    key = format!("{} when {}", key, when);
+                }
+                // Identical bindings listed twice stay distinguishable
+                let mut unique = key.clone();
+                let mut n = 2;
+                while entries.contains_key(&unique) {
+                    unique = format!("{} #{}", key, n);
+                    n += 1;
+                }
+                entries.insert(unique, binding);
+            }
+            Ok(entries)
+        }
+        (EXTENSIONS_LIST_FILE, Value::Array(extensions)) => Ok(extensions
+            .into_iter()
+            .map(|extension| {
+                let id = extension.pointer("/identifier/id").and_then(Value::as_str).map(normalize_extension_id).unwrap_or_default();
+                (id, extension)
+            })
+            .collect()),
+        (_, Value::Object(map)) => Ok(map.into_iter().collect()),
+        _ => Err(format!("unexpected top-level structure in {}", path)),
+    }
+}
+
+fn structured_diff(path: &str, old: &[u8], new: &[u8]) -> StructuredDiff {
+    let parse = |data: &[u8]| {
+        let default = if path == KEYBINDINGS_FILE || path == EXTENSIONS_LIST_FILE { Value::Array(Vec::new()) } else { Value::Object(Default::default()) };
+        jsonc::parse_or(&String::from_utf8_lossy(data), default).map_err(|e| e.to_string()).and_then(|value| keyed_entries(path, value))
+    };
+    let (mut old, new) = match (parse(old), parse(new)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(e), _) | (_, Err(e)) => return StructuredDiff { path: path.to_string(), changes: Vec::new(), error: Some(e) },
+    };
+
+    let mut changes = Vec::new();
+    for (key, new_value) in new {
+        match old.remove(&key) {
+            Some(old_value) if old_value == new_value => {}
+            Some(old_value) => changes.push(KeyChange { key, change: ChangeKind::Modified, old: Some(old_value), new: Some(new_value) }),
+            None => changes.push(KeyChange { key, change: ChangeKind::Added, old: None, new: Some(new_value) }),
+        }
+    }
+    changes.extend(old.into_iter().map(|(key, old_value)| KeyChange { key, change: ChangeKind::Removed, old: Some(old_value), new: None }));
+    changes.sort_by(|a, b| a.key.cmp(&b.key));
+    StructuredDiff { path: path.to_string(), changes, error: None }
+}
+
+impl CursorManager {
+    // Hashes of every file on one side. Live sessions skip Config.archive_exclude so caches that never
+    // get archived do not show up as added
+    fn diff_side_files(&self, source: &DiffSource, key: Option<&ArchiveKey>) -> Result<FileHashes, Box<dyn Error>> {
+        let mut files = FileHashes::new();
+        match source {
+            DiffSource::Session(name) => {
+                validate_session_name(name)?;
+                let dir = self.session_dir(name);
+                if !dir.is_dir() {
+                    return Err(Box::new(CursorManagerError::SessionNotFound { name: name.clone() }));
+                }
+                hash_tree(&dir, Path::new(""), &self.config.archive_exclude, &mut files)?;
+            }
+            DiffSource::Archive(name) => {
+                let entry = self.resolve_archive(name)?;
+                match entry.format {
+                    ArchiveFormat::Directory => hash_tree(&legacy_archive_root(&entry.path), Path::new(""), &[], &mut files)?,
+                    ArchiveFormat::Snapshot => {
+                        let index = read_snapshot_index(&entry.path)?;
+                        files.extend(index.files.into_iter().map(|file| (file.path, (file.size, file.sha256))));
+                    }
+                    ArchiveFormat::TarZst | ArchiveFormat::EncryptedTarZst => {
+                        // Manifests with per-file checksums save reading the archive
+                        let manifest = entry.read_manifest().filter(|m| entry.format == ArchiveFormat::TarZst && (!m.files.is_empty() || m.file_count == 0));
+                        if let Some(manifest) = manifest {
+                            files.extend(manifest.files.into_iter().map(|file| (file.path, (file.size, file.sha256))));
+                            return Ok(files);
+                        }
+                        let mut tar = open_tar_stream(&entry, key)?;
+                        for tar_entry in tar.entries()? {
+                            let tar_entry = tar_entry?;
+                            if !tar_entry.header().entry_type().is_file() {
+                                continue;
+                            }
+                            let tar_path = tar_entry.path()?.to_path_buf();
+                            if is_private_manifest(&tar_path) {
+                                continue;
+                            }
+                            let mut reader = HashingReader::new(tar_entry);
+                            io::copy(&mut reader, &mut io::sink())?;
+                            files.insert(relative_string(&tar_path), (reader.read, reader.hex_digest()));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    fn diff_side_contents(&self, source: &DiffSource, paths: &[&str], key: Option<&ArchiveKey>) -> Result<BTreeMap<String, Vec<u8>>, Box<dyn Error>> {
+        match source {
+            DiffSource::Session(name) => Ok(read_tree_files(&self.session_dir(name), paths)),
+            DiffSource::Archive(name) => self.read_archive_files(&self.resolve_archive(name)?, paths, key),
+        }
+    }
+
+    // Compare two archives, or an archive and a live session. `key` opens whichever sides are encrypted
+    pub fn diff_archive(&self, a: &DiffSource, b: &DiffSource, key: Option<&ArchiveKey>) -> Result<ArchiveDiff, Box<dyn Error>> {
+        let mut old_files = self.diff_side_files(a, key)?;
+        let new_files = self.diff_side_files(b, key)?;
+
+        let mut diff = ArchiveDiff {
+            a: a.label(),
+            b: b.label(),
+            added: Vec::new(),
+            removed: Vec::new(),
+            modified: Vec::new(),
+            unchanged: 0,
+            structured: Vec::new(),
+        };
+        for (path, (new_size, new_hash)) in new_files {
+            match old_files.remove(&path) {
+                Some((_, old_hash)) if old_hash == new_hash => diff.unchanged += 1,
+                Some((old_size, _)) => diff.modified.push(ChangedFile { path, old_size, new_size }),
+                None => diff.added.push(DiffFile { path, size: new_size }),
+            }
+        }
+        diff.removed = old_files.into_iter().map(|(path, (size, _))| DiffFile { path, size }).collect();
+
+        let structured: Vec<&str> = STRUCTURED_FILES.iter().copied().filter(|path| diff.modified.iter().any(|file| file.path == *path)).collect();
+        if !structured.is_empty() {
+            let old_contents = self.diff_side_contents(a, &structured, key)?;
+            let new_contents = self.diff_side_contents(b, &structured, key)?;
+            for path in structured {
+                if let (Some(old), Some(new)) = (old_contents.get(path), new_contents.get(path)) {
+                    diff.structured.push(structured_diff(path, old, new));
+                }
+            }
+        }
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use serde_json::json;
+    use super::{structured_diff, ArchiveDiff, ChangeKind, DiffSource};
+    use crate::profile::{KEYBINDINGS_FILE, SETTINGS_FILE};
+    use crate::test_support::{manager, session};
+    use crate::{ArchiveMode, ArchiveOptions, CursorManager};
+
+    fn paths<T>(files: &[T], path: impl Fn(&T) -> &str) -> Vec<&str> {
+        files.iter().map(path).collect()
+    }
+
+    fn assert_edits(diff: &ArchiveDiff) {
+        assert_eq!(paths(&diff.added, |file| &file.path), vec!["User/snippets/new.json"]);
+        assert_eq!(paths(&diff.removed, |file| &file.path), vec![KEYBINDINGS_FILE]);
+        assert_eq!(paths(&diff.modified, |file| &file.path), vec![SETTINGS_FILE]);
+        // session.json
+        assert_eq!(diff.unchanged, 1);
+
+        assert_eq!(diff.structured.len(), 1);
+        let settings = &diff.structured[0];
+        assert_eq!(settings.path, SETTINGS_FILE);
+        assert_eq!(settings.error, None);
+        let changes: Vec<(&str, ChangeKind)> = settings.changes.iter().map(|change| (change.key.as_str(), change.change)).collect();
+        assert_eq!(changes, vec![
+            ("editor.fontSize", ChangeKind::Modified),
+            ("editor.tabSize", ChangeKind::Removed),
+            ("workbench.colorTheme", ChangeKind::Added),
+        ]);
+        assert_eq!(settings.changes[0].old, Some(json!(12)));
+        assert_eq!(settings.changes[0].new, Some(json!(14)));
+    }
+
+    // Archive "work" under the name "before" and edit the live profile afterwards
+    fn archive_then_edit(manager: &CursorManager) {
+        let dir = session(manager, "work", &[
+            (SETTINGS_FILE, "{\n    \"editor.fontSize\": 12,\n    \"editor.tabSize\": 4\n}"),
+            (KEYBINDINGS_FILE, "[]"),
+        ]);
+        let archived = manager.create_archive("work", &ArchiveOptions { mode: Some(ArchiveMode::Full), ..Default::default() }).unwrap().0;
+        manager.rename_archive_entry(&manager.resolve_archive(&archived.name).unwrap(), "work", "before").unwrap();
+
+        fs::write(dir.join(SETTINGS_FILE), "{\n    // bigger\n    \"editor.fontSize\": 14,\n    \"workbench.colorTheme\": \"Dark\"\n}").unwrap();
+        fs::remove_file(dir.join(KEYBINDINGS_FILE)).unwrap();
+        fs::create_dir_all(dir.join("User/snippets")).unwrap();
+        fs::write(dir.join("User/snippets/new.json"), "{}").unwrap();
+    }
+
+    #[test]
+    fn diff_against_the_live_session() {
+        let (_dir, manager) = manager();
+        archive_then_edit(&manager);
+
+        let diff = manager.diff_archive(&DiffSource::Archive("before".to_string()), &DiffSource::Session("work".to_string()), None).unwrap();
+        assert_eq!(diff.a, "archive before");
+        assert_eq!(diff.b, "session work");
+        assert_edits(&diff);
+    }
+
+    #[test]
+    fn diff_between_a_full_archive_and_a_snapshot() {
+        let (_dir, manager) = manager();
+        archive_then_edit(&manager);
+        let after = manager.create_archive("work", &ArchiveOptions { mode: Some(ArchiveMode::Snapshot), ..Default::default() }).unwrap().0.name;
+
+        let diff = manager.diff_archive(&DiffSource::Archive("before".to_string()), &DiffSource::Archive(after.clone()), None).unwrap();
+        assert_edits(&diff);
+
+        // The other way round, additions become removals
+        let reverse = manager.diff_archive(&DiffSource::Archive(after), &DiffSource::Archive("before".to_string()), None).unwrap();
+        assert_eq!(paths(&reverse.removed, |file| &file.path), vec!["User/snippets/new.json"]);
+        assert_eq!(paths(&reverse.added, |file| &file.path), vec![KEYBINDINGS_FILE]);
+    }
+
+    #[test]
+    fn reordered_keybindings_are_not_a_change() {
+        let old = br#"[{ "key": "f5", "command": "run" }, { "key": "f6", "command": "debug" }]"#;
+        let new = br#"[{ "key": "f6", "command": "debug" }, { "key": "f5", "command": "run", "when": "editorFocus" }]"#;
+        let diff = structured_diff(KEYBINDINGS_FILE, old, new);
+        let changes: Vec<(&str, ChangeKind)> = diff.changes.iter().map(|change| (change.key.as_str(), change.change)).collect();
+        assert_eq!(changes, vec![("f5 run", ChangeKind::Removed), ("f5 run when editorFocus", ChangeKind::Added)]);
+
+        let unparsable = structured_diff(SETTINGS_FILE, b"{}", b"[1]");
+        assert!(unparsable.changes.is_empty());
+        assert!(unparsable.error.is_some());
+    }
+}
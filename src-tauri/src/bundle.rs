@@ -0,0 +1,613 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use crate::extensions::{normalize_extension_id, EXTENSIONS_MANIFEST};
+use crate::profile::{path_matches, relative_string, EXTENSIONS_DIR, KEYBINDINGS_FILE, SESSION_FILE, SETTINGS_FILE, SNIPPETS_DIR};
+use crate::snapshot::safe_relative;
+use crate::staging::{commit_staged, staging_path, sync_dir, PARTIAL_SUFFIX};
+use crate::vsix::{install_vsix_into, read_vsix, VSIX_EXTENSION_PREFIX};
+use crate::{jsonc, rewrite_path_prefix, validate_session_name, CursorManager, CursorManagerError, SessionInfo};
+
+// Layout written by this version. Bundles also name the oldest reader that can import them, so
+// additions that older readers can ignore do not lock them out
+const BUNDLE_VERSION: u32 = 1;
+const MIN_READER_VERSION: u32 = 1;
+
+const BUNDLE_MANIFEST: &str = "bundle.json";
+const PROFILE_PREFIX: &str = "profile/";
+const VSIX_PREFIX: &str = "vsix/";
+const WORKSPACE_ENTRY: &str = "workspace.code-workspace";
+// Profile paths that travel in a bundle; state, caches and extension folders do not
+const BUNDLED_PATHS: [&str; 3] = [SETTINGS_FILE, KEYBINDINGS_FILE, SNIPPETS_DIR];
+// Setting and variable names ending in one of these are secrets. Compared in lowercase without '_' and '-'
+const SECRET_SUFFIXES: [&str; 9] = ["token", "password", "passwd", "secret", "apikey", "accesskey", "privatekey", "authorization", "credentials"];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportOptions {
+    // Package installed extensions as .vsix files so the importer does not need a marketplace
+    #[serde(default)]
+    pub include_vsix: bool,
+    #[serde(default = "default_true")]
+    pub include_workspace: bool,
+    // Keep settings and environment variables that look like tokens, passwords or keys
+    #[serde(default)]
+    pub include_secrets: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            include_vsix: false,
+            include_workspace: true,
+            include_secrets: false,
+        }
+    }
+}
+
+// The parts of session.json that mean something on another machine
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BundleSessionInfo {
+    #[serde(default)]
+    pub projects: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub recommended_extensions: Vec<String>,
+    #[serde(default)]
+    pub disabled_extensions: Vec<String>,
+    #[serde(default)]
+    pub launch_args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BundledExtension {
+    pub id: String,
+    pub version: String,
+    // Entry of the packaged .vsix; without one the extension is only recommended on import
+    #[serde(default)]
+    pub vsix: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub min_reader_version: u32,
+    pub app_version: String,
+    pub created: DateTime<Local>,
+    pub session: String,
+    pub session_info: BundleSessionInfo,
+    // Profile files stored under profile/, relative to the profile root
+    pub files: Vec<String>,
+    pub extensions: Vec<BundledExtension>,
+    pub workspace: bool,
+    pub secrets_excluded: bool,
+    // "<file>: <key>" of every value left out as a secret
+    #[serde(default)]
+    pub redacted: Vec<String>,
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let name = key.rsplit('.').next().unwrap_or(key);
+    let normalized: String = name.chars().filter(|c| *c != '_' && *c != '-').collect::<String>().to_lowercase();
+    SECRET_SUFFIXES.iter().any(|suffix| normalized.ends_with(suffix))
+}
+
+// Drop secret members at any depth, recording where they were
+fn scrub_value(value: &mut Value, path: &str, redacted: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|key, _| {
+                let secret = is_secret_key(key);
+                if secret {
+                    redacted.push(format!("{}.{}", path, key));
+                }
+                !secret
+            });
+            for (key, child) in map.iter_mut() {
+                scrub_value(child, &format!("{}.{}", path, key), redacted);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                scrub_value(item, &format!("{}[{}]", path, index), redacted);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Remove secrets from a settings or workspace document through jsonc patches, so comments elsewhere survive
+fn scrub_settings(text: &str, file: &str, redacted: &mut Vec<String>) -> Result<String, Box<dyn Error>> {
+    let map = match jsonc::parse_or(text, Value::Object(Map::new()))? {
+        Value::Object(map) => map,
+        _ => return Ok(text.to_string()),
+    };
+    let mut patch = Map::new();
+    for (key, mut value) in map {
+        if is_secret_key(&key) {
+            redacted.push(format!("{}: {}", file, key));
+            patch.insert(key, Value::Null);
+            continue;
+        }
+        let mut found = Vec::new();
+        scrub_value(&mut value, &key, &mut found);
+        if !found.is_empty() {
+            redacted.extend(found.into_iter().map(|path| format!("{}: {}", file, path)));
+            patch.insert(key, value);
+        }
+    }
+    if patch.is_empty() {
+        return Ok(text.to_string());
+    }
+    Ok(jsonc::patch_object(text, &patch)?)
+}
+
+// Regular files below `root/relative`, symlinks are skipped
+fn collect_files(root: &Path, relative: &Path, files: &mut Vec<String>) -> io::Result<()> {
+    let metadata = match fs::symlink_metadata(root.join(relative)) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if metadata.is_file() {
+        files.push(relative_string(relative));
+    } else if metadata.is_dir() {
+        for entry in fs::read_dir(root.join(relative))? {
+            collect_files(root, &relative.join(entry?.file_name()), files)?;
+        }
+    }
+    Ok(())
+}
+
+// Zip an installed extension folder the way a .vsix lays it out
+fn pack_vsix(extension_dir: &Path, output: File) -> Result<File, Box<dyn Error>> {
+    let mut files = Vec::new();
+    collect_files(extension_dir, Path::new(""), &mut files)?;
+    let mut zip = ZipWriter::new(output);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for file in files {
+        zip.start_file(format!("{}{}", VSIX_EXTENSION_PREFIX, file), options)?;
+        io::copy(&mut File::open(extension_dir.join(&file))?, &mut zip)?;
+    }
+    Ok(zip.finish()?)
+}
+
+// Recommend `ids` in a workspace file on top of whatever it already recommends
+fn add_recommendations(text: &str, ids: &[String]) -> Result<String, Box<dyn Error>> {
+    let workspace = jsonc::parse_or(text, Value::Object(Map::new()))?;
+    let mut extensions = match workspace.get("extensions") {
+        Some(Value::Object(extensions)) => extensions.clone(),
+        _ => Map::new(),
+    };
+    let mut recommendations = extensions.get("recommendations").and_then(Value::as_array).cloned().unwrap_or_default();
+    for id in ids {
+        if !recommendations.iter().any(|existing| existing.as_str().map(normalize_extension_id).as_deref() == Some(id.as_str())) {
+            recommendations.push(Value::String(id.clone()));
+        }
+    }
+    extensions.insert("recommendations".to_string(), Value::Array(recommendations));
+    let mut patch = Map::new();
+    patch.insert("extensions".to_string(), Value::Object(extensions));
+    Ok(jsonc::patch_object(text, &patch)?)
+}
+
+fn read_manifest_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, path: &Path) -> Result<BundleManifest, Box<dyn Error>> {
+    let mut data = String::new();
+    archive
+        .by_name(BUNDLE_MANIFEST)
+        .map_err(|_| format!("{} is not a session bundle: {} is missing", path.to_string_lossy(), BUNDLE_MANIFEST))?
+        .read_to_string(&mut data)?;
+    let manifest: BundleManifest = serde_json::from_str(&data)?;
+    if manifest.min_reader_version > BUNDLE_VERSION {
+        return Err(Box::new(CursorManagerError::IncompatibleBundle {
+            path: path.to_string_lossy().to_string(),
+            version: manifest.format_version,
+            supported: BUNDLE_VERSION,
+        }));
+    }
+    if manifest.format_version > BUNDLE_VERSION {
+        tracing::info!("Bundle {} uses format {}, importing the parts format {} knows", path.to_string_lossy(), manifest.format_version, BUNDLE_VERSION);
+    }
+    Ok(manifest)
+}
+
+// The manifest of a bundle, after checking this version can import it
+pub fn read_bundle_manifest(path: &Path) -> Result<BundleManifest, Box<dyn Error>> {
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(|e| format!("{} is not a session bundle: {}", path.to_string_lossy(), e))?;
+    read_manifest_entry(&mut archive, path)
+}
+
+impl CursorManager {
+    // Write settings, keybindings, snippets, the extension list and optionally VSIXes and the workspace
+    // file of `session` to a single bundle file at `path`
+    pub fn export_session(&self, session: &str, path: &Path, options: &ExportOptions) -> Result<String, Box<dyn Error>> {
+        let session_info = self.read_session_info(session)?;
+        let session_dir = self.session_dir(session);
+        let mut redacted = Vec::new();
+
+        let mut bundle_info = BundleSessionInfo {
+            projects: session_info.projects.clone(),
+            tags: session_info.tags.clone(),
+            notes: session_info.notes.clone(),
+            color: session_info.color.clone(),
+            icon: session_info.icon.clone(),
+            recommended_extensions: session_info.recommended_extensions.clone(),
+            disabled_extensions: session_info.disabled_extensions.clone(),
+            launch_args: session_info.launch_args.clone(),
+            env: session_info.env.clone(),
+        };
+        if !options.include_secrets {
+            bundle_info.env.retain(|name, _| {
+                let secret = is_secret_key(name);
+                if secret {
+                    redacted.push(format!("{}: env.{}", SESSION_FILE, name));
+                }
+                !secret
+            });
+        }
+
+        let mut files = Vec::new();
+        for relative in BUNDLED_PATHS {
+            collect_files(&session_dir, Path::new(relative), &mut files)?;
+        }
+        let mut contents = Vec::new();
+        for file in &files {
+            let data = fs::read(session_dir.join(file))?;
+            let data = if file == SETTINGS_FILE && !options.include_secrets {
+                scrub_settings(&String::from_utf8_lossy(&data), file, &mut redacted)?.into_bytes()
+            } else {
+                data
+            };
+            contents.push(data);
+        }
+
+        let workspace_path = self.workspace_file_path(session);
+        let workspace = if options.include_workspace && workspace_path.is_file() {
+            let text = fs::read_to_string(&workspace_path)?;
+            if options.include_secrets {
+                Some(text)
+            } else {
+                Some(scrub_settings(&text, WORKSPACE_ENTRY, &mut redacted)?)
+            }
+        } else {
+            None
+        };
+
+        let cached = if options.include_vsix { self.list_vsix_cache()? } else { Vec::new() };
+        let installed: Vec<_> = self.list_session_extensions(session)?.into_iter().filter(|extension| extension.present).collect();
+        let extensions: Vec<BundledExtension> = installed
+            .iter()
+            .map(|extension| BundledExtension {
+                id: extension.id.clone(),
+                version: extension.version.clone(),
+                vsix: options.include_vsix.then(|| format!("{}{}-{}.vsix", VSIX_PREFIX, extension.id, extension.version)),
+            })
+            .collect();
+
+        let manifest = BundleManifest {
+            format_version: BUNDLE_VERSION,
+            min_reader_version: MIN_READER_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            created: Local::now(),
+            session: session.to_string(),
+            session_info: bundle_info,
+            files,
+            extensions,
+            workspace: workspace.is_some(),
+            secrets_excluded: !options.include_secrets,
+            redacted,
+        };
+
+        // Written under a hidden name next to the target and renamed once complete
+        let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+        let file_name = match path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Err(format!("Export path '{}' has no file name", path.to_string_lossy()).into()),
+        };
+        fs::create_dir_all(parent)?;
+        let partial = parent.join(format!(".{}.{}{}", file_name, uuid::Uuid::new_v4(), PARTIAL_SUFFIX));
+
+        let written = (|| -> Result<(), Box<dyn Error>> {
+            let mut zip = ZipWriter::new(File::create(&partial)?);
+            let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+            // VSIXes are zip files already
+            let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+            zip.start_file(BUNDLE_MANIFEST, deflated)?;
+            zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+            for (file, data) in manifest.files.iter().zip(&contents) {
+                zip.start_file(format!("{}{}", PROFILE_PREFIX, file), deflated)?;
+                zip.write_all(data)?;
+            }
+            if let Some(workspace) = &workspace {
+                zip.start_file(WORKSPACE_ENTRY, deflated)?;
+                zip.write_all(workspace.as_bytes())?;
+            }
+            for (bundled, extension) in manifest.extensions.iter().zip(&installed) {
+                let entry = match &bundled.vsix {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                let mut vsix = match cached.iter().find(|package| package.id == extension.id && package.version == extension.version) {
+                    Some(package) => File::open(&package.path)?,
+                    None => pack_vsix(Path::new(&extension.path), tempfile::tempfile()?)?,
+                };
+                vsix.seek(SeekFrom::Start(0))?;
+                zip.start_file(entry.as_str(), stored)?;
+                io::copy(&mut vsix, &mut zip)?;
+            }
+            zip.finish()?.sync_all()?;
+            fs::rename(&partial, path)?;
+            sync_dir(parent)?;
+            Ok(())
+        })();
+        if let Err(e) = written {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+
+        let packaged = manifest.extensions.iter().filter(|extension| extension.vsix.is_some()).count();
+        Ok(format!(
+            "Session '{}' exported to '{}' ({} files, {} extensions of which {} packaged, {} secret value(s) left out)",
+            session,
+            path.to_string_lossy(),
+            manifest.files.len(),
+            manifest.extensions.len(),
+            packaged,
+            manifest.redacted.len()
+        ))
+    }
+
+    // Create a session from a bundle written by export_session. `name` defaults to the exported session's name
+    pub fn import_session(&self, path: &Path, name: Option<&str>) -> Result<String, Box<dyn Error>> {
+        let mut archive = ZipArchive::new(File::open(path)?).map_err(|e| format!("{} is not a session bundle: {}", path.to_string_lossy(), e))?;
+        let manifest = read_manifest_entry(&mut archive, path)?;
+
+        let name = name.unwrap_or(&manifest.session).to_string();
+        validate_session_name(&name)?;
+        let session_dir = self.session_dir(&name);
+        if session_dir.exists() {
+            return Err(Box::new(CursorManagerError::SessionExists { name }));
+        }
+        let _slot = self.reserve_session_slot(&name)?;
+
+        let profile_base = Path::new(&self.config.profile_base);
+        let needed = (0..archive.len()).filter_map(|index| archive.by_index(index).ok().map(|entry| entry.size())).sum();
+        self.ensure_free_space(&[(profile_base, needed)])?;
+
+        // Built under a hidden name and renamed once complete, like a clone
+        let staging = staging_path(profile_base, &name);
+        let mut missing = Vec::new();
+        let imported = (|| -> Result<SessionInfo, Box<dyn Error>> {
+            fs::create_dir_all(&staging)?;
+            for file in &manifest.files {
+                let relative = safe_relative(file)?;
+                // A bundle only carries settings, keybindings and snippets; anything else would land
+                // in the profile unchecked, extensions included
+                if !BUNDLED_PATHS.iter().any(|allowed| path_matches(&relative_string(relative), allowed)) {
+                    return Err(format!("Bundle entry '{}' is outside the settings, keybindings and snippets a bundle carries", file).into());
+                }
+                let target = staging.join(relative);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                io::copy(&mut archive.by_name(&format!("{}{}", PROFILE_PREFIX, file))?, &mut File::create(&target)?)?;
+            }
+
+            let extensions_dir = staging.join(EXTENSIONS_DIR);
+            let unpacked = tempfile::tempdir()?;
+            for (index, extension) in manifest.extensions.iter().enumerate() {
+                let entry = match &extension.vsix {
+                    Some(entry) => entry,
+                    None => {
+                        missing.push(normalize_extension_id(&extension.id));
+                        continue;
+                    }
+                };
+                let vsix_path = unpacked.path().join(format!("{}.vsix", index));
+                io::copy(&mut archive.by_name(entry)?, &mut File::create(&vsix_path)?)?;
+                install_vsix_into(&extensions_dir, &read_vsix(&vsix_path)?)?;
+            }
+            // Installed extensions are registered at their staging location
+            let extensions_json = extensions_dir.join(EXTENSIONS_MANIFEST);
+            if extensions_json.exists() {
+                let mut extensions: Value = serde_json::from_str(&fs::read_to_string(&extensions_json)?)?;
+                rewrite_path_prefix(&mut extensions, &staging.to_string_lossy(), &session_dir.to_string_lossy());
+                fs::write(&extensions_json, serde_json::to_string(&extensions)?)?;
+            }
+
+            let bundle_info = &manifest.session_info;
+            let mut session_info = SessionInfo::new(&name, &session_dir, None);
+            session_info.projects = bundle_info.projects.clone();
+            session_info.tags = bundle_info.tags.clone();
+            session_info.notes = bundle_info.notes.clone();
+            session_info.color = bundle_info.color.clone();
+            session_info.icon = bundle_info.icon.clone();
+            session_info.disabled_extensions = bundle_info.disabled_extensions.clone();
+            session_info.launch_args = bundle_info.launch_args.clone();
+            session_info.env = bundle_info.env.clone();
+            // Extensions that were not packaged are suggested through the workspace file instead
+            session_info.recommended_extensions = bundle_info.recommended_extensions.clone();
+            for id in &missing {
+                if !session_info.recommended_extensions.iter().any(|existing| normalize_extension_id(existing) == *id) {
+                    session_info.recommended_extensions.push(id.clone());
+                }
+            }
+            fs::write(staging.join(SESSION_FILE), serde_json::to_string_pretty(&session_info)?)?;
+
+            if manifest.workspace {
+                let mut workspace = String::new();
+                archive.by_name(WORKSPACE_ENTRY)?.read_to_string(&mut workspace)?;
+                if !missing.is_empty() {
+                    workspace = add_recommendations(&workspace, &missing)?;
+                }
+                fs::write(staging.join(format!("{}.code-workspace", name)), workspace)?;
+            }
+            commit_staged(&staging, &session_dir)?;
+            Ok(session_info)
+        })();
+        let session_info = match imported {
+            Ok(session_info) => session_info,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&staging);
+                return Err(e);
+            }
+        };
+        if !manifest.workspace && (!session_info.projects.is_empty() || !session_info.recommended_extensions.is_empty()) {
+            self.write_workspace_file(&session_info)?;
+        }
+
+        Ok(format!(
+            "Bundle '{}' imported as session '{}' ({} files, {} extensions installed, {} recommended)",
+            path.to_string_lossy(),
+            name,
+            manifest.files.len(),
+            manifest.extensions.len() - missing.len(),
+            missing.len()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::Path;
+    use chrono::Local;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+    use super::{read_bundle_manifest, BundleManifest, BundleSessionInfo, ExportOptions, BUNDLE_MANIFEST, BUNDLE_VERSION, PROFILE_PREFIX};
+    use crate::test_support::{manager, session};
+    use crate::CursorManagerError;
+
+    const SETTINGS: &str = "{\n    // font for the editor\n    \"editor.fontSize\": 14,\n    \"github.token\": \"abc\",\n    \"http\": { \"proxy\": \"p\", \"proxyAuthorization\": \"basic xyz\" }\n}\n";
+    const WORKSPACE: &str = "{\n    // the project folders\n    \"folders\": [],\n    \"settings\": { \"sonar.token\": \"def\" }\n}\n";
+
+    fn write_bundle(path: &Path, min_reader_version: u32, files: &[(&str, &str)]) {
+        let manifest = BundleManifest {
+            format_version: BUNDLE_VERSION,
+            min_reader_version,
+            app_version: "0.0.0".to_string(),
+            created: Local::now(),
+            session: "shared".to_string(),
+            session_info: BundleSessionInfo::default(),
+            files: files.iter().map(|(file, _)| file.to_string()).collect(),
+            extensions: Vec::new(),
+            workspace: false,
+            secrets_excluded: true,
+            redacted: Vec::new(),
+        };
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        zip.start_file(BUNDLE_MANIFEST, SimpleFileOptions::default()).unwrap();
+        zip.write_all(serde_json::to_string(&manifest).unwrap().as_bytes()).unwrap();
+        for (file, content) in files {
+            zip.start_file(format!("{}{}", PROFILE_PREFIX, file), SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn export_leaves_secrets_out_and_keeps_comments() {
+        let (dir, manager) = manager();
+        session(&manager, "work", &[("User/settings.json", SETTINGS), ("work.code-workspace", WORKSPACE)]);
+        manager.update_session_info("work", |info| {
+            info.env.insert("NPM_TOKEN".to_string(), "secret".to_string());
+            info.env.insert("NODE_ENV".to_string(), "development".to_string());
+        }).unwrap();
+        let bundle = dir.path().join("work.cursor-bundle");
+        manager.export_session("work", &bundle, &ExportOptions::default()).unwrap();
+
+        let manifest = read_bundle_manifest(&bundle).unwrap();
+        assert_eq!(manifest.redacted, vec![
+            "session.json: env.NPM_TOKEN",
+            "User/settings.json: github.token",
+            "User/settings.json: http.proxyAuthorization",
+            "workspace.code-workspace: settings.sonar.token",
+        ]);
+        assert!(!manifest.session_info.env.contains_key("NPM_TOKEN"));
+        assert_eq!(manifest.session_info.env["NODE_ENV"], "development");
+
+        manager.import_session(&bundle, Some("copy")).unwrap();
+        let settings = fs::read_to_string(manager.session_dir("copy").join("User/settings.json")).unwrap();
+        assert!(settings.contains("// font for the editor"));
+        assert!(settings.contains("\"proxy\""));
+        assert!(!settings.contains("abc") && !settings.contains("xyz"));
+        let workspace = fs::read_to_string(manager.workspace_file_path("copy")).unwrap();
+        assert!(workspace.contains("// the project folders"));
+        assert!(!workspace.contains("def"));
+    }
+
+    #[test]
+    fn an_exported_session_imports_with_its_files_and_metadata() {
+        let (dir, manager) = manager();
+        session(&manager, "work", &[
+            ("User/settings.json", "{ \"editor.fontSize\": 14 }"),
+            ("User/keybindings.json", "[]"),
+            ("User/snippets/rust.json", "{}"),
+            ("User/globalStorage/state.vscdb", "state"),
+        ]);
+        manager.update_session_info("work", |info| {
+            info.tags = vec!["client".to_string()];
+            info.launch_args = vec!["--disable-gpu".to_string()];
+        }).unwrap();
+        let bundle = dir.path().join("work.cursor-bundle");
+        manager.export_session("work", &bundle, &ExportOptions { include_secrets: true, ..Default::default() }).unwrap();
+        manager.import_session(&bundle, None).unwrap_err();
+        manager.import_session(&bundle, Some("copy")).unwrap();
+
+        let copy = manager.session_dir("copy");
+        assert_eq!(fs::read_to_string(copy.join("User/settings.json")).unwrap(), "{ \"editor.fontSize\": 14 }");
+        assert_eq!(fs::read_to_string(copy.join("User/keybindings.json")).unwrap(), "[]");
+        assert_eq!(fs::read_to_string(copy.join("User/snippets/rust.json")).unwrap(), "{}");
+        // Editor state stays behind
+        assert!(!copy.join("User/globalStorage").exists());
+        let info = manager.read_session_info("copy").unwrap();
+        assert_eq!(info.name, "copy");
+        assert_eq!(info.tags, vec!["client"]);
+        assert_eq!(info.launch_args, vec!["--disable-gpu"]);
+    }
+
+    #[test]
+    fn bundles_needing_a_newer_reader_are_rejected() {
+        let (dir, manager) = manager();
+        let bundle = dir.path().join("future.cursor-bundle");
+        write_bundle(&bundle, BUNDLE_VERSION + 1, &[("User/settings.json", "{}")]);
+
+        let error = manager.import_session(&bundle, None).unwrap_err();
+        assert!(matches!(error.downcast_ref::<CursorManagerError>(), Some(CursorManagerError::IncompatibleBundle { .. })));
+        assert!(!manager.session_dir("shared").exists());
+    }
+
+    #[test]
+    fn bundle_entries_outside_the_bundled_paths_are_rejected() {
+        let (dir, manager) = manager();
+        for file in ["extensions/evil/extension.js", "User/globalStorage/state.vscdb", "../outside.json"] {
+            let bundle = dir.path().join("crafted.cursor-bundle");
+            write_bundle(&bundle, BUNDLE_VERSION, &[("User/settings.json", "{}"), (file, "payload")]);
+            assert!(manager.import_session(&bundle, None).is_err(), "{} was imported", file);
+            assert!(!manager.session_dir("shared").exists());
+        }
+        assert!(!dir.path().join("outside.json").exists() && !dir.path().join("profiles/outside.json").exists());
+        assert_eq!(fs::read_dir(dir.path().join("profiles")).unwrap().count(), 0);
+    }
+}
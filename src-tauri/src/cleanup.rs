@@ -0,0 +1,200 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use crate::disk_usage::UsageCategory;
+use crate::jobs;
+use crate::profile::dir_size;
+use crate::CursorManager;
+
+// Categories the app rebuilds by itself. For workspaceStorage only entries of deleted folders count
+pub const CLEANABLE_CATEGORIES: [UsageCategory; 6] = [
+    UsageCategory::Cache,
+    UsageCategory::CodeCache,
+    UsageCategory::GpuCache,
+    UsageCategory::CachedData,
+    UsageCategory::Logs,
+    UsageCategory::WorkspaceStorage,
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CleanedEntry {
+    // Relative to the profile root
+    pub path: String,
+    pub category: UsageCategory,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CleanReport {
+    pub session: String,
+    pub dry_run: bool,
+    pub freed_bytes: u64,
+    pub removed: Vec<CleanedEntry>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BulkCleanResult {
+    pub session: String,
+    pub report: Option<CleanReport>,
+    pub error: Option<String>,
+}
+
+// Minimal percent-decoding for the file:// URIs in workspace.json
+fn decode_uri_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(byte) = encoded.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    let path = String::from_utf8(decoded).ok()?;
+    // file:///c%3A/Users/... on Windows
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+// A workspaceStorage entry is stale when its workspace.json points at a local folder or workspace file that is gone
+fn is_stale_workspace_entry(entry: &Path) -> bool {
+    let workspace: serde_json::Value = match fs::read_to_string(entry.join("workspace.json")).ok().and_then(|data| serde_json::from_str(&data).ok()) {
+        Some(workspace) => workspace,
+        None => return false,
+    };
+    let uri = match workspace.get("folder").or_else(|| workspace.get("workspace")).and_then(|v| v.as_str()) {
+        Some(uri) => uri,
+        None => return false,
+    };
+    // Remote and virtual workspaces cannot be checked from here
+    match decode_uri_path(uri) {
+        Some(path) => !path.exists(),
+        None => false,
+    }
+}
+
+fn modified(path: &Path) -> SystemTime {
+    fs::metadata(path).and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+impl CursorManager {
+    // Paths (relative to the profile root) that cleaning `category` would delete
+    fn clean_candidates(&self, root: &Path, category: UsageCategory) -> Vec<String> {
+        let relative = match category.relative_path() {
+            Some(relative) => relative,
+            None => return Vec::new(),
+        };
+        let dir = root.join(relative);
+        let children = || -> Vec<PathBuf> {
+            fs::read_dir(&dir)
+                .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+                .unwrap_or_default()
+        };
+
+        match category {
+            UsageCategory::WorkspaceStorage => children()
+                .into_iter()
+                .filter(|entry| entry.is_dir() && is_stale_workspace_entry(entry))
+                .filter_map(|entry| entry.file_name().map(|n| format!("{}/{}", relative, n.to_string_lossy())))
+                .collect(),
+            // Each launch writes a new timestamped folder; keep the latest so the current run is untouched
+            UsageCategory::Logs => {
+                let mut folders = children();
+                folders.sort_by_key(|folder| modified(folder));
+                folders.pop();
+                folders
+                    .into_iter()
+                    .filter_map(|entry| entry.file_name().map(|n| format!("{}/{}", relative, n.to_string_lossy())))
+                    .collect()
+            }
+            _ if dir.exists() => vec![relative.to_string()],
+            _ => Vec::new(),
+        }
+    }
+
+    // Delete regenerable data from a profile. No categories means all of CLEANABLE_CATEGORIES
+    pub fn clean_session(&self, session: &str, categories: &[UsageCategory], dry_run: bool) -> Result<CleanReport, Box<dyn Error>> {
+        // Fails early for unknown sessions
+        self.read_session_info(session)?;
+        let categories: Vec<UsageCategory> = if categories.is_empty() { CLEANABLE_CATEGORIES.to_vec() } else { categories.to_vec() };
+        if let Some(category) = categories.iter().find(|c| !CLEANABLE_CATEGORIES.contains(c)) {
+            return Err(format!("{:?} holds user data and cannot be cleaned", category).into());
+        }
+        if !dry_run {
+            self.ensure_not_running(session)?;
+        }
+
+        let root = self.session_dir(session);
+        let mut report = CleanReport {
+            session: session.to_string(),
+            dry_run,
+            freed_bytes: 0,
+            removed: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        let mut cancelled = None;
+        'categories: for category in categories {
+            for relative in self.clean_candidates(&root, category) {
+                let path = root.join(&relative);
+                let bytes = dir_size(&path);
+                if let Err(e) = jobs::checkpoint(&relative, bytes, 1) {
+                    cancelled = Some(e);
+                    break 'categories;
+                }
+                if !dry_run {
+                    let removed = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+                    if let Err(e) = removed {
+                        report.errors.push(format!("{}: {}", relative, e));
+                        continue;
+                    }
+                }
+                report.freed_bytes += bytes;
+                report.removed.push(CleanedEntry { path: relative, category, bytes });
+            }
+        }
+
+        if !dry_run && report.freed_bytes > 0 {
+            tracing::info!("Cleaned {} bytes from session '{}'", report.freed_bytes, session);
+            // The cached size is stale now; the next listing or scan recomputes it
+            self.update_session_info(session, |session_info| session_info.disk_usage = None)?;
+        }
+        // A cancelled clean stops between entries, with the cached size already invalidated
+        if let Some(e) = cancelled {
+            return Err(e.into());
+        }
+        Ok(report)
+    }
+
+    // Clean several sessions (all of them when none are named); failures do not stop the rest
+    pub fn clean_sessions(&self, sessions: Option<Vec<String>>, categories: &[UsageCategory], dry_run: bool) -> Result<Vec<BulkCleanResult>, Box<dyn Error>> {
+        let sessions = match sessions {
+            Some(sessions) => sessions,
+            None => self.list_sessions()?.into_iter().map(|s| s.name).collect(),
+        };
+
+        let mut results = Vec::new();
+        for session in sessions {
+            let result = match self.clean_session(&session, categories, dry_run) {
+                Ok(report) => BulkCleanResult { session, report: Some(report), error: None },
+                Err(e) => BulkCleanResult { session, report: None, error: Some(e.to_string()) },
+            };
+            // Cancelling the job stops the whole run, not just the current session
+            jobs::check_cancelled()?;
+            results.push(result);
+        }
+        Ok(results)
+    }
+}
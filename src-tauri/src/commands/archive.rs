@@ -1,27 +1,102 @@
-use tauri::State;
-use crate::{Config, CursorManager, ArchiveInfo};
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{Emitter, Runtime, State};
+use crate::activity::read_config;
+use crate::encryption::generate_key_file;
+use crate::integrity::ARCHIVE_VERIFIED_EVENT;
+use crate::{Config, CursorManager, ArchiveContentEntry, ArchiveDiff, ArchiveInfo, ArchiveKey, ArchiveOptions, ArchiveVerification, DiffSource, ExtractResult, GcReport, RestoreOptions, RetentionPolicy, RetentionReport};
 
 #[tauri::command]
-pub fn list_archives(config_state: State<'_, Config>) -> Result<Vec<ArchiveInfo>, String> {
-    let manager = CursorManager::new(Some(config_state.inner().clone()));
+pub fn list_archives(config_state: State<'_, Mutex<Config>>) -> Result<Vec<ArchiveInfo>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
     manager.list_archives().map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub fn archive_session(config_state: State<'_, Config>, session: &str) -> Result<String, String> {
-    let manager = CursorManager::new(Some(config_state.inner().clone()));
-    manager.archive_session(session).map_err(|e| e.to_string())
+pub fn archive_session(config_state: State<'_, Mutex<Config>>, session: &str, options: Option<ArchiveOptions>) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.archive_session(session, &options.unwrap_or_default()).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-pub fn restore_archive(config_state: State<'_, Config>, archive: &str, newSessionName: Option<&str>) -> Result<String, String> {
-    let manager = CursorManager::new(Some(config_state.inner().clone()));
-    manager.restore_archive(archive, newSessionName).map_err(|e| e.to_string())
+pub fn restore_archive(config_state: State<'_, Mutex<Config>>, archive: &str, newSessionName: Option<&str>, skipVerify: Option<bool>, key: Option<ArchiveKey>, options: Option<RestoreOptions>) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.restore_archive(archive, newSessionName, !skipVerify.unwrap_or(false), key.as_ref(), &options.unwrap_or_default()).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub fn delete_archive(config_state: State<'_, Config>, archive: &str) -> Result<String, String> {
-    let manager = CursorManager::new(Some(config_state.inner().clone()));
+pub fn verify_archive(config_state: State<'_, Mutex<Config>>, archive: &str, key: Option<ArchiveKey>) -> Result<ArchiveVerification, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.verify_archive(archive, key.as_ref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_archive_contents(config_state: State<'_, Mutex<Config>>, archive: &str, prefix: Option<&str>, key: Option<ArchiveKey>) -> Result<Vec<ArchiveContentEntry>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.list_archive_contents(archive, prefix, key.as_ref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn extract_from_archive(config_state: State<'_, Mutex<Config>>, archive: &str, paths: Vec<String>, destination: &str, key: Option<ArchiveKey>) -> Result<ExtractResult, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.extract_from_archive(archive, &paths, Path::new(destination), key.as_ref()).map_err(|e| e.to_string())
+}
+
+// Either side may be a live session; `key` opens whichever sides are encrypted
+#[tauri::command]
+pub fn diff_archive(config_state: State<'_, Mutex<Config>>, a: DiffSource, b: DiffSource, key: Option<ArchiveKey>) -> Result<ArchiveDiff, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.diff_archive(&a, &b, key.as_ref()).map_err(|e| e.to_string())
+}
+
+// Creates a new age identity file for Config.archive_key_file and returns its public key
+#[tauri::command]
+pub fn generate_archive_key(path: &str) -> Result<String, String> {
+    generate_key_file(Path::new(path)).map_err(|e| e.to_string())
+}
+
+// Re-hashing every archive reads all of them, so it runs on a blocking thread and reports each result as an event
+#[tauri::command]
+pub async fn verify_archives<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Mutex<Config>>) -> Result<Vec<ArchiveVerification>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    tauri::async_runtime::spawn_blocking(move || {
+        manager
+            .verify_archives(|verification| {
+                let _ = app.emit(ARCHIVE_VERIFIED_EVENT, verification.clone());
+            })
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn delete_archive(config_state: State<'_, Mutex<Config>>, archive: &str) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
     manager.delete_archive(archive).map_err(|e| e.to_string())
-} 
\ No newline at end of file
+}
+
+#[tauri::command]
+pub fn set_archive_pinned(config_state: State<'_, Mutex<Config>>, archive: &str, pinned: bool) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.set_archive_pinned(archive, pinned).map_err(|e| e.to_string())
+}
+
+// Runs the configured policy unless one is passed in; dry runs only report what would be deleted
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn apply_archive_retention(config_state: State<'_, Mutex<Config>>, policy: Option<RetentionPolicy>, dryRun: Option<bool>) -> Result<RetentionReport, String> {
+    let config = read_config(&config_state);
+    let policy = policy.unwrap_or_else(|| config.archive_retention.clone());
+    let manager = CursorManager::new(Some(config));
+    manager.apply_archive_retention(&policy, dryRun.unwrap_or(true)).map_err(|e| e.to_string())
+}
+
+// Removes snapshot chunks no snapshot references and repairs the reference counts
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn gc_archives(config_state: State<'_, Mutex<Config>>, dryRun: Option<bool>) -> Result<GcReport, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.gc_archives(dryRun.unwrap_or(false)).map_err(|e| e.to_string())
+}
@@ -1,16 +1,33 @@
-use tauri::State;
+use tauri::{Emitter, Runtime, State};
 use std::sync::Mutex;
-use crate::Config;
+use validator::Validate;
+use crate::activity::read_config;
+use crate::{Config, CursorManager};
+
+pub const CONFIG_WARNING_EVENT: &str = "config-warning";
 
 #[tauri::command]
-pub fn get_config(config_state: State<'_, Config>) -> Config {
-    config_state.inner().clone()
+pub fn get_config(config_state: State<'_, Mutex<Config>>) -> Config {
+    read_config(&config_state)
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-pub fn update_config(newConfig: Config, config_state: State<'_, Mutex<Config>>) -> Result<Config, String> {
+pub fn update_config<R: Runtime>(app: tauri::AppHandle<R>, newConfig: Config, config_state: State<'_, Mutex<Config>>) -> Result<Config, String> {
+    newConfig.validate().map_err(|e| format!("Invalid configuration: {}", e))?;
+
+    // Lowering the cap never deletes sessions, it only blocks new ones until enough are removed
+    let current = CursorManager::new(Some(newConfig.clone())).occupied_session_count();
+    if current > newConfig.max_sessions as usize {
+        let warning = format!(
+            "max_sessions is {} but {} sessions already exist; no new sessions can be created until some are removed",
+            newConfig.max_sessions, current
+        );
+        tracing::warn!("{}", warning);
+        let _ = app.emit(CONFIG_WARNING_EVENT, warning);
+    }
+
     let mut config = config_state.lock().map_err(|e| e.to_string())?;
     *config = newConfig.clone();
     Ok(newConfig)
-} 
\ No newline at end of file
+}
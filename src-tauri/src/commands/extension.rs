@@ -0,0 +1,55 @@
+use std::sync::Mutex;
+use tauri::State;
+use crate::activity::read_config;
+use crate::{Config, CursorManager, ExtensionInfo, ExtensionSummary, ExtensionSyncPlan, ExtensionUsage, VsixInstallResult, VsixPackage};
+
+#[tauri::command]
+pub fn list_session_extensions(config_state: State<'_, Mutex<Config>>, session: &str) -> Result<Vec<ExtensionInfo>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.list_session_extensions(session).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn find_extension_sessions(config_state: State<'_, Mutex<Config>>, extensionId: &str, version: Option<&str>) -> Result<Vec<ExtensionUsage>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.find_extension_sessions(extensionId, version).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn extension_overview(config_state: State<'_, Mutex<Config>>) -> Result<Vec<ExtensionSummary>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.extension_overview().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn set_extension_enabled(config_state: State<'_, Mutex<Config>>, session: &str, extensionId: &str, enabled: bool) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.set_extension_enabled(session, extensionId, enabled).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_vsix_cache(config_state: State<'_, Mutex<Config>>) -> Result<Vec<VsixPackage>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.list_vsix_cache().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_to_vsix_cache(config_state: State<'_, Mutex<Config>>, path: &str) -> Result<VsixPackage, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.add_to_vsix_cache(path).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn install_vsix(config_state: State<'_, Mutex<Config>>, sessions: Vec<String>, packages: Vec<String>) -> Result<Vec<VsixInstallResult>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.install_vsix(&sessions, &packages).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn sync_extensions(config_state: State<'_, Mutex<Config>>, source: &str, target: &str, dryRun: Option<bool>) -> Result<ExtensionSyncPlan, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.sync_extensions(source, target, dryRun.unwrap_or(true)).map_err(|e| e.to_string())
+}
@@ -0,0 +1,62 @@
+use std::sync::Mutex;
+use tauri::{Runtime, State};
+use crate::activity::read_config;
+use crate::jobs;
+use crate::{ArchiveKey, ArchiveOptions, CloneOptions, Config, JobInfo, JobRequest, RestoreOptions, UsageCategory};
+
+// Runs archive, restore, clone, clean, verify or disk usage in the background and returns the job id.
+// Progress arrives as job-progress events, the outcome as a job-finished event
+#[tauri::command]
+pub fn start_job<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Mutex<Config>>, request: JobRequest) -> Result<String, String> {
+    Ok(jobs::start_job(app, read_config(&config_state), request))
+}
+
+// Background versions of the long running commands. They take the same arguments as their blocking
+// counterparts and return the job id; the job-finished event carries what those would have returned
+
+#[tauri::command]
+pub fn start_archive_job<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Mutex<Config>>, session: String, options: Option<ArchiveOptions>) -> Result<String, String> {
+    Ok(jobs::start_job(app, read_config(&config_state), JobRequest::Archive { session, options }))
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn start_restore_job<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Mutex<Config>>, archive: String, newSessionName: Option<String>, skipVerify: Option<bool>, key: Option<ArchiveKey>, options: Option<RestoreOptions>) -> Result<String, String> {
+    let request = JobRequest::Restore { archive, new_session_name: newSessionName, skip_verify: skipVerify.unwrap_or(false), key, options };
+    Ok(jobs::start_job(app, read_config(&config_state), request))
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn start_clone_job<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Mutex<Config>>, source: String, newName: String, options: Option<CloneOptions>) -> Result<String, String> {
+    Ok(jobs::start_job(app, read_config(&config_state), JobRequest::Clone { source, new_name: newName, options }))
+}
+
+// All sessions when none are named
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn start_clean_job<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Mutex<Config>>, sessions: Option<Vec<String>>, categories: Option<Vec<UsageCategory>>, dryRun: Option<bool>) -> Result<String, String> {
+    let request = JobRequest::Clean { sessions, categories: categories.unwrap_or_default(), dry_run: dryRun.unwrap_or(false) };
+    Ok(jobs::start_job(app, read_config(&config_state), request))
+}
+
+// All archives when none is named
+#[tauri::command]
+pub fn start_verify_job<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Mutex<Config>>, archive: Option<String>, key: Option<ArchiveKey>) -> Result<String, String> {
+    Ok(jobs::start_job(app, read_config(&config_state), JobRequest::Verify { archive, key }))
+}
+
+#[tauri::command]
+pub fn start_disk_usage_job<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Mutex<Config>>, session: String) -> Result<String, String> {
+    Ok(jobs::start_job(app, read_config(&config_state), JobRequest::DiskUsage { session }))
+}
+
+#[tauri::command]
+pub fn cancel_job(id: &str) -> Result<JobInfo, String> {
+    jobs::cancel_job(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_jobs() -> Result<Vec<JobInfo>, String> {
+    Ok(jobs::list_jobs())
+}
@@ -1,14 +1,16 @@
+use std::sync::Mutex;
 use tauri::{Runtime, State};
+use crate::activity::read_config;
 use crate::{Config, CursorManager};
 
 #[tauri::command]
-pub async fn spoof_mac_cmd<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Config>, interface: Option<String>) -> Result<String, String> {
-    let manager = CursorManager::new(Some(config_state.inner().clone()));
+pub async fn spoof_mac_cmd<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Mutex<Config>>, interface: Option<String>) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
     manager.spoof_mac(&app, interface).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub fn random_mac(config_state: State<'_, Config>) -> String {
-    let manager = CursorManager::new(Some(config_state.inner().clone()));
+pub fn random_mac(config_state: State<'_, Mutex<Config>>) -> String {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
     manager.random_mac()
 } 
\ No newline at end of file
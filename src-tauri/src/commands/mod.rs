@@ -1,13 +1,23 @@
 // Re-export all command modules
 pub mod config;
 pub mod session;
+pub mod template;
+pub mod settings;
+pub mod extension;
+pub mod storage;
 pub mod archive;
 pub mod mac;
 pub mod system;
+pub mod job;
 
 // Re-export all command functions for easy usage
 pub use config::*;
 pub use session::*;
+pub use template::*;
+pub use settings::*;
+pub use extension::*;
+pub use storage::*;
 pub use archive::*;
 pub use mac::*;
-pub use system::*; 
\ No newline at end of file
+pub use system::*;
+pub use job::*; 
\ No newline at end of file
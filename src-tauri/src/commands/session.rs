@@ -1,27 +1,110 @@
+use std::path::Path;
+use std::sync::Mutex;
 use tauri::{Runtime, State};
-use crate::{Config, CursorManager, SessionInfo, ElectronApp};
+use crate::activity::read_config;
+use crate::bundle::read_bundle_manifest;
+use crate::{Config, CursorManager, SessionInfo, SessionMetadataUpdate, ElectronApp, ProjectGitStatus, CloneOptions, ProcessRegistry, SessionActivity, IdleEvent, BundleManifest, ExportOptions};
 
 #[tauri::command]
-pub fn list_sessions(config_state: State<'_, Config>) -> Result<Vec<SessionInfo>, String> {
-    let manager = CursorManager::new(Some(config_state.inner().clone()));
-    manager.list_sessions().map_err(|e| e.to_string())
+#[allow(non_snake_case)]
+pub fn list_sessions(config_state: State<'_, Mutex<Config>>, includeGitStatus: Option<bool>, includeDiskUsage: Option<bool>) -> Result<Vec<SessionInfo>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    let mut sessions = manager.list_sessions().map_err(|e| e.to_string())?;
+    if includeGitStatus.unwrap_or(false) {
+        for session in sessions.iter_mut() {
+            session.git_status = Some(manager.git_status_for(session));
+        }
+    }
+    // Cached totals are always returned; this sizes sessions without a recent scan, without persisting
+    if includeDiskUsage.unwrap_or(false) {
+        for session in sessions.iter_mut() {
+            manager.cached_disk_usage(session);
+        }
+    }
+    Ok(sessions)
 }
 
 #[tauri::command]
-pub fn create_session(config_state: State<'_, Config>, name: &str, electron_app: ElectronApp) -> Result<String, String> {
-    let manager = CursorManager::new(Some(config_state.inner().clone()));
-    manager.create_session(name, electron_app).map_err(|e| e.to_string())
+pub fn create_session(config_state: State<'_, Mutex<Config>>, name: &str, electron_app: Option<ElectronApp>, template: Option<&str>) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.create_session(name, electron_app, template).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn rename_session(config_state: State<'_, Mutex<Config>>, oldName: &str, newName: &str) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.rename_session(oldName, newName).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn clone_session(config_state: State<'_, Mutex<Config>>, source: &str, newName: &str, options: Option<CloneOptions>) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.clone_session(source, newName, options.unwrap_or_default()).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub fn delete_session(config_state: State<'_, Config>, session: &str) -> Result<String, String> {
-    let manager = CursorManager::new(Some(config_state.inner().clone()));
+pub fn delete_session(config_state: State<'_, Mutex<Config>>, session: &str) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
     manager.delete_session(session).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-pub async fn launch_session_cmd<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Config>, session: &str, spoofMac: bool, connectVpn: bool) -> Result<String, String> {
-    let manager = CursorManager::new(Some(config_state.inner().clone()));
+pub async fn launch_session_cmd<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Mutex<Config>>, session: &str, spoofMac: bool, connectVpn: bool) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
     manager.launch_session(&app, session, spoofMac, connectVpn).await.map_err(|e| e.to_string())
-} 
\ No newline at end of file
+} 
+
+#[tauri::command]
+pub fn set_session_projects(config_state: State<'_, Mutex<Config>>, session: &str, projects: Vec<String>) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.set_session_projects(session, projects).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn update_session_metadata(config_state: State<'_, Mutex<Config>>, session: &str, update: SessionMetadataUpdate) -> Result<SessionInfo, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.update_session_metadata(session, update).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn session_git_status(config_state: State<'_, Mutex<Config>>, session: &str) -> Result<Vec<ProjectGitStatus>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.session_git_status(session).map_err(|e| e.to_string())
+}
+
+// Explicit activity signal, e.g. from the UI when the user interacts with a session
+#[tauri::command]
+pub fn touch_session(registry: State<'_, ProcessRegistry>, session: &str) -> bool {
+    registry.touch(session, "touch")
+}
+
+#[tauri::command]
+pub fn list_session_activity(registry: State<'_, ProcessRegistry>) -> Vec<SessionActivity> {
+    registry.list()
+}
+
+#[tauri::command]
+pub fn list_idle_events(registry: State<'_, ProcessRegistry>) -> Vec<IdleEvent> {
+    registry.events()
+}
+
+#[tauri::command]
+pub fn export_session(config_state: State<'_, Mutex<Config>>, session: &str, path: &str, options: Option<ExportOptions>) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.export_session(session, Path::new(path), &options.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+// Manifest of a bundle, for showing what an import would bring in
+#[tauri::command]
+pub fn read_session_bundle(path: &str) -> Result<BundleManifest, String> {
+    read_bundle_manifest(Path::new(path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn import_session(config_state: State<'_, Mutex<Config>>, path: &str, name: Option<&str>) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.import_session(Path::new(path), name).map_err(|e| e.to_string())
+}
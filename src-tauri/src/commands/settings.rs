@@ -0,0 +1,28 @@
+use std::sync::Mutex;
+use tauri::State;
+use crate::activity::read_config;
+use crate::{Config, CursorManager, BulkPatchResult, UserFile, UserFileKind, UserFilePatch};
+
+#[tauri::command]
+pub fn read_user_file(config_state: State<'_, Mutex<Config>>, session: &str, kind: UserFileKind) -> Result<UserFile, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.read_user_file(session, kind).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn write_user_file(config_state: State<'_, Mutex<Config>>, session: &str, kind: UserFileKind, content: &str) -> Result<UserFile, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.write_user_file(session, kind, content).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn patch_user_file(config_state: State<'_, Mutex<Config>>, session: &str, patch: UserFilePatch) -> Result<UserFile, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.patch_user_file(session, &patch).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn patch_user_file_bulk(config_state: State<'_, Mutex<Config>>, sessions: Option<Vec<String>>, patch: UserFilePatch) -> Result<Vec<BulkPatchResult>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.patch_user_file_bulk(sessions, &patch).map_err(|e| e.to_string())
+}
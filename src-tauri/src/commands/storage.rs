@@ -0,0 +1,34 @@
+use std::sync::Mutex;
+use tauri::{Emitter, Runtime, State};
+use crate::activity::read_config;
+use crate::disk_usage::DISK_USAGE_PROGRESS_EVENT;
+use crate::{BulkCleanResult, CleanReport, Config, CursorManager, DiskUsageReport, UsageCategory};
+
+// Big profiles take a while to walk, so the scan runs on a blocking thread and reports progress as events
+#[tauri::command]
+pub async fn session_disk_usage<R: Runtime>(app: tauri::AppHandle<R>, config_state: State<'_, Mutex<Config>>, session: String) -> Result<DiskUsageReport, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    tauri::async_runtime::spawn_blocking(move || {
+        manager
+            .session_disk_usage(&session, |progress| {
+                let _ = app.emit(DISK_USAGE_PROGRESS_EVENT, progress.clone());
+            })
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn clean_session(config_state: State<'_, Mutex<Config>>, session: &str, categories: Option<Vec<UsageCategory>>, dryRun: Option<bool>) -> Result<CleanReport, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.clean_session(session, &categories.unwrap_or_default(), dryRun.unwrap_or(false)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn clean_sessions(config_state: State<'_, Mutex<Config>>, sessions: Option<Vec<String>>, categories: Option<Vec<UsageCategory>>, dryRun: Option<bool>) -> Result<Vec<BulkCleanResult>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.clean_sessions(sessions, &categories.unwrap_or_default(), dryRun.unwrap_or(false)).map_err(|e| e.to_string())
+}
@@ -0,0 +1,29 @@
+use std::sync::Mutex;
+use tauri::State;
+use crate::activity::read_config;
+use crate::{Config, CursorManager, SessionTemplate};
+
+#[tauri::command]
+pub fn list_templates(config_state: State<'_, Mutex<Config>>) -> Result<Vec<SessionTemplate>, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.list_templates().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn save_template(config_state: State<'_, Mutex<Config>>, template: SessionTemplate, overwrite: bool) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.save_template(&template, overwrite).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+pub fn create_template_from_session(config_state: State<'_, Mutex<Config>>, session: &str, templateName: &str, overwrite: bool) -> Result<SessionTemplate, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.create_template_from_session(session, templateName, overwrite).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn delete_template(config_state: State<'_, Mutex<Config>>, name: &str) -> Result<String, String> {
+    let manager = CursorManager::new(Some(read_config(&config_state)));
+    manager.delete_template(name).map_err(|e| e.to_string())
+}
@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use sysinfo::Disks;
+use crate::archive::{legacy_archive_root, ArchiveEntry, ArchiveFormat, ArchiveMode};
+use crate::profile::{path_matches, relative_string};
+use crate::snapshot::read_snapshot_index;
+use crate::{CursorManager, CursorManagerError};
+
+// Assumed compressed/uncompressed ratio when a session has never been archived in full
+const DEFAULT_COMPRESSION_RATIO: f64 = 0.5;
+// A past archive of mostly empty files says little about the next one
+const MIN_COMPRESSION_RATIO: f64 = 0.2;
+// Unpacked files take at least one block each
+const BLOCK_SIZE: u64 = 4096;
+// Left free on top of the estimate, so an operation never fills the filesystem completely
+const HEADROOM: u64 = 32 * 1024 * 1024;
+
+fn on_disk(size: u64) -> u64 {
+    size.div_ceil(BLOCK_SIZE).max(1) * BLOCK_SIZE
+}
+
+// Size of the regular files below `root/relative` that `exclude` keeps, symlinks are not followed
+fn tree_files(root: &Path, relative: &Path, exclude: &[String], files: &mut Vec<(String, u64, Option<i64>)>) {
+    let path = relative_string(relative);
+    if !path.is_empty() && exclude.iter().any(|pattern| path_matches(&path, pattern)) {
+        return;
+    }
+    let metadata = match fs::symlink_metadata(root.join(relative)) {
+        Ok(metadata) => metadata,
+        Err(_) => return,
+    };
+    if metadata.is_file() {
+        let modified = metadata.modified().ok().and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok()).map(|d| d.as_secs() as i64);
+        files.push((path, metadata.len(), modified));
+    } else if metadata.is_dir() {
+        if let Ok(entries) = fs::read_dir(root.join(relative)) {
+            for entry in entries.flatten() {
+                tree_files(root, &relative.join(entry.file_name()), exclude, files);
+            }
+        }
+    }
+}
+
+// Room a copy of the selected files takes once written out
+pub(crate) fn unpacked_size(root: &Path, include: &[String], exclude: &[String]) -> u64 {
+    let mut files = Vec::new();
+    for relative in include {
+        tree_files(root, Path::new(relative), exclude, &mut files);
+    }
+    files.iter().map(|(_, size, _)| on_disk(*size)).sum()
+}
+
+// The filesystem holding `path`, or its nearest existing ancestor for paths not created yet
+fn disk_of(disks: &Disks, path: &Path) -> Option<(PathBuf, u64)> {
+    let existing = path.ancestors().find(|ancestor| ancestor.exists())?;
+    let canonical = fs::canonicalize(existing).ok()?;
+    disks
+        .list()
+        .iter()
+        .filter(|disk| canonical.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| (disk.mount_point().to_path_buf(), disk.available_space()))
+}
+
+impl CursorManager {
+    // compressed/uncompressed of the session's latest full archive
+    fn compression_ratio(&self, session: &str) -> f64 {
+        let entries = match self.archive_entries() {
+            Ok(entries) => entries,
+            Err(_) => return DEFAULT_COMPRESSION_RATIO,
+        };
+        entries
+            .iter()
+            .filter(|entry| matches!(entry.format, ArchiveFormat::TarZst | ArchiveFormat::EncryptedTarZst))
+            .filter_map(|entry| entry.read_manifest())
+            .filter(|manifest| manifest.session == session && manifest.total_bytes > 0)
+            .max_by_key(|manifest| manifest.created)
+            .map(|manifest| (manifest.archive_bytes as f64 / manifest.total_bytes as f64).clamp(MIN_COMPRESSION_RATIO, 1.0))
+            .unwrap_or(DEFAULT_COMPRESSION_RATIO)
+    }
+
+    // Bytes a new archive of `session` adds to the archive base. Snapshots only store what changed since
+    // the session's latest snapshot, judged by size and modification time
+    pub(crate) fn estimate_archive_bytes(&self, session: &str, mode: ArchiveMode, exclude: &[String]) -> u64 {
+        let mut files = Vec::new();
+        tree_files(&self.session_dir(session), Path::new(""), exclude, &mut files);
+        let ratio = self.compression_ratio(session);
+
+        let unchanged = match mode {
+            ArchiveMode::Full => BTreeMap::new(),
+            ArchiveMode::Snapshot => {
+                let latest = self
+                    .archive_entries()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|entry| entry.format == ArchiveFormat::Snapshot)
+                    .map(|entry| (entry.origin(), entry))
+                    .filter(|((origin, _), _)| origin == session)
+                    .max_by_key(|((_, created), _)| *created)
+                    .and_then(|(_, entry)| read_snapshot_index(&entry.path).ok());
+                latest
+                    .map(|index| index.files.into_iter().map(|file| (file.path, (file.size, file.modified))).collect())
+                    .unwrap_or_default()
+            }
+        };
+        let changed: u64 = files
+            .iter()
+            .filter(|(path, size, modified)| unchanged.get(path) != Some(&(*size, *modified)))
+            .map(|(_, size, _)| *size)
+            .sum();
+        (changed as f64 * ratio) as u64
+    }
+
+    // Room the unpacked contents of an archive take
+    pub(crate) fn estimate_restore_bytes(&self, entry: &ArchiveEntry) -> u64 {
+        match entry.format {
+            ArchiveFormat::Directory => unpacked_size(&legacy_archive_root(&entry.path), &[String::new()], &[]),
+            ArchiveFormat::Snapshot => read_snapshot_index(&entry.path)
+                .map(|index| index.files.iter().map(|file| on_disk(file.size)).sum())
+                .unwrap_or(0),
+            ArchiveFormat::TarZst | ArchiveFormat::EncryptedTarZst => match entry.read_manifest() {
+                Some(manifest) if !manifest.files.is_empty() => manifest.files.iter().map(|file| on_disk(file.size)).sum(),
+                Some(manifest) => manifest.total_bytes + manifest.file_count * BLOCK_SIZE,
+                // Nothing recorded; assume the archive unpacks to twice its size
+                None => (fs::metadata(&entry.path).map(|m| m.len()).unwrap_or(0) as f64 / DEFAULT_COMPRESSION_RATIO) as u64,
+            },
+        }
+    }
+
+    // Fail with InsufficientSpace unless every filesystem has room for what is about to be written to it.
+    // Needs on the same filesystem add up; paths whose filesystem cannot be determined are not checked
+    pub(crate) fn ensure_free_space(&self, needs: &[(&Path, u64)]) -> Result<(), Box<dyn Error>> {
+        let disks = Disks::new_with_refreshed_list();
+        let mut by_disk: BTreeMap<PathBuf, (&Path, u64, u64)> = BTreeMap::new();
+        for (path, bytes) in needs {
+            match disk_of(&disks, path) {
+                Some((mount_point, available)) => by_disk.entry(mount_point).or_insert((path, 0, available)).1 += bytes,
+                None => tracing::debug!("No filesystem found for {}, skipping the free space check", path.to_string_lossy()),
+            }
+        }
+        for (path, needed, available) in by_disk.into_values() {
+            let needed = needed + HEADROOM;
+            if needed > available {
+                return Err(Box::new(CursorManagerError::InsufficientSpace {
+                    path: path.to_string_lossy().to_string(),
+                    needed,
+                    available,
+                }));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use sysinfo::Disks;
+    use super::{disk_of, on_disk, unpacked_size, BLOCK_SIZE, HEADROOM};
+    use crate::test_support::{manager, session};
+    use crate::CursorManagerError;
+
+    fn is_insufficient(error: Box<dyn std::error::Error>) -> bool {
+        matches!(error.downcast_ref::<CursorManagerError>(), Some(CursorManagerError::InsufficientSpace { .. }))
+    }
+
+    #[test]
+    fn more_than_the_free_space_is_refused() {
+        let (dir, manager) = manager();
+        let (_, available) = disk_of(&Disks::new_with_refreshed_list(), dir.path()).unwrap();
+        let profiles = Path::new(&manager.config.profile_base);
+        let archives = Path::new(&manager.config.archive_base);
+
+        manager.ensure_free_space(&[(profiles, 0)]).unwrap();
+        assert!(is_insufficient(manager.ensure_free_space(&[(profiles, available)]).unwrap_err()));
+        // The headroom counts against the free space as well
+        assert!(is_insufficient(manager.ensure_free_space(&[(profiles, available - HEADROOM / 2)]).unwrap_err()));
+        // Two needs on the same filesystem add up, even though each fits alone. Other writers may change the
+        // free space while the test runs, hence the margin
+        let most = available.saturating_sub(HEADROOM) / 5 * 3;
+        manager.ensure_free_space(&[(archives, most)]).unwrap();
+        assert!(is_insufficient(manager.ensure_free_space(&[(profiles, most), (archives, most)]).unwrap_err()));
+    }
+
+    #[test]
+    fn unpacked_files_take_whole_blocks() {
+        let (_dir, manager) = manager();
+        let dir = session(&manager, "work", &[("User/a.json", "{}"), ("User/b.bin", &"x".repeat(BLOCK_SIZE as usize + 1)), ("cache/c", "c")]);
+        assert_eq!(on_disk(0), BLOCK_SIZE);
+        assert_eq!(on_disk(BLOCK_SIZE), BLOCK_SIZE);
+        assert_eq!(unpacked_size(&dir, &["User".to_string()], &[]), 3 * BLOCK_SIZE);
+        assert_eq!(unpacked_size(&dir, &["User".to_string()], &["User/b.bin".to_string()]), BLOCK_SIZE);
+    }
+}
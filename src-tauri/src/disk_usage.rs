@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use crate::jobs;
+use crate::profile::{dir_size, path_matches, relative_string, EXTENSIONS_DIR, GLOBAL_STORAGE_DIR, WORKSPACE_STORAGE_DIR};
+use crate::{CursorManager, SessionInfo};
+
+pub const DISK_USAGE_PROGRESS_EVENT: &str = "disk-usage-progress";
+const LARGEST_ENTRIES: usize = 20;
+// Emit a progress event every this many files
+const PROGRESS_INTERVAL: u64 = 500;
+// Cached totals older than this are recomputed by listings
+const CACHED_USAGE_MAX_AGE_HOURS: i64 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum UsageCategory {
+    Cache,
+    CodeCache,
+    GpuCache,
+    CachedData,
+    Logs,
+    WorkspaceStorage,
+    Extensions,
+    GlobalStorage,
+    Other,
+}
+
+impl UsageCategory {
+    pub const ALL: [UsageCategory; 9] = [
+        UsageCategory::Cache,
+        UsageCategory::CodeCache,
+        UsageCategory::GpuCache,
+        UsageCategory::CachedData,
+        UsageCategory::Logs,
+        UsageCategory::WorkspaceStorage,
+        UsageCategory::Extensions,
+        UsageCategory::GlobalStorage,
+        UsageCategory::Other,
+    ];
+
+    // Location relative to the profile root, None for everything else
+    pub fn relative_path(&self) -> Option<&'static str> {
+        match self {
+            UsageCategory::Cache => Some("Cache"),
+            UsageCategory::CodeCache => Some("Code Cache"),
+            UsageCategory::GpuCache => Some("GPUCache"),
+            UsageCategory::CachedData => Some("CachedData"),
+            UsageCategory::Logs => Some("logs"),
+            UsageCategory::WorkspaceStorage => Some(WORKSPACE_STORAGE_DIR),
+            UsageCategory::Extensions => Some(EXTENSIONS_DIR),
+            UsageCategory::GlobalStorage => Some(GLOBAL_STORAGE_DIR),
+            UsageCategory::Other => None,
+        }
+    }
+
+    pub fn of(relative: &str) -> UsageCategory {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|category| category.relative_path().map(|p| path_matches(relative, p)).unwrap_or(false))
+            .unwrap_or(UsageCategory::Other)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryUsage {
+    pub category: UsageCategory,
+    pub path: Option<String>,
+    pub bytes: u64,
+    pub files: u64,
+}
+
+// A top-level profile entry, or a direct child of a category folder (one extension, one workspace hash, ...)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UsageEntry {
+    pub path: String,
+    pub category: UsageCategory,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiskUsageReport {
+    pub session: String,
+    pub total_bytes: u64,
+    pub total_files: u64,
+    pub categories: Vec<CategoryUsage>,
+    pub largest: Vec<UsageEntry>,
+    pub scanned_at: DateTime<Local>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiskUsageProgress {
+    pub session: String,
+    pub files: u64,
+    pub bytes: u64,
+    pub current_path: String,
+    pub done: bool,
+}
+
+// Last known profile size, stored in session.json so listings do not have to walk every profile
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CachedDiskUsage {
+    pub total_bytes: u64,
+    pub scanned_at: DateTime<Local>,
+}
+
+fn entry_key(relative: &str, category: UsageCategory) -> String {
+    let depth = category.relative_path().map(|p| p.split('/').count()).unwrap_or(0) + 1;
+    relative.split('/').take(depth).collect::<Vec<_>>().join("/")
+}
+
+impl CursorManager {
+    // Walk a profile and break its size down by category. `progress` is called periodically and once at the end
+    pub fn session_disk_usage(&self, session: &str, mut progress: impl FnMut(&DiskUsageProgress)) -> Result<DiskUsageReport, Box<dyn Error>> {
+        // Fails early for unknown sessions
+        self.read_session_info(session)?;
+        let root = self.session_dir(session);
+
+        let mut categories: HashMap<UsageCategory, CategoryUsage> = HashMap::new();
+        let mut entries: HashMap<String, (UsageCategory, u64)> = HashMap::new();
+        let mut total_bytes = 0;
+        let mut total_files = 0;
+
+        let mut pending = vec![root.clone()];
+        while let Some(dir) = pending.pop() {
+            let listing = match fs::read_dir(&dir) {
+                Ok(listing) => listing,
+                Err(e) => {
+                    tracing::warn!("Cannot read {}: {}", dir.to_string_lossy(), e);
+                    continue;
+                }
+            };
+            for entry in listing.flatten() {
+                let path = entry.path();
+                // Symlinks are neither followed nor counted
+                let metadata = match fs::symlink_metadata(&path) {
+                    Ok(metadata) => metadata,
+                    Err(_) => continue,
+                };
+                if metadata.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                if !metadata.is_file() {
+                    continue;
+                }
+
+                let relative = path.strip_prefix(&root).map(relative_string).unwrap_or_default();
+                let category = UsageCategory::of(&relative);
+                let usage = categories.entry(category).or_insert_with(|| CategoryUsage {
+                    category,
+                    path: category.relative_path().map(|p| p.to_string()),
+                    bytes: 0,
+                    files: 0,
+                });
+                usage.bytes += metadata.len();
+                usage.files += 1;
+                entries.entry(entry_key(&relative, category)).or_insert((category, 0)).1 += metadata.len();
+
+                jobs::checkpoint(&relative, metadata.len(), 1)?;
+                total_bytes += metadata.len();
+                total_files += 1;
+                if total_files % PROGRESS_INTERVAL == 0 {
+                    progress(&DiskUsageProgress {
+                        session: session.to_string(),
+                        files: total_files,
+                        bytes: total_bytes,
+                        current_path: relative,
+                        done: false,
+                    });
+                }
+            }
+        }
+
+        let mut categories: Vec<CategoryUsage> = UsageCategory::ALL
+            .iter()
+            .map(|category| categories.remove(category).unwrap_or_else(|| CategoryUsage {
+                category: *category,
+                path: category.relative_path().map(|p| p.to_string()),
+                bytes: 0,
+                files: 0,
+            }))
+            .collect();
+        categories.sort_by_key(|usage| std::cmp::Reverse(usage.bytes));
+
+        let mut largest: Vec<UsageEntry> = entries
+            .into_iter()
+            .map(|(path, (category, bytes))| UsageEntry { path, category, bytes })
+            .collect();
+        largest.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+        largest.truncate(LARGEST_ENTRIES);
+
+        let report = DiskUsageReport {
+            session: session.to_string(),
+            total_bytes,
+            total_files,
+            categories,
+            largest,
+            scanned_at: Local::now(),
+        };
+
+        let cached = CachedDiskUsage { total_bytes, scanned_at: report.scanned_at };
+        if let Err(e) = self.update_session_info(session, |session_info| session_info.disk_usage = Some(cached)) {
+            tracing::warn!("Could not cache disk usage for '{}': {}", session, e);
+        }
+
+        progress(&DiskUsageProgress {
+            session: session.to_string(),
+            files: total_files,
+            bytes: total_bytes,
+            current_path: String::new(),
+            done: true,
+        });
+        Ok(report)
+    }
+
+    // Quick total for listings. Sessions without a recent full scan are sized again; the result is
+    // returned in `session_info` but not persisted, only full scans write session.json
+    pub fn cached_disk_usage(&self, session_info: &mut SessionInfo) -> CachedDiskUsage {
+        if let Some(cached) = &session_info.disk_usage {
+            if Local::now() - cached.scanned_at < chrono::Duration::hours(CACHED_USAGE_MAX_AGE_HOURS) {
+                return cached.clone();
+            }
+        }
+        let cached = CachedDiskUsage {
+            total_bytes: dir_size(&self.session_dir(&session_info.name)),
+            scanned_at: Local::now(),
+        };
+        session_info.disk_usage = Some(cached.clone());
+        cached
+    }
+}
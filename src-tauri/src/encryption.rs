@@ -0,0 +1,200 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use age::secrecy::{ExposeSecret, SecretString};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use crate::CursorManagerError;
+
+// Key for encrypting or opening an archive. Key files are age identity files ("AGE-SECRET-KEY-1..."),
+// or recipient files ("age1...") which can only encrypt
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveKey {
+    Passphrase(String),
+    KeyFile(String),
+}
+
+// Keeps passphrases out of logs
+impl fmt::Debug for ArchiveKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveKey::Passphrase(_) => write!(f, "Passphrase(<redacted>)"),
+            ArchiveKey::KeyFile(path) => write!(f, "KeyFile({:?})", path),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum EncryptionKind {
+    Passphrase,
+    KeyFile,
+}
+
+impl ArchiveKey {
+    pub fn kind(&self) -> EncryptionKind {
+        match self {
+            ArchiveKey::Passphrase(_) => EncryptionKind::Passphrase,
+            ArchiveKey::KeyFile(_) => EncryptionKind::KeyFile,
+        }
+    }
+}
+
+fn key_lines(data: &str) -> impl Iterator<Item = &str> {
+    data.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+fn key_file_recipients(path: &str) -> Result<Vec<Box<dyn age::Recipient + Send>>, Box<dyn Error>> {
+    let data = fs::read_to_string(path).map_err(|e| format!("Cannot read key file {}: {}", path, e))?;
+    if key_lines(&data).all(|line| line.starts_with("age1")) {
+        let mut recipients: Vec<Box<dyn age::Recipient + Send>> = Vec::new();
+        for line in key_lines(&data) {
+            let recipient: age::x25519::Recipient = line.parse().map_err(|e| format!("Invalid public key in {}: {}", path, e))?;
+            recipients.push(Box::new(recipient));
+        }
+        if recipients.is_empty() {
+            return Err(format!("Key file {} holds no keys", path).into());
+        }
+        return Ok(recipients);
+    }
+    let identities = age::IdentityFile::from_buffer(data.as_bytes()).map_err(|e| format!("Invalid key file {}: {}", path, e))?;
+    Ok(identities.to_recipients()?)
+}
+
+fn key_file_identities(path: &str) -> Result<Vec<Box<dyn age::Identity>>, Box<dyn Error>> {
+    let data = fs::read_to_string(path).map_err(|e| format!("Cannot read key file {}: {}", path, e))?;
+    if key_lines(&data).all(|line| line.starts_with("age1")) {
+        return Err(format!("Key file {} only holds public keys; opening an archive needs the secret key", path).into());
+    }
+    let identities = age::IdentityFile::from_buffer(data.as_bytes()).map_err(|e| format!("Invalid key file {}: {}", path, e))?;
+    Ok(identities.into_identities()?)
+}
+
+pub fn encrypting_writer<W: Write>(key: &ArchiveKey, output: W) -> Result<age::stream::StreamWriter<W>, Box<dyn Error>> {
+    let encryptor = match key {
+        ArchiveKey::Passphrase(passphrase) => {
+            if passphrase.is_empty() {
+                return Err("Archive passphrase cannot be empty".into());
+            }
+            age::Encryptor::with_user_passphrase(SecretString::from(passphrase.clone()))
+        }
+        ArchiveKey::KeyFile(path) => {
+            let recipients = key_file_recipients(path)?;
+            age::Encryptor::with_recipients(recipients.iter().map(|recipient| recipient.as_ref() as &dyn age::Recipient))?
+        }
+    };
+    Ok(encryptor.wrap_output(output)?)
+}
+
+// Wrong passphrases and keys come back as CursorManagerError::ArchiveKeyInvalid
+pub fn decrypting_reader<R: Read>(key: &ArchiveKey, input: R, archive: &str) -> Result<age::stream::StreamReader<R>, Box<dyn Error>> {
+    let decryptor = age::Decryptor::new(input).map_err(|e| format!("Archive '{}' is not a readable encrypted archive: {}", archive, e))?;
+    let invalid = || Box::new(CursorManagerError::ArchiveKeyInvalid { name: archive.to_string() }) as Box<dyn Error>;
+
+    let decrypted = match key {
+        ArchiveKey::Passphrase(passphrase) => {
+            if !decryptor.is_scrypt() {
+                return Err(invalid());
+            }
+            let identity = age::scrypt::Identity::new(SecretString::from(passphrase.clone()));
+            decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity))
+        }
+        ArchiveKey::KeyFile(path) => {
+            let identities = key_file_identities(path)?;
+            decryptor.decrypt(identities.iter().map(|identity| identity.as_ref()))
+        }
+    };
+    decrypted.map_err(|e| match e {
+        age::DecryptError::DecryptionFailed | age::DecryptError::KeyDecryptionFailed | age::DecryptError::NoMatchingKeys => invalid(),
+        other => other.into(),
+    })
+}
+
+// Write a new age identity to `path` and return its public key
+pub fn generate_key_file(path: &Path) -> Result<String, Box<dyn Error>> {
+    if path.exists() {
+        return Err(format!("Key file already exists: {}", path.to_string_lossy()).into());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let identity = age::x25519::Identity::generate();
+    let public_key = identity.to_public().to_string();
+    let content = format!(
+        "# created: {}\n# public key: {}\n{}\n",
+        Local::now().to_rfc3339(),
+        public_key,
+        identity.to_string().expose_secret()
+    );
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    Ok(public_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::{generate_key_file, ArchiveKey};
+    use crate::test_support::{manager, session};
+    use crate::{ArchiveOptions, CursorManager, CursorManagerError, RestoreOptions};
+
+    fn is_key_invalid(error: Box<dyn std::error::Error>) -> bool {
+        matches!(error.downcast_ref::<CursorManagerError>(), Some(CursorManagerError::ArchiveKeyInvalid { .. }))
+    }
+
+    fn archive_with(manager: &CursorManager, key: ArchiveKey) -> String {
+        session(manager, "work", &[("User/settings.json", "{ \"secret\": 1 }")]);
+        manager.archive_session("work", &ArchiveOptions { key: Some(key), ..Default::default() }).unwrap();
+        manager.archive_entries().unwrap().remove(0).name
+    }
+
+    #[test]
+    fn a_wrong_passphrase_restores_nothing() {
+        let (_dir, manager) = manager();
+        let archive = archive_with(&manager, ArchiveKey::Passphrase("right".to_string()));
+
+        let wrong = ArchiveKey::Passphrase("wrong".to_string());
+        assert!(is_key_invalid(manager.restore_archive(&archive, Some("copy"), false, Some(&wrong), &RestoreOptions::default()).unwrap_err()));
+        assert!(manager.restore_archive(&archive, Some("copy"), false, None, &RestoreOptions::default()).is_err());
+        assert!(!manager.session_dir("copy").exists());
+
+        let right = ArchiveKey::Passphrase("right".to_string());
+        manager.restore_archive(&archive, Some("copy"), true, Some(&right), &RestoreOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(manager.session_dir("copy").join("User/settings.json")).unwrap(), "{ \"secret\": 1 }");
+    }
+
+    #[test]
+    fn another_key_file_cannot_open_the_archive() {
+        let (dir, manager) = manager();
+        let owner = dir.path().join("keys/owner.txt");
+        let other = dir.path().join("keys/other.txt");
+        generate_key_file(&owner).unwrap();
+        generate_key_file(&other).unwrap();
+        let archive = archive_with(&manager, ArchiveKey::KeyFile(owner.to_string_lossy().to_string()));
+
+        let wrong = ArchiveKey::KeyFile(other.to_string_lossy().to_string());
+        assert!(is_key_invalid(manager.restore_archive(&archive, Some("copy"), false, Some(&wrong), &RestoreOptions::default()).unwrap_err()));
+        // A passphrase cannot open a key file archive either
+        let passphrase = ArchiveKey::Passphrase("guess".to_string());
+        assert!(is_key_invalid(manager.restore_archive(&archive, Some("copy"), false, Some(&passphrase), &RestoreOptions::default()).unwrap_err()));
+        assert!(!manager.session_dir("copy").exists());
+        assert!(generate_key_file(&owner).is_err());
+
+        let right = ArchiveKey::KeyFile(owner.to_string_lossy().to_string());
+        manager.restore_archive(&archive, Some("copy"), false, Some(&right), &RestoreOptions::default()).unwrap();
+        assert!(manager.session_dir("copy").join("User/settings.json").is_file());
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::profile::{dir_size, write_file_atomic, EXTENSIONS_DIR};
+use crate::CursorManager;
+
+pub const EXTENSIONS_MANIFEST: &str = "extensions.json";
+// Folders of uninstalled extensions that the editor has not cleaned up yet
+const OBSOLETE_MARKER: &str = ".obsolete";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExtensionInfo {
+    // Lowercase "publisher.name", the form the editor uses for ids
+    pub id: String,
+    pub publisher: String,
+    pub name: String,
+    pub display_name: Option<String>,
+    pub version: String,
+    // In the session's disabled_extensions, so launched with --disable-extension. Extensions the user
+    // disabled from inside the editor are recorded in its state database, which is not read here
+    pub disabled_by_manager: bool,
+    pub size: u64,
+    pub path: String,
+    // Listed in extensions.json
+    pub registered: bool,
+    // The extension folder exists on disk
+    pub present: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExtensionUsage {
+    pub session: String,
+    pub version: String,
+    pub disabled_by_manager: bool,
+}
+
+// One extension across all sessions, grouped by installed version
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExtensionSummary {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub versions: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PackageJson {
+    pub publisher: Option<String>,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    #[serde(rename = "displayName")]
+    pub display_name: Option<String>,
+}
+
+pub fn normalize_extension_id(id: &str) -> String {
+    id.trim().to_lowercase()
+}
+
+// Entries of extensions.json, empty when the file is missing or unreadable
+fn read_manifest_entries(extensions_dir: &Path) -> Vec<serde_json::Value> {
+    fs::read_to_string(extensions_dir.join(EXTENSIONS_MANIFEST))
+        .ok()
+        .and_then(|data| serde_json::from_str::<Vec<serde_json::Value>>(&data).ok())
+        .unwrap_or_default()
+}
+
+fn manifest_folder(entry: &serde_json::Value) -> Option<String> {
+    if let Some(relative) = entry.get("relativeLocation").and_then(|v| v.as_str()) {
+        return Some(relative.to_string());
+    }
+    entry
+        .pointer("/location/path")
+        .or_else(|| entry.get("location"))
+        .and_then(|v| v.as_str())
+        .and_then(|path| Path::new(path).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+}
+
+fn read_obsolete(extensions_dir: &Path) -> HashSet<String> {
+    fs::read_to_string(extensions_dir.join(OBSOLETE_MARKER))
+        .ok()
+        .and_then(|data| serde_json::from_str::<HashMap<String, serde_json::Value>>(&data).ok())
+        .map(|map| map.into_keys().collect())
+        .unwrap_or_default()
+}
+
+// Inventory of one extensions directory. `disabled` holds the ids the manager launches with --disable-extension
+pub fn scan_extensions_dir(extensions_dir: &Path, disabled: &[String]) -> Vec<ExtensionInfo> {
+    let disabled: HashSet<String> = disabled.iter().map(|id| normalize_extension_id(id)).collect();
+    let obsolete = read_obsolete(extensions_dir);
+    let manifest = read_manifest_entries(extensions_dir);
+    let registered_folders: HashSet<String> = manifest.iter().filter_map(manifest_folder).collect();
+
+    let mut extensions = Vec::new();
+    let mut seen_folders = HashSet::new();
+
+    if let Ok(entries) = fs::read_dir(extensions_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let folder = entry.file_name().to_string_lossy().to_string();
+            if !path.is_dir() || folder.starts_with('.') || obsolete.contains(&folder) {
+                continue;
+            }
+            let package: PackageJson = match fs::read_to_string(path.join("package.json")).ok().and_then(|data| serde_json::from_str(&data).ok()) {
+                Some(package) => package,
+                None => continue,
+            };
+            let (publisher, name) = match (package.publisher, package.name) {
+                (Some(publisher), Some(name)) => (publisher, name),
+                _ => continue,
+            };
+
+            let id = normalize_extension_id(&format!("{}.{}", publisher, name));
+            seen_folders.insert(folder.clone());
+            extensions.push(ExtensionInfo {
+                disabled_by_manager: disabled.contains(&id),
+                id,
+                publisher,
+                name,
+                display_name: package.display_name,
+                version: package.version.unwrap_or_default(),
+                size: dir_size(&path),
+                path: path.to_string_lossy().to_string(),
+                registered: registered_folders.contains(&folder),
+                present: true,
+            });
+        }
+    }
+
+    // Registered in extensions.json but the folder is gone
+    for entry in &manifest {
+        let folder = match manifest_folder(entry) {
+            Some(folder) if !seen_folders.contains(&folder) && !obsolete.contains(&folder) => folder,
+            _ => continue,
+        };
+        let id = match entry.pointer("/identifier/id").and_then(|v| v.as_str()) {
+            Some(id) => normalize_extension_id(id),
+            None => continue,
+        };
+        let (publisher, name) = id.split_once('.').map(|(p, n)| (p.to_string(), n.to_string())).unwrap_or_default();
+        extensions.push(ExtensionInfo {
+            disabled_by_manager: disabled.contains(&id),
+            id,
+            publisher,
+            name,
+            display_name: None,
+            version: entry.get("version").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+            size: 0,
+            path: extensions_dir.join(&folder).to_string_lossy().to_string(),
+            registered: true,
+            present: false,
+        });
+    }
+
+    extensions.sort_by(|a, b| a.id.cmp(&b.id).then_with(|| a.version.cmp(&b.version)));
+    extensions
+}
+
+// Record an installed extension in extensions.json, replacing any other version of it
+pub fn register_extension(extensions_dir: &Path, id: &str, version: &str, folder: &str) -> Result<(), Box<dyn Error>> {
+    let id = normalize_extension_id(id);
+    let mut entries = read_manifest_entries(extensions_dir);
+    entries.retain(|entry| entry.pointer("/identifier/id").and_then(|v| v.as_str()).map(normalize_extension_id) != Some(id.clone()));
+
+    let location = extensions_dir.join(folder);
+    entries.push(serde_json::json!({
+        "identifier": { "id": id },
+        "version": version,
+        "location": { "$mid": 1, "path": location.to_string_lossy(), "scheme": "file" },
+        "relativeLocation": folder,
+        "metadata": {
+            "installedTimestamp": chrono::Local::now().timestamp_millis(),
+            "source": "vsix",
+        },
+    }));
+
+    write_file_atomic(&extensions_dir.join(EXTENSIONS_MANIFEST), serde_json::to_string(&entries)?.as_bytes())?;
+    Ok(())
+}
+
+// Delete every installed version of an extension and drop it from extensions.json
+pub fn remove_extension(extensions_dir: &Path, id: &str) -> Result<(), Box<dyn Error>> {
+    let id = normalize_extension_id(id);
+    for extension in scan_extensions_dir(extensions_dir, &[]) {
+        if extension.id == id && extension.present {
+            fs::remove_dir_all(&extension.path)?;
+        }
+    }
+
+    let mut entries = read_manifest_entries(extensions_dir);
+    let before = entries.len();
+    entries.retain(|entry| entry.pointer("/identifier/id").and_then(|v| v.as_str()).map(normalize_extension_id) != Some(id.clone()));
+    if entries.len() != before {
+        write_file_atomic(&extensions_dir.join(EXTENSIONS_MANIFEST), serde_json::to_string(&entries)?.as_bytes())?;
+    }
+    Ok(())
+}
+
+impl CursorManager {
+    pub fn list_session_extensions(&self, session: &str) -> Result<Vec<ExtensionInfo>, Box<dyn Error>> {
+        let session_info = self.read_session_info(session)?;
+        Ok(scan_extensions_dir(&self.session_dir(session).join(EXTENSIONS_DIR), &session_info.disabled_extensions))
+    }
+
+    // Which sessions have an extension, optionally only at one version
+    pub fn find_extension_sessions(&self, extension_id: &str, version: Option<&str>) -> Result<Vec<ExtensionUsage>, Box<dyn Error>> {
+        let wanted = normalize_extension_id(extension_id);
+        let mut usages = Vec::new();
+
+        for session in self.list_sessions()? {
+            for extension in self.list_session_extensions(&session.name)? {
+                if extension.id == wanted && extension.present && version.map(|v| v == extension.version).unwrap_or(true) {
+                    usages.push(ExtensionUsage {
+                        session: session.name.clone(),
+                        version: extension.version,
+                        disabled_by_manager: extension.disabled_by_manager,
+                    });
+                }
+            }
+        }
+        Ok(usages)
+    }
+
+    pub fn extension_overview(&self) -> Result<Vec<ExtensionSummary>, Box<dyn Error>> {
+        let mut summaries: BTreeMap<String, ExtensionSummary> = BTreeMap::new();
+
+        for session in self.list_sessions()? {
+            for extension in self.list_session_extensions(&session.name)? {
+                if !extension.present {
+                    continue;
+                }
+                let summary = summaries.entry(extension.id.clone()).or_insert_with(|| ExtensionSummary {
+                    id: extension.id.clone(),
+                    display_name: None,
+                    versions: BTreeMap::new(),
+                });
+                if summary.display_name.is_none() {
+                    summary.display_name = extension.display_name.clone();
+                }
+                summary.versions.entry(extension.version).or_default().push(session.name.clone());
+            }
+        }
+        Ok(summaries.into_values().collect())
+    }
+
+    // Disabled extensions are passed to the editor as --disable-extension on launch
+    pub fn set_extension_enabled(&self, session: &str, extension_id: &str, enabled: bool) -> Result<String, Box<dyn Error>> {
+        let mut session_info = self.read_session_info(session)?;
+        let id = normalize_extension_id(extension_id);
+
+        session_info.disabled_extensions.retain(|existing| normalize_extension_id(existing) != id);
+        if !enabled {
+            session_info.disabled_extensions.push(id.clone());
+            session_info.disabled_extensions.sort();
+        }
+        session_info.modified = Some(chrono::Local::now());
+        self.write_session_info(&session_info)?;
+
+        Ok(format!("Extension '{}' {} in session '{}'", id, if enabled { "enabled" } else { "disabled" }, session))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install(extensions_dir: &Path, folder: &str, publisher: &str, name: &str, version: &str) {
+        let dir = extensions_dir.join(folder);
+        fs::create_dir_all(&dir).unwrap();
+        let package = serde_json::json!({ "publisher": publisher, "name": name, "version": version });
+        fs::write(dir.join("package.json"), package.to_string()).unwrap();
+    }
+
+    #[test]
+    fn inventory_reports_manager_disabled_registered_and_missing_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let extensions_dir = dir.path();
+        install(extensions_dir, "acme.tool-1.0.0", "Acme", "Tool", "1.0.0");
+        install(extensions_dir, "acme.old-0.1.0", "acme", "old", "0.1.0");
+        register_extension(extensions_dir, "acme.tool", "1.0.0", "acme.tool-1.0.0").unwrap();
+        register_extension(extensions_dir, "acme.gone", "2.0.0", "acme.gone-2.0.0").unwrap();
+        fs::write(extensions_dir.join(OBSOLETE_MARKER), r#"{"acme.old-0.1.0": true}"#).unwrap();
+
+        let extensions = scan_extensions_dir(extensions_dir, &["ACME.Tool".to_string()]);
+        let ids: Vec<&str> = extensions.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["acme.gone", "acme.tool"]);
+
+        let tool = &extensions[1];
+        assert!(tool.disabled_by_manager && tool.registered && tool.present);
+        let gone = &extensions[0];
+        assert!(!gone.disabled_by_manager && gone.registered && !gone.present);
+        assert_eq!(gone.version, "2.0.0");
+    }
+}
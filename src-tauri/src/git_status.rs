@@ -0,0 +1,149 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use serde::{Deserialize, Serialize};
+use crate::{CursorManager, SessionInfo};
+
+// Last commit on the checked out branch
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommitSummary {
+    pub hash: String,
+    pub summary: String,
+    pub author: String,
+    pub date: String,
+}
+
+// Local git state of one project folder attached to a session
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProjectGitStatus {
+    pub path: String,
+    pub is_repo: bool,
+    pub branch: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    pub dirty_files: u32,
+    pub stash_count: u32,
+    pub last_commit: Option<CommitSummary>,
+    pub error: Option<String>,
+}
+
+impl ProjectGitStatus {
+    fn empty(path: &Path) -> Self {
+        Self {
+            path: path.to_string_lossy().to_string(),
+            is_repo: false,
+            branch: None,
+            upstream: None,
+            ahead: 0,
+            behind: 0,
+            dirty_files: 0,
+            stash_count: 0,
+            last_commit: None,
+            error: None,
+        }
+    }
+}
+
+// Run a git command against a working tree. Only local plumbing is used, nothing here touches a remote.
+fn run_git(repo: &Path, args: &[&str]) -> std::io::Result<Option<String>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()?;
+
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&output.stdout).to_string()))
+}
+
+pub fn project_git_status(path: &Path) -> ProjectGitStatus {
+    let mut status = ProjectGitStatus::empty(path);
+
+    if !path.is_dir() {
+        status.error = Some("Project folder does not exist".to_string());
+        return status;
+    }
+
+    match run_git(path, &["rev-parse", "--is-inside-work-tree"]) {
+        Ok(Some(out)) if out.trim() == "true" => status.is_repo = true,
+        Ok(_) => return status,
+        Err(e) => {
+            status.error = Some(format!("Failed to run git: {}", e));
+            return status;
+        }
+    }
+
+    // Branch, upstream, ahead/behind and dirty files all come from one porcelain v2 call
+    if let Ok(Some(out)) = run_git(path, &["status", "--porcelain=v2", "--branch"]) {
+        for line in out.lines() {
+            if let Some(head) = line.strip_prefix("# branch.head ") {
+                if head != "(detached)" {
+                    status.branch = Some(head.to_string());
+                }
+            } else if let Some(upstream) = line.strip_prefix("# branch.upstream ") {
+                status.upstream = Some(upstream.to_string());
+            } else if let Some(ab) = line.strip_prefix("# branch.ab ") {
+                for part in ab.split_whitespace() {
+                    if let Some(n) = part.strip_prefix('+') {
+                        status.ahead = n.parse().unwrap_or(0);
+                    } else if let Some(n) = part.strip_prefix('-') {
+                        status.behind = n.parse().unwrap_or(0);
+                    }
+                }
+            } else if !line.starts_with('#') && !line.is_empty() {
+                status.dirty_files += 1;
+            }
+        }
+    } else {
+        status.error = Some("git status failed".to_string());
+    }
+
+    // refs/stash is missing entirely when nothing is stashed
+    if let Ok(Some(out)) = run_git(path, &["rev-list", "--walk-reflogs", "--count", "refs/stash"]) {
+        status.stash_count = out.trim().parse().unwrap_or(0);
+    }
+
+    // Fails on a repository without commits, which simply leaves last_commit empty
+    if let Ok(Some(out)) = run_git(path, &["log", "-1", "--format=%H%x1f%s%x1f%an%x1f%cI"]) {
+        let fields: Vec<&str> = out.trim_end().split('\x1f').collect();
+        if fields.len() == 4 {
+            status.last_commit = Some(CommitSummary {
+                hash: fields[0].to_string(),
+                summary: fields[1].to_string(),
+                author: fields[2].to_string(),
+                date: fields[3].to_string(),
+            });
+        }
+    }
+
+    status
+}
+
+impl CursorManager {
+    // Project paths may be stored relative to the configured workspace base
+    pub fn resolve_project_path(&self, project: &str) -> PathBuf {
+        let path = PathBuf::from(project);
+        if path.is_absolute() {
+            path
+        } else {
+            Path::new(&self.config.workspace_base).join(path)
+        }
+    }
+
+    pub fn git_status_for(&self, session_info: &SessionInfo) -> Vec<ProjectGitStatus> {
+        session_info.projects
+            .iter()
+            .map(|project| project_git_status(&self.resolve_project_path(project)))
+            .collect()
+    }
+
+    pub fn session_git_status(&self, session: &str) -> Result<Vec<ProjectGitStatus>, Box<dyn Error>> {
+        let session_info = self.read_session_info(session)?;
+        Ok(self.git_status_for(&session_info))
+    }
+}
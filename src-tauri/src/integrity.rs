@@ -0,0 +1,306 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use crate::archive::{is_private_manifest, open_tar_stream, ArchiveFormat, HashingReader, HashingWriter, PrivateManifest};
+use crate::jobs;
+use crate::profile::relative_string;
+use crate::snapshot::{lock_store, read_snapshot_index};
+use crate::{ArchiveKey, CursorManager};
+
+pub const ARCHIVE_VERIFIED_EVENT: &str = "archive-verified";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModifiedFile {
+    pub path: String,
+    pub expected_size: u64,
+    pub actual_size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchiveVerification {
+    pub archive: String,
+    pub verified_at: DateTime<Local>,
+    pub ok: bool,
+    // None when there was no manifest hash to compare with
+    pub content_hash_ok: Option<bool>,
+    // False for manifests written before per-file checksums and for encrypted archives checked without
+    // their key; only the archive hash (and readability) were checked
+    pub per_file: bool,
+    pub checked_files: u64,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+    pub modified: Vec<ModifiedFile>,
+    // Unreadable archive, truncated stream, missing manifest
+    pub errors: Vec<String>,
+}
+
+impl ArchiveVerification {
+    fn new(archive: &str) -> Self {
+        Self {
+            archive: archive.to_string(),
+            verified_at: Local::now(),
+            ok: false,
+            content_hash_ok: None,
+            per_file: false,
+            checked_files: 0,
+            missing: Vec::new(),
+            extra: Vec::new(),
+            modified: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn finish(mut self) -> Self {
+        self.ok = self.content_hash_ok != Some(false)
+            && self.missing.is_empty()
+            && self.extra.is_empty()
+            && self.modified.is_empty()
+            && self.errors.is_empty();
+        self
+    }
+
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.content_hash_ok == Some(false) {
+            parts.push("archive hash mismatch".to_string());
+        }
+        for (count, label) in [(self.missing.len(), "missing"), (self.extra.len(), "extra"), (self.modified.len(), "modified")] {
+            if count > 0 {
+                parts.push(format!("{} {} file(s)", count, label));
+            }
+        }
+        parts.extend(self.errors.iter().cloned());
+        if parts.is_empty() {
+            "ok".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+enum ScanError {
+    // The caller's problem rather than the archive's: a wrong key or a cancelled job
+    Key(Box<dyn Error>),
+    Archive(Box<dyn Error>),
+}
+
+impl From<io::Error> for ScanError {
+    fn from(e: io::Error) -> Self {
+        ScanError::Archive(e.into())
+    }
+}
+
+fn hash_file(path: &std::path::Path) -> io::Result<String> {
+    let mut reader = HashingReader::new(File::open(path)?);
+    io::copy(&mut reader, &mut io::sink())?;
+    Ok(format!("sha256:{}", reader.hex_digest()))
+}
+
+impl CursorManager {
+    // Re-hash an archive against its manifest: the archive file as a whole, then every file inside it.
+    // Without a key, encrypted archives only get the archive hash checked
+    pub fn verify_archive(&self, archive: &str, key: Option<&ArchiveKey>) -> Result<ArchiveVerification, Box<dyn Error>> {
+        let entry = self.resolve_archive(archive)?;
+        let mut verification = ArchiveVerification::new(&entry.name);
+
+        let manifest = match (entry.format, entry.read_manifest()) {
+            (ArchiveFormat::Directory, _) | (_, None) => {
+                verification.errors.push("no manifest with checksums to verify against".to_string());
+                return Ok(verification.finish());
+            }
+            (_, Some(manifest)) => manifest,
+        };
+
+        match hash_file(&entry.path) {
+            Ok(hash) => verification.content_hash_ok = Some(hash == manifest.content_hash),
+            Err(e) => {
+                verification.errors.push(format!("cannot read archive: {}", e));
+                return Ok(verification.finish());
+            }
+        }
+        if entry.format == ArchiveFormat::EncryptedTarZst && key.is_none() {
+            return Ok(verification.finish());
+        }
+
+        // (path, size, sha256) of everything found in the archive
+        let mut actual: Vec<(String, u64, String)> = Vec::new();
+        let mut embedded: Option<PrivateManifest> = None;
+        let mut chunk_errors = Vec::new();
+        let scan: Result<(), ScanError> = match entry.format {
+            // A truncated or damaged stream surfaces as an error part way through the entries
+            ArchiveFormat::TarZst | ArchiveFormat::EncryptedTarZst => (|| {
+                // An intact archive that cannot be opened means the key is wrong, which is the caller's mistake
+                let mut tar = match open_tar_stream(&entry, key) {
+                    Ok(tar) => tar,
+                    Err(e) if verification.content_hash_ok == Some(true) => return Err(ScanError::Key(e)),
+                    Err(e) => return Err(ScanError::Archive(e)),
+                };
+                for tar_entry in tar.entries()? {
+                    let tar_entry = tar_entry?;
+                    if !tar_entry.header().entry_type().is_file() {
+                        continue;
+                    }
+                    let tar_path = tar_entry.path()?.to_path_buf();
+                    if is_private_manifest(&tar_path) {
+                        embedded = Some(serde_json::from_reader(tar_entry).map_err(io::Error::from)?);
+                        continue;
+                    }
+                    let mut reader = HashingReader::new(tar_entry);
+                    io::copy(&mut reader, &mut io::sink())?;
+                    let size = reader.read;
+                    let path = relative_string(&tar_path);
+                    jobs::checkpoint(&path, size, 1).map_err(|e| ScanError::Key(e.into()))?;
+                    actual.push((path, size, reader.hex_digest()));
+                }
+                Ok(())
+            })(),
+            // Files are rebuilt from their chunks; a lost chunk fails that file only
+            _ => (|| {
+                let _store = lock_store();
+                let index = read_snapshot_index(&entry.path).map_err(ScanError::Archive)?;
+                for file in &index.files {
+                    jobs::checkpoint(&file.path, file.size, 1).map_err(|e| ScanError::Key(e.into()))?;
+                    let mut writer = HashingWriter::new(io::sink());
+                    match self.read_snapshot_file(file, &mut writer) {
+                        Ok(()) => {
+                            let size = writer.written;
+                            actual.push((file.path.clone(), size, writer.hex_digest()));
+                        }
+                        Err(e) => {
+                            actual.push((file.path.clone(), writer.written, String::new()));
+                            chunk_errors.push(e.to_string());
+                        }
+                    }
+                }
+                Ok(())
+            })(),
+        };
+        match scan {
+            Ok(()) => {}
+            Err(ScanError::Key(e)) => return Err(e),
+            Err(ScanError::Archive(e)) => verification.errors.push(format!("archive is unreadable after {} file(s): {}", actual.len(), e)),
+        }
+        verification.errors.extend(chunk_errors);
+        verification.checked_files = actual.len() as u64;
+
+        let files = match entry.format {
+            ArchiveFormat::EncryptedTarZst => match embedded {
+                Some(embedded) => embedded.files,
+                None => {
+                    verification.errors.push("embedded manifest is missing".to_string());
+                    Vec::new()
+                }
+            },
+            _ => manifest.files,
+        };
+        verification.per_file = !files.is_empty() || manifest.file_count == 0;
+        if !verification.per_file {
+            if verification.checked_files != manifest.file_count {
+                verification.errors.push(format!("expected {} file(s), found {}", manifest.file_count, verification.checked_files));
+            }
+            return Ok(verification.finish());
+        }
+
+        let mut expected: BTreeMap<String, (u64, String)> = files.into_iter().map(|f| (f.path, (f.size, f.sha256))).collect();
+        for (path, actual_size, actual_hash) in actual {
+            match expected.remove(&path) {
+                Some((_, hash)) if hash == actual_hash => {}
+                Some((expected_size, _)) => verification.modified.push(ModifiedFile { path, expected_size, actual_size }),
+                None => verification.extra.push(path),
+            }
+        }
+        verification.missing = expected.into_keys().collect();
+        Ok(verification.finish())
+    }
+
+    // Verify every archive, reporting each result as it is done
+    pub fn verify_archives(&self, mut on_result: impl FnMut(&ArchiveVerification)) -> Result<Vec<ArchiveVerification>, Box<dyn Error>> {
+        let mut results = Vec::new();
+        for entry in self.archive_entries()? {
+            let verification = self.verify_archive(&entry.name, None);
+            jobs::check_cancelled()?;
+            let verification = match verification {
+                Ok(verification) => verification,
+                Err(e) => {
+                    let mut failed = ArchiveVerification::new(&entry.name);
+                    failed.errors.push(e.to_string());
+                    failed.finish()
+                }
+            };
+            if !verification.ok {
+                tracing::warn!("Archive '{}' failed verification: {}", verification.archive, verification.summary());
+            }
+            on_result(&verification);
+            results.push(verification);
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::archive::{write_tar_zst, ArchiveEntry};
+    use crate::test_support::{manager, session};
+    use crate::{ArchiveMode, ArchiveOptions, CursorManager, CursorManagerError, RestoreOptions};
+
+    fn full_archive(manager: &CursorManager) -> ArchiveEntry {
+        session(manager, "work", &[("User/settings.json", "{ \"a\": 1 }"), ("User/keybindings.json", "[]")]);
+        // create_archive leaves the session in place, so it can be changed and packed again
+        let name = manager.create_archive("work", &ArchiveOptions { mode: Some(ArchiveMode::Full), ..Default::default() }).unwrap().0.name;
+        manager.resolve_archive(&name).unwrap()
+    }
+
+    #[test]
+    fn an_untouched_archive_verifies() {
+        let (_dir, manager) = manager();
+        let entry = full_archive(&manager);
+        let verification = manager.verify_archive(&entry.name, None).unwrap();
+        assert!(verification.ok, "{}", verification.summary());
+        assert!(verification.per_file);
+        // session.json and the two files
+        assert_eq!(verification.checked_files, 3);
+    }
+
+    #[test]
+    fn a_tampered_file_is_reported_and_blocks_a_verified_restore() {
+        let (_dir, manager) = manager();
+        let entry = full_archive(&manager);
+
+        // Rewrite the archive from a changed profile, keeping its manifest
+        let source = manager.session_dir("work");
+        fs::write(source.join("User/settings.json"), "{ \"a\": 2 }").unwrap();
+        fs::remove_file(source.join("User/keybindings.json")).unwrap();
+        fs::write(source.join("User/extra.json"), "{}").unwrap();
+        write_tar_zst(&source, &entry.path, 3, &[], None, None).unwrap();
+
+        let verification = manager.verify_archive(&entry.name, None).unwrap();
+        assert!(!verification.ok);
+        assert_eq!(verification.content_hash_ok, Some(false));
+        assert_eq!(verification.modified.iter().map(|file| file.path.as_str()).collect::<Vec<_>>(), vec!["User/settings.json"]);
+        assert_eq!(verification.missing, vec!["User/keybindings.json"]);
+        assert_eq!(verification.extra, vec!["User/extra.json"]);
+        assert_eq!(verification.summary(), "archive hash mismatch, 1 missing file(s), 1 extra file(s), 1 modified file(s)");
+
+        let error = manager.restore_archive(&entry.name, Some("copy"), true, None, &RestoreOptions::default()).unwrap_err();
+        assert!(matches!(error.downcast_ref::<CursorManagerError>(), Some(CursorManagerError::ArchiveCorrupted { .. })));
+        assert!(!manager.session_dir("copy").exists());
+    }
+
+    #[test]
+    fn a_damaged_archive_file_fails_verification() {
+        let (_dir, manager) = manager();
+        let entry = full_archive(&manager);
+        let mut data = fs::read(&entry.path).unwrap();
+        let middle = data.len() / 2;
+        data[middle] ^= 0xff;
+        fs::write(&entry.path, data).unwrap();
+
+        let verification = manager.verify_archive(&entry.name, None).unwrap();
+        assert!(!verification.ok);
+        assert_eq!(verification.content_hash_ok, Some(false));
+    }
+}
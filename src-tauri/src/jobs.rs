@@ -0,0 +1,455 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Runtime};
+use crate::profile::dir_size;
+use crate::{ArchiveKey, ArchiveOptions, CloneOptions, Config, CursorManager, RestoreOptions, UsageCategory};
+
+pub const JOB_PROGRESS_EVENT: &str = "job-progress";
+pub const JOB_FINISHED_EVENT: &str = "job-finished";
+// Finished jobs kept for list_jobs
+const RECENT_JOBS: usize = 50;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum JobRequest {
+    Archive {
+        session: String,
+        #[serde(default)]
+        options: Option<ArchiveOptions>,
+    },
+    Restore {
+        archive: String,
+        #[serde(default)]
+        new_session_name: Option<String>,
+        #[serde(default)]
+        skip_verify: bool,
+        #[serde(default)]
+        key: Option<ArchiveKey>,
+        #[serde(default)]
+        options: Option<RestoreOptions>,
+    },
+    Clone {
+        source: String,
+        new_name: String,
+        #[serde(default)]
+        options: Option<CloneOptions>,
+    },
+    // All sessions when none are named
+    Clean {
+        #[serde(default)]
+        sessions: Option<Vec<String>>,
+        #[serde(default)]
+        categories: Vec<UsageCategory>,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    // All archives when none is named
+    Verify {
+        #[serde(default)]
+        archive: Option<String>,
+        #[serde(default)]
+        key: Option<ArchiveKey>,
+    },
+    DiskUsage {
+        session: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum JobKind {
+    Archive,
+    Restore,
+    Clone,
+    Clean,
+    Verify,
+    DiskUsage,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum JobState {
+    Running,
+    // Cancel requested; the job stops at its next checkpoint
+    Cancelling,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct JobProgress {
+    pub bytes: u64,
+    pub files: u64,
+    // Estimates taken before the job starts; None when unknown
+    pub total_bytes: Option<u64>,
+    pub total_files: Option<u64>,
+    pub current_path: Option<String>,
+    pub eta_seconds: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: JobKind,
+    // Session or archive the job works on
+    pub target: Option<String>,
+    pub state: JobState,
+    pub started: DateTime<Local>,
+    pub finished: Option<DateTime<Local>>,
+    pub progress: JobProgress,
+    // The operation's usual return value, once completed
+    pub result: Option<serde_json::Value>,
+    // Why a job failed, or for a cancelled one, where it stopped
+    pub error: Option<String>,
+}
+
+impl JobRequest {
+    fn kind(&self) -> JobKind {
+        match self {
+            JobRequest::Archive { .. } => JobKind::Archive,
+            JobRequest::Restore { .. } => JobKind::Restore,
+            JobRequest::Clone { .. } => JobKind::Clone,
+            JobRequest::Clean { .. } => JobKind::Clean,
+            JobRequest::Verify { .. } => JobKind::Verify,
+            JobRequest::DiskUsage { .. } => JobKind::DiskUsage,
+        }
+    }
+
+    fn target(&self) -> Option<String> {
+        match self {
+            JobRequest::Archive { session, .. } | JobRequest::DiskUsage { session } => Some(session.clone()),
+            JobRequest::Restore { archive, .. } => Some(archive.clone()),
+            JobRequest::Clone { source, .. } => Some(source.clone()),
+            JobRequest::Clean { sessions, .. } => sessions.as_ref().map(|sessions| sessions.join(", ")),
+            JobRequest::Verify { archive, .. } => archive.clone(),
+        }
+    }
+}
+
+struct JobSlot {
+    info: JobInfo,
+    cancel: Arc<AtomicBool>,
+}
+
+// CursorManager is rebuilt for every command, so running and recent jobs live outside of it
+static JOBS: Mutex<Vec<JobSlot>> = Mutex::new(Vec::new());
+
+fn jobs() -> MutexGuard<'static, Vec<JobSlot>> {
+    JOBS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// The job running on this thread. Operations report through checkpoint() without knowing about it
+struct JobContext {
+    id: String,
+    cancel: Arc<AtomicBool>,
+    started: Instant,
+    // None until the first report, which goes out at the first checkpoint
+    last_report: Option<Instant>,
+    interval: Duration,
+    progress: JobProgress,
+    report: Reporter,
+}
+
+// Sends a job event, JOB_PROGRESS_EVENT or JOB_FINISHED_EVENT, to wherever jobs are watched from
+type Reporter = Arc<dyn Fn(&str, &JobInfo) + Send + Sync>;
+
+thread_local! {
+    static CURRENT: RefCell<Option<JobContext>> = const { RefCell::new(None) };
+}
+
+impl JobContext {
+    fn publish(&mut self) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        self.progress.eta_seconds = match self.progress.total_bytes {
+            Some(total) if self.progress.bytes > 0 => Some((elapsed * total.saturating_sub(self.progress.bytes) as f64 / self.progress.bytes as f64) as u64),
+            _ => None,
+        };
+        let info = jobs().iter_mut().find(|slot| slot.info.id == self.id).map(|slot| {
+            slot.info.progress = self.progress.clone();
+            slot.info.clone()
+        });
+        if let Some(info) = info {
+            (self.report)(JOB_PROGRESS_EVENT, &info);
+        }
+    }
+}
+
+// Called by long operations after each file. Outside of a job it does nothing; inside one it records
+// progress and fails once the job has been cancelled, so the operation unwinds through its usual error path
+pub fn checkpoint(path: &str, bytes: u64, files: u64) -> io::Result<()> {
+    CURRENT.with(|current| {
+        let mut current = current.borrow_mut();
+        let context = match current.as_mut() {
+            Some(context) => context,
+            None => return Ok(()),
+        };
+        if context.cancel.load(Ordering::Relaxed) {
+            return Err(io::Error::other("Job cancelled"));
+        }
+        context.progress.bytes += bytes;
+        context.progress.files += files;
+        context.progress.current_path = Some(path.to_string());
+        if context.last_report.is_none_or(|last| last.elapsed() >= context.interval) {
+            context.last_report = Some(Instant::now());
+            context.publish();
+        }
+        Ok(())
+    })
+}
+
+// For loops that keep going past failed items, so a cancelled job does not look like one more failure
+pub fn check_cancelled() -> io::Result<()> {
+    let cancelled = CURRENT.with(|current| current.borrow().as_ref().is_some_and(|context| context.cancel.load(Ordering::Relaxed)));
+    if cancelled {
+        return Err(io::Error::other("Job cancelled"));
+    }
+    Ok(())
+}
+
+fn set_totals(total_bytes: Option<u64>, total_files: Option<u64>) {
+    CURRENT.with(|current| {
+        if let Some(context) = current.borrow_mut().as_mut() {
+            context.progress.total_bytes = total_bytes;
+            context.progress.total_files = total_files;
+        }
+    });
+}
+
+impl CursorManager {
+    fn run_job(&self, request: JobRequest) -> Result<serde_json::Value, Box<dyn Error>> {
+        let value = match request {
+            JobRequest::Archive { session, options } => {
+                set_totals(Some(dir_size(&self.session_dir(&session))), None);
+                serde_json::to_value(self.archive_session(&session, &options.unwrap_or_default())?)?
+            }
+            JobRequest::Restore { archive, new_session_name, skip_verify, key, options } => {
+                // Verifying reads every file once before the restore reads them again
+                let passes = if skip_verify { 1 } else { 2 };
+                match self.resolve_archive(&archive)?.read_manifest() {
+                    Some(manifest) => set_totals(Some(manifest.total_bytes * passes), Some(manifest.file_count * passes)),
+                    None => set_totals(None, None),
+                }
+                let restored = self.restore_archive(&archive, new_session_name.as_deref(), !skip_verify, key.as_ref(), &options.unwrap_or_default())?;
+                serde_json::to_value(restored)?
+            }
+            JobRequest::Clone { source, new_name, options } => {
+                let options = options.unwrap_or_default();
+                let source_dir = self.session_dir(&source);
+                set_totals(Some(options.include.iter().map(|relative| dir_size(&source_dir.join(relative))).sum()), None);
+                serde_json::to_value(self.clone_session(&source, &new_name, options)?)?
+            }
+            JobRequest::Clean { sessions, categories, dry_run } => serde_json::to_value(self.clean_sessions(sessions, &categories, dry_run)?)?,
+            JobRequest::Verify { archive: Some(archive), key } => {
+                if let Some(manifest) = self.resolve_archive(&archive)?.read_manifest() {
+                    set_totals(Some(manifest.total_bytes), Some(manifest.file_count));
+                }
+                serde_json::to_value(self.verify_archive(&archive, key.as_ref())?)?
+            }
+            JobRequest::Verify { archive: None, .. } => serde_json::to_value(self.verify_archives(|_| {})?)?,
+            JobRequest::DiskUsage { session } => {
+                // The last scan is a good guess for this one
+                let cached = self.read_session_info(&session)?.disk_usage;
+                set_totals(cached.map(|usage| usage.total_bytes), None);
+                serde_json::to_value(self.session_disk_usage(&session, |_| {})?)?
+            }
+        };
+        Ok(value)
+    }
+}
+
+// Start `request` on its own thread and return its id right away. Progress goes out as
+// JOB_PROGRESS_EVENT, the final state as JOB_FINISHED_EVENT
+pub fn start_job<R: Runtime>(app: tauri::AppHandle<R>, config: Config, request: JobRequest) -> String {
+    let report: Reporter = Arc::new(move |event, info| {
+        let _ = app.emit(event, info.clone());
+    });
+    spawn_job(config, request, report, PROGRESS_INTERVAL)
+}
+
+fn spawn_job(config: Config, request: JobRequest, report: Reporter, interval: Duration) -> String {
+    let id = uuid::Uuid::new_v4().to_string();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let info = JobInfo {
+        id: id.clone(),
+        kind: request.kind(),
+        target: request.target(),
+        state: JobState::Running,
+        started: Local::now(),
+        finished: None,
+        progress: JobProgress::default(),
+        result: None,
+        error: None,
+    };
+    jobs().push(JobSlot { info: info.clone(), cancel: cancel.clone() });
+    report(JOB_PROGRESS_EVENT, &info);
+
+    let job_id = id.clone();
+    std::thread::spawn(move || {
+        CURRENT.with(|current| {
+            *current.borrow_mut() = Some(JobContext {
+                id: job_id.clone(),
+                cancel: cancel.clone(),
+                started: Instant::now(),
+                last_report: None,
+                interval,
+                progress: JobProgress::default(),
+                report: report.clone(),
+            });
+        });
+
+        let manager = CursorManager::new(Some(config));
+        let result = manager.run_job(request).map_err(|e| e.to_string());
+        let progress = CURRENT.with(|current| current.borrow_mut().take().map(|context| context.progress)).unwrap_or_default();
+
+        let finished = {
+            let mut jobs = jobs();
+            let slot = match jobs.iter_mut().find(|slot| slot.info.id == job_id) {
+                Some(slot) => slot,
+                None => return,
+            };
+            slot.info.finished = Some(Local::now());
+            slot.info.progress = JobProgress { eta_seconds: None, ..progress };
+            match result {
+                Ok(value) => {
+                    slot.info.state = JobState::Completed;
+                    slot.info.result = Some(value);
+                }
+                // Kept, as it can say what the cancel left behind
+                Err(e) if cancel.load(Ordering::Relaxed) => {
+                    slot.info.state = JobState::Cancelled;
+                    slot.info.error = Some(e);
+                }
+                Err(e) => {
+                    slot.info.state = JobState::Failed;
+                    slot.info.error = Some(e);
+                }
+            }
+            let info = slot.info.clone();
+            prune_finished(&mut jobs);
+            info
+        };
+        match finished.state {
+            JobState::Failed => tracing::warn!("Job {} ({:?}) failed: {}", finished.id, finished.kind, finished.error.as_deref().unwrap_or("")),
+            state => tracing::info!("Job {} ({:?}) {:?}", finished.id, finished.kind, state),
+        }
+        report(JOB_FINISHED_EVENT, &finished);
+    });
+    id
+}
+
+fn prune_finished(jobs: &mut Vec<JobSlot>) {
+    let finished = jobs.iter().filter(|slot| slot.info.finished.is_some()).count();
+    let mut excess = finished.saturating_sub(RECENT_JOBS);
+    // Oldest first, as jobs are appended when they start
+    jobs.retain(|slot| {
+        if excess > 0 && slot.info.finished.is_some() {
+            excess -= 1;
+            return false;
+        }
+        true
+    });
+}
+
+pub fn cancel_job(id: &str) -> Result<JobInfo, Box<dyn Error>> {
+    let mut jobs = jobs();
+    let slot = jobs.iter_mut().find(|slot| slot.info.id == id).ok_or_else(|| format!("Job not found: {}", id))?;
+    if slot.info.finished.is_some() {
+        return Err(format!("Job {} has already finished", id).into());
+    }
+    slot.cancel.store(true, Ordering::Relaxed);
+    slot.info.state = JobState::Cancelling;
+    Ok(slot.info.clone())
+}
+
+// Running jobs first, then the most recent ones
+pub fn list_jobs() -> Vec<JobInfo> {
+    let mut infos: Vec<JobInfo> = jobs().iter().map(|slot| slot.info.clone()).collect();
+    infos.sort_by(|a, b| a.finished.is_some().cmp(&b.finished.is_some()).then_with(|| b.started.cmp(&a.started)));
+    infos
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::mpsc;
+    use crate::test_support::{manager, session};
+    use crate::{ArchiveMode, MergePolicy, RestoreMode};
+
+    // Run `request` to the end, reporting every checkpoint to `on_progress`
+    fn run(manager: &CursorManager, request: JobRequest, on_progress: impl Fn(&JobInfo) + Send + Sync + 'static) -> JobInfo {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let report: Reporter = Arc::new(move |event, info| match event {
+            JOB_FINISHED_EVENT => sender.lock().unwrap().send(info.clone()).unwrap(),
+            _ => on_progress(info),
+        });
+        spawn_job(manager.config.clone(), request, report, Duration::ZERO);
+        receiver.recv_timeout(Duration::from_secs(60)).unwrap()
+    }
+
+    fn cancel_once(info: &JobInfo) {
+        if info.state == JobState::Running && info.progress.files > 0 {
+            cancel_job(&info.id).unwrap();
+        }
+    }
+
+    #[test]
+    fn a_cancelled_clone_leaves_nothing_behind() {
+        let (_dir, manager) = manager();
+        let files: Vec<(String, String)> = (0..20).map(|i| (format!("extensions/ext/file{}.js", i), "x".repeat(100))).collect();
+        let files: Vec<(&str, &str)> = files.iter().map(|(path, content)| (path.as_str(), content.as_str())).collect();
+        session(&manager, "source", &files);
+
+        let request = JobRequest::Clone { source: "source".to_string(), new_name: "copy".to_string(), options: None };
+        let finished = run(&manager, request, cancel_once);
+
+        assert_eq!(finished.state, JobState::Cancelled);
+        assert!(finished.progress.files > 0 && finished.progress.files < 20);
+        let left: Vec<_> = fs::read_dir(&manager.config.profile_base).unwrap().flatten().map(|e| e.file_name()).collect();
+        assert_eq!(left, vec!["source"]);
+    }
+
+    #[test]
+    fn cancelling_a_merge_points_at_the_safety_archive() {
+        let (_dir, manager) = manager();
+        let files: Vec<(String, String)> = (0..10).map(|i| (format!("User/file{}.txt", i), "archived".to_string())).collect();
+        let files: Vec<(&str, &str)> = files.iter().map(|(path, content)| (path.as_str(), content.as_str())).collect();
+        session(&manager, "live", &files);
+        let options = ArchiveOptions { mode: Some(ArchiveMode::Full), ..Default::default() };
+        let archived = manager.create_archive("live", &options).unwrap().0;
+        let archived = manager.resolve_archive(&archived.name).unwrap();
+        // Out of the way of the safety archive, which may get the same timestamp
+        manager.rename_archive_entry(&archived, "live", "before").unwrap();
+
+        // Cancel at the first checkpoint after the safety archive exists, which is inside the merge
+        let watched = manager.config.clone();
+        let cancel_in_merge = move |info: &JobInfo| {
+            let archives = CursorManager::new(Some(watched.clone())).archive_entries().unwrap_or_default();
+            if info.state == JobState::Running && archives.len() == 2 {
+                cancel_job(&info.id).unwrap();
+            }
+        };
+        let request = JobRequest::Restore {
+            archive: "before".to_string(),
+            new_session_name: Some("live".to_string()),
+            skip_verify: true,
+            key: None,
+            options: Some(RestoreOptions { mode: RestoreMode::Merge, merge_policy: MergePolicy::PreferArchive, categories: None }),
+        };
+        let finished = run(&manager, request, cancel_in_merge);
+
+        assert_eq!(finished.state, JobState::Cancelled);
+        let safety = manager.archive_entries().unwrap().into_iter().find(|entry| entry.name != "before").unwrap();
+        assert!(finished.error.unwrap().contains(&format!("safety archive '{}'", safety.name)));
+    }
+}
@@ -0,0 +1,456 @@
+// Minimal JSON-with-comments support for VS Code style settings files.
+//
+// Parsing strips comments and trailing commas before handing the text to serde_json. Editing works on the
+// original text: only the spans of the members being changed are rewritten, so comments and formatting
+// elsewhere in the file survive.
+
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug)]
+pub struct JsoncError {
+    pub message: String,
+}
+
+impl std::fmt::Display for JsoncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid JSONC: {}", self.message)
+    }
+}
+
+impl std::error::Error for JsoncError {}
+
+fn jsonc_error(message: impl Into<String>) -> JsoncError {
+    JsoncError { message: message.into() }
+}
+
+const DEFAULT_INDENT: &str = "    ";
+
+// Skip whitespace, line comments and block comments
+fn skip_trivia(bytes: &[u8], mut pos: usize) -> usize {
+    loop {
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if bytes[pos..].starts_with(b"//") {
+            while pos < bytes.len() && bytes[pos] != b'\n' {
+                pos += 1;
+            }
+        } else if bytes[pos..].starts_with(b"/*") {
+            pos = match bytes[pos + 2..].windows(2).position(|w| w == b"*/") {
+                Some(offset) => pos + 2 + offset + 2,
+                None => bytes.len(),
+            };
+        } else {
+            return pos;
+        }
+    }
+}
+
+// End of the comments that start on the same line as `pos`. A block comment may run over several
+// lines, and whatever follows it on its last line counts as well
+fn trailing_comments_end(bytes: &[u8], mut pos: usize) -> usize {
+    let mut end = pos;
+    loop {
+        while pos < bytes.len() && matches!(bytes[pos], b' ' | b'\t' | b'\r') {
+            pos += 1;
+        }
+        if bytes[pos..].starts_with(b"//") {
+            return bytes[pos..].iter().position(|b| *b == b'\n').map(|i| pos + i).unwrap_or(bytes.len());
+        } else if bytes[pos..].starts_with(b"/*") {
+            pos = match bytes[pos + 2..].windows(2).position(|w| w == b"*/") {
+                Some(offset) => pos + 2 + offset + 2,
+                None => bytes.len(),
+            };
+            end = pos;
+        } else {
+            return end;
+        }
+    }
+}
+
+fn skip_string(bytes: &[u8], pos: usize) -> Result<usize, JsoncError> {
+    let mut i = pos + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Ok(i + 1),
+            _ => i += 1,
+        }
+    }
+    Err(jsonc_error("unterminated string"))
+}
+
+fn skip_value(bytes: &[u8], pos: usize) -> Result<usize, JsoncError> {
+    match bytes.get(pos) {
+        None => Err(jsonc_error("unexpected end of input")),
+        Some(b'"') => skip_string(bytes, pos),
+        Some(b'{') | Some(b'[') => {
+            let mut depth = 0usize;
+            let mut i = pos;
+            loop {
+                i = skip_trivia(bytes, i);
+                match bytes.get(i) {
+                    None => return Err(jsonc_error("unterminated object or array")),
+                    Some(b'"') => i = skip_string(bytes, i)?,
+                    Some(b'{') | Some(b'[') => {
+                        depth += 1;
+                        i += 1;
+                    }
+                    Some(b'}') | Some(b']') => {
+                        depth -= 1;
+                        i += 1;
+                        if depth == 0 {
+                            return Ok(i);
+                        }
+                    }
+                    Some(_) => i += 1,
+                }
+            }
+        }
+        Some(_) => {
+            let mut i = pos;
+            while i < bytes.len() && !matches!(bytes[i], b',' | b'}' | b']' | b'/') && !bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            if i == pos {
+                Err(jsonc_error(format!("unexpected character at offset {}", pos)))
+            } else {
+                Ok(i)
+            }
+        }
+    }
+}
+
+// Remove comments and trailing commas so serde_json can parse the text
+pub fn strip(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let end = skip_string(bytes, i).unwrap_or(bytes.len());
+                out.extend_from_slice(&bytes[i..end]);
+                i = end;
+            }
+            b'/' if bytes[i..].starts_with(b"//") || bytes[i..].starts_with(b"/*") => {
+                let end = skip_trivia(bytes, i);
+                // Keep line structure so serde_json error positions stay meaningful
+                out.extend(bytes[i..end].iter().map(|b| if *b == b'\n' { b'\n' } else { b' ' }));
+                i = end;
+            }
+            b',' => {
+                let next = skip_trivia(bytes, i + 1);
+                if !matches!(bytes.get(next), Some(b'}') | Some(b']')) {
+                    out.push(b',');
+                }
+                i += 1;
+            }
+            other => {
+                out.push(other);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).unwrap_or_default()
+}
+
+// Parse JSONC, treating an empty or comment-only document as `default`
+pub fn parse_or(text: &str, default: Value) -> Result<Value, JsoncError> {
+    let stripped = strip(text);
+    if stripped.trim().is_empty() {
+        return Ok(default);
+    }
+    serde_json::from_str(&stripped).map_err(|e| jsonc_error(e.to_string()))
+}
+
+// One member of an object or element of an array, as byte offsets into the source text
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub key: Option<String>,
+    pub start: usize,
+    pub value_start: usize,
+    pub value_end: usize,
+    pub comma: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct Container {
+    pub open: usize,
+    pub close: usize,
+    pub items: Vec<Item>,
+}
+
+// Locate the members of the top-level object or array
+pub fn top_level(text: &str) -> Result<Container, JsoncError> {
+    let bytes = text.as_bytes();
+    let open = skip_trivia(bytes, 0);
+    let is_object = match bytes.get(open) {
+        Some(b'{') => true,
+        Some(b'[') => false,
+        _ => return Err(jsonc_error("expected an object or array at the top level")),
+    };
+
+    let mut items = Vec::new();
+    let mut pos = open + 1;
+    loop {
+        pos = skip_trivia(bytes, pos);
+        match bytes.get(pos) {
+            None => return Err(jsonc_error("unterminated document")),
+            Some(b'}') | Some(b']') => {
+                return Ok(Container { open, close: pos, items });
+            }
+            _ => {}
+        }
+
+        let start = pos;
+        let key = if is_object {
+            if bytes[pos] != b'"' {
+                return Err(jsonc_error(format!("expected a key at offset {}", pos)));
+            }
+            let key_end = skip_string(bytes, pos)?;
+            let key: String = serde_json::from_str(&text[pos..key_end]).map_err(|e| jsonc_error(e.to_string()))?;
+            pos = skip_trivia(bytes, key_end);
+            if bytes.get(pos) != Some(&b':') {
+                return Err(jsonc_error(format!("expected ':' at offset {}", pos)));
+            }
+            pos = skip_trivia(bytes, pos + 1);
+            Some(key)
+        } else {
+            None
+        };
+
+        let value_start = pos;
+        let value_end = skip_value(bytes, pos)?;
+        let after = skip_trivia(bytes, value_end);
+        let comma = if bytes.get(after) == Some(&b',') { Some(after) } else { None };
+        pos = comma.map(|c| c + 1).unwrap_or(after);
+        items.push(Item { key, start, value_start, value_end, comma });
+    }
+}
+
+pub fn item_value(text: &str, item: &Item) -> Result<Value, JsoncError> {
+    parse_or(&text[item.value_start..item.value_end], Value::Null)
+}
+
+fn line_start(text: &str, pos: usize) -> usize {
+    text[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0)
+}
+
+fn indent_of(text: &str, pos: usize) -> String {
+    text[line_start(text, pos)..pos]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+// Pretty print a value so that its continuation lines line up under `indent`
+fn render(value: &Value, indent: &str) -> String {
+    let mut buffer = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(DEFAULT_INDENT.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+    if value.serialize(&mut serializer).is_err() {
+        return "null".to_string();
+    }
+    String::from_utf8_lossy(&buffer).replace('\n', &format!("\n{}", indent))
+}
+
+fn render_item(key: Option<&str>, value: &Value, indent: &str) -> String {
+    match key {
+        Some(key) => format!("{}: {}", Value::String(key.to_string()), render(value, indent)),
+        None => render(value, indent),
+    }
+}
+
+pub fn replace_item_value(text: &str, item: &Item, value: &Value) -> String {
+    let indent = indent_of(text, item.start);
+    let mut out = text.to_string();
+    out.replace_range(item.value_start..item.value_end, &render(value, &indent));
+    out
+}
+
+fn is_trailing_trivia(rest: &str) -> bool {
+    let rest = rest.trim();
+    rest.is_empty() || (rest.starts_with("//") && !rest.contains('\n'))
+}
+
+pub fn remove_item(text: &str, container: &Container, index: usize) -> String {
+    let item = &container.items[index];
+    let mut out = text.to_string();
+
+    match item.comma {
+        Some(comma) => {
+            // Drop the whole line when the item sits on its own line
+            let mut from = item.start;
+            let line = line_start(text, item.start);
+            if text[line..item.start].trim().is_empty() {
+                from = line;
+            }
+            let mut to = comma + 1;
+            let rest_of_line = text[to..].find('\n').map(|i| to + i + 1).unwrap_or(text.len());
+            if from == line && is_trailing_trivia(&text[to..rest_of_line]) {
+                to = rest_of_line;
+            }
+            out.replace_range(from..to, "");
+        }
+        None if index == 0 => {
+            out.replace_range(container.open + 1..container.close, "\n");
+        }
+        None => {
+            // Last item without a trailing comma: drop its line and the previous item's comma, but not
+            // a comment that trails the previous item
+            let previous_comma = container.items[index - 1].comma;
+            let line = line_start(text, item.start);
+            let from = if line > 0 && text[line..item.start].trim().is_empty() {
+                line - 1
+            } else {
+                previous_comma.map(|comma| comma + 1).unwrap_or(item.start)
+            };
+            // A comment after the value on the same line goes with the item
+            let line_end = text[item.value_end..].find('\n').map(|i| item.value_end + i).unwrap_or(text.len()).min(container.close);
+            let to = if is_trailing_trivia(&text[item.value_end..line_end]) { line_end } else { item.value_end };
+            out.replace_range(from..to, "");
+            if let Some(comma) = previous_comma {
+                out.remove(comma);
+            }
+        }
+    }
+    out
+}
+
+pub fn append_item(text: &str, container: &Container, key: Option<&str>, value: &Value) -> String {
+    let mut out = text.to_string();
+
+    match container.items.last() {
+        Some(last) => {
+            let indent = indent_of(text, last.start);
+            let rendered = render_item(key, value, &indent);
+            match last.comma {
+                Some(comma) => {
+                    // File already uses trailing commas, keep doing so
+                    out.insert_str(comma + 1, &format!("\n{}{},", indent, rendered));
+                }
+                None => {
+                    // Insert after any comment trailing the last value on the same line
+                    let insert_at = trailing_comments_end(text.as_bytes(), last.value_end).min(container.close);
+                    out.insert_str(insert_at, &format!("\n{}{}", indent, rendered));
+                    out.insert(last.value_end, ',');
+                }
+            }
+        }
+        None => {
+            let base_indent = indent_of(text, container.open);
+            let indent = format!("{}{}", base_indent, DEFAULT_INDENT);
+            let rendered = render_item(key, value, &indent);
+            let interior = &text[container.open + 1..container.close];
+            if interior.trim().is_empty() {
+                out.replace_range(container.open + 1..container.close, &format!("\n{}{}\n{}", indent, rendered, base_indent));
+            } else {
+                out.insert_str(container.close, &format!("{}{}\n{}", indent, rendered, base_indent));
+            }
+        }
+    }
+    out
+}
+
+// Set or (with a null value) remove top-level keys of an object document
+pub fn patch_object(text: &str, patch: &serde_json::Map<String, Value>) -> Result<String, JsoncError> {
+    let mut text = if strip(text).trim().is_empty() { "{}\n".to_string() } else { text.to_string() };
+
+    for (key, value) in patch {
+        let container = top_level(&text)?;
+        if text.as_bytes()[container.open] != b'{' {
+            return Err(jsonc_error("expected an object at the top level"));
+        }
+        let existing = container.items.iter().position(|item| item.key.as_deref() == Some(key.as_str()));
+        text = match (existing, value.is_null()) {
+            (Some(index), true) => remove_item(&text, &container, index),
+            (Some(index), false) => replace_item_value(&text, &container.items[index], value),
+            (None, true) => text,
+            (None, false) => append_item(&text, &container, Some(key), value),
+        };
+    }
+
+    // Never hand back something we cannot read again
+    parse_or(&text, Value::Null)?;
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn patch(text: &str, patch: Value) -> String {
+        patch_object(text, patch.as_object().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parses_comments_and_trailing_commas() {
+        let text = "// settings\n{\n    /* font */ \"size\": 14, // px\n    \"list\": [1, 2,],\n}\n";
+        assert_eq!(parse_or(text, Value::Null).unwrap(), json!({ "size": 14, "list": [1, 2] }));
+        assert_eq!(parse_or("  // nothing yet\n", json!({})).unwrap(), json!({}));
+    }
+
+    #[test]
+    fn comment_markers_inside_strings_are_kept() {
+        let text = "{\n    \"url\": \"https://example.com/*path*/\", // real comment\n    \"quote\": \"a \\\" // b\",\n}";
+        assert_eq!(
+            parse_or(text, Value::Null).unwrap(),
+            json!({ "url": "https://example.com/*path*/", "quote": "a \" // b" })
+        );
+        let patched = patch(text, json!({ "quote": "c" }));
+        assert!(patched.contains("\"url\": \"https://example.com/*path*/\", // real comment"));
+        assert_eq!(parse_or(&patched, Value::Null).unwrap()["quote"], "c");
+    }
+
+    #[test]
+    fn patch_keeps_comments_and_trailing_commas() {
+        let text = "{\n    // keep me\n    \"a\": 1,\n    \"b\": 2,\n}\n";
+        let patched = patch(text, json!({ "a": 10, "b": null, "c": true }));
+        assert_eq!(patched, "{\n    // keep me\n    \"a\": 10,\n    \"c\": true,\n}\n");
+    }
+
+    #[test]
+    fn patch_replaces_nested_values_and_matches_top_level_keys_only() {
+        let text = "{\n    \"editor.fontSize\": 12,\n    \"workbench\": {\n        \"theme\": \"dark\" // nested\n    },\n    \"theme\": \"light\"\n}";
+        let patched = patch(text, json!({ "theme": "solar", "workbench": { "theme": "dim", "icons": ["a"] } }));
+        assert_eq!(
+            parse_or(&patched, Value::Null).unwrap(),
+            json!({ "editor.fontSize": 12, "workbench": { "theme": "dim", "icons": ["a"] }, "theme": "solar" })
+        );
+        // Nested objects are indented under their key
+        assert!(patched.contains("    \"workbench\": {\n        \"theme\": \"dim\",\n        \"icons\": [\n            \"a\"\n        ]\n    },\n"));
+    }
+
+    #[test]
+    fn append_goes_after_a_block_comment_trailing_the_last_member() {
+        let text = "{\n    \"a\": 1 /* first line\n       second line */\n}\n";
+        assert_eq!(
+            patch(text, json!({ "b": 2 })),
+            "{\n    \"a\": 1, /* first line\n       second line */\n    \"b\": 2\n}\n"
+        );
+        let text = "{\n    \"a\": 1 // one\n}";
+        assert_eq!(patch(text, json!({ "b": 2 })), "{\n    \"a\": 1, // one\n    \"b\": 2\n}");
+    }
+
+    #[test]
+    fn removing_the_last_member_keeps_the_comment_of_the_one_before() {
+        let text = "{\n    \"a\": 1, // about a\n    \"b\": 2 // about b\n}\n";
+        assert_eq!(patch(text, json!({ "b": null })), "{\n    \"a\": 1 // about a\n}\n");
+        assert_eq!(patch("{ \"a\": 1, \"b\": 2 }", json!({ "b": null })), "{ \"a\": 1}");
+        assert_eq!(patch("{ \"a\": 1 }", json!({ "a": null })), "{\n}");
+    }
+
+    #[test]
+    fn patch_fills_empty_documents() {
+        assert_eq!(patch("", json!({ "a": 1 })), "{\n    \"a\": 1\n}\n");
+        assert_eq!(parse_or(&patch("// only a comment\n", json!({ "a": 1 })), Value::Null).unwrap(), json!({ "a": 1 }));
+    }
+
+    #[test]
+    fn patch_rejects_a_top_level_array() {
+        assert!(patch_object("[1, 2]", json!({ "a": 1 }).as_object().unwrap()).is_err());
+    }
+}
@@ -1,12 +1,11 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 use std::fs;
 use std::path::{Path, PathBuf};
-use tauri::Runtime;
-use chrono::{Local, DateTime};
-use serde::{Deserialize, Serialize};
+use tauri::{Manager, Runtime};
+use chrono::{Local, DateTime, NaiveDate};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::error::Error;
 use rand::distributions::{Alphanumeric, DistString};
-use fs_extra;
 use tauri_plugin_shell::ShellExt;
 use std::collections::HashMap;
 use thiserror::Error;
@@ -15,9 +14,55 @@ use validator::{Validate, ValidationError};
 use path_clean::PathClean;
 use tracing::error;
 use sysinfo::System;
+use sha2::{Digest, Sha256};
 
 // Module for all command functions
+pub mod activity;
+pub mod archive;
+pub mod archive_contents;
+pub mod archive_diff;
+pub mod bundle;
+pub mod cleanup;
 pub mod commands;
+pub mod disk_space;
+pub mod disk_usage;
+pub mod encryption;
+pub mod extensions;
+pub mod git_status;
+pub mod integrity;
+pub mod jobs;
+pub mod jsonc;
+pub mod limits;
+pub mod profile;
+pub mod restore;
+pub mod retention;
+pub mod snapshot;
+pub mod staging;
+pub mod templates;
+#[cfg(test)]
+mod test_support;
+pub mod user_files;
+pub mod vsix;
+
+pub use archive::{ArchiveFormat, ArchiveInfo, ArchiveManifest, ArchiveMode, ArchiveOptions, ArchivedFile};
+pub use archive_contents::{ArchiveContentEntry, ExtractResult};
+pub use archive_diff::{ArchiveDiff, ChangeKind, ChangedFile, DiffFile, DiffSource, KeyChange, StructuredDiff};
+pub use bundle::{BundleManifest, BundleSessionInfo, BundledExtension, ExportOptions};
+pub use activity::{IdleAction, IdleEvent, ProcessRegistry, SessionActivity};
+pub use cleanup::{BulkCleanResult, CleanReport, CleanedEntry};
+pub use disk_usage::{CachedDiskUsage, CategoryUsage, DiskUsageProgress, DiskUsageReport, UsageCategory, UsageEntry};
+pub use encryption::{ArchiveKey, EncryptionKind};
+pub use extensions::{ExtensionInfo, ExtensionSummary, ExtensionUsage};
+pub use git_status::{CommitSummary, ProjectGitStatus};
+pub use integrity::{ArchiveVerification, ModifiedFile};
+pub use jobs::{JobInfo, JobKind, JobProgress, JobRequest, JobState};
+pub use profile::{CloneOptions, CopyStats};
+pub use restore::{MergePolicy, RestoreCategory, RestoreMode, RestoreOptions};
+pub use retention::{ArchiveRecord, RetentionDecision, RetentionPolicy, RetentionReport};
+pub use snapshot::{GcReport, SnapshotStats};
+pub use templates::SessionTemplate;
+pub use user_files::{BulkPatchResult, UserFile, UserFileKind, UserFilePatch};
+pub use vsix::{ExtensionAction, ExtensionChange, ExtensionSyncPlan, VsixInstallResult, VsixPackage};
 
 // Custom error types for better error handling
 #[derive(Error, Debug)]
@@ -34,9 +79,42 @@ pub enum CursorManagerError {
     #[error("Session not found: {name}")]
     SessionNotFound { name: String },
     
+    #[error("Session already exists: {name}")]
+    SessionExists { name: String },
+    
+    #[error("Session is running: {name}")]
+    SessionRunning { name: String },
+    
+    #[error("Session limit reached: {current} of {limit} sessions in use")]
+    SessionLimitReached { limit: usize, current: usize },
+    
+    #[error("Template not found: {name}")]
+    TemplateNotFound { name: String },
+    
+    #[error("Template already exists: {name}")]
+    TemplateExists { name: String },
+    
+    #[error("Invalid template name: {message}")]
+    InvalidTemplateName { message: String },
+    
     #[error("Archive not found: {name}")]
     ArchiveNotFound { name: String },
     
+    #[error("Archive failed verification: {name} ({problems})")]
+    ArchiveCorrupted { name: String, problems: String },
+    
+    #[error("Archive is encrypted, a passphrase or key file is required: {name}")]
+    ArchiveKeyRequired { name: String },
+    
+    #[error("Wrong passphrase or key for archive: {name}")]
+    ArchiveKeyInvalid { name: String },
+    
+    #[error("Bundle format {version} is newer than this version can import (up to {supported}): {path}")]
+    IncompatibleBundle { path: String, version: u32, supported: u32 },
+    
+    #[error("Not enough disk space at {path}: {needed} bytes needed, {available} bytes available")]
+    InsufficientSpace { path: String, needed: u64, available: u64 },
+    
     #[error("Path security violation: {path}")]
     PathSecurityViolation { path: String },
     
@@ -95,13 +173,58 @@ pub struct Config {
     #[validate(length(min = 1, message = "Network interface cannot be empty"))]
     pub network_interface: String,
     
+    // Folder of .vsix packages used to install extensions without network access
+    #[validate(custom(function = "validate_path"))]
+    #[serde(default = "default_vsix_cache")]
+    pub vsix_cache: String,
+    
     // Enhanced configuration
+    #[validate(range(min = 1, message = "max_sessions must be at least 1"))]
     pub max_sessions: u32,
+    // 0 disables the idle timeout
     pub session_timeout_minutes: u32,
+    #[serde(default)]
+    pub idle_action: IdleAction,
     pub auto_cleanup_archives: bool,
+    // Applied on a schedule while auto_cleanup_archives is on, and on demand
+    #[serde(default)]
+    pub archive_retention: RetentionPolicy,
+    // zstd level for new archives, 1 (fast) to 22 (small)
+    #[validate(range(min = 1, max = 22, message = "archive_compression_level must be between 1 and 22"))]
+    #[serde(default = "default_archive_compression_level")]
+    pub archive_compression_level: i32,
+    // Whether archive_session writes full archives or deduplicated snapshots by default
+    #[serde(default)]
+    pub archive_mode: ArchiveMode,
+    // age key file that full archives are encrypted to unless an archive says otherwise
+    #[serde(default)]
+    pub archive_key_file: Option<String>,
+    // Profile paths left out of new archives; they are rebuilt by the app on demand
+    #[serde(default = "default_archive_exclude")]
+    pub archive_exclude: Vec<String>,
     pub enable_system_monitoring: bool,
 }
 
+fn home_path(name: &str) -> String {
+    let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    home.join(name).to_string_lossy().to_string()
+}
+
+fn default_vsix_cache() -> String {
+    home_path("cursor-vsix-cache")
+}
+
+fn default_archive_compression_level() -> i32 {
+    3
+}
+
+fn default_archive_exclude() -> Vec<String> {
+    ["Cache", "Code Cache", "GPUCache", "CachedData", "DawnCache", "DawnGraphiteCache", "DawnWebGPUCache", "Service Worker/CacheStorage", "Service Worker/ScriptCache"]
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect()
+}
+
 impl Default for Config {
     fn default() -> Self {
         let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
@@ -112,28 +235,194 @@ impl Default for Config {
             archive_base: home.join("cursor-archives").to_string_lossy().to_string(),
             workspace_base: home.join("projects").to_string_lossy().to_string(),
             network_interface: "en0".to_string(),
+            vsix_cache: default_vsix_cache(),
             max_sessions: 50,
             session_timeout_minutes: 1440, // 24 hours
+            idle_action: IdleAction::default(),
             auto_cleanup_archives: true,
+            archive_retention: RetentionPolicy::default(),
+            archive_compression_level: default_archive_compression_level(),
+            archive_mode: ArchiveMode::default(),
+            archive_key_file: None,
+            archive_exclude: default_archive_exclude(),
             enable_system_monitoring: true,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SessionInfo {
+    // Empty in session.json files written before ids existed; those get one derived from the
+    // directory on read, which is saved with the next write
+    #[serde(default)]
+    pub id: String,
     pub name: String,
     pub path: String,
-    pub created: String,
+    #[serde(deserialize_with = "deserialize_timestamp")]
+    pub created: DateTime<Local>,
+    #[serde(default)]
+    pub modified: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub last_launched: Option<DateTime<Local>>,
     pub electron_app: Option<ElectronApp>,
+    #[serde(default)]
+    pub projects: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    // Names this session had before it was renamed, oldest first
+    #[serde(default)]
+    pub previous_names: Vec<String>,
+    // Template the session was created from, if any
+    #[serde(default)]
+    pub template: Option<String>,
+    // Extension ids suggested through the workspace file
+    #[serde(default)]
+    pub recommended_extensions: Vec<String>,
+    // Extension ids passed as --disable-extension on launch
+    #[serde(default)]
+    pub disabled_extensions: Vec<String>,
+    // Extra arguments and environment passed to the Electron app on launch
+    #[serde(default)]
+    pub launch_args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    // Total profile size from the last disk usage scan
+    #[serde(default)]
+    pub disk_usage: Option<CachedDiskUsage>,
+    // Only filled in on demand by list_sessions, never persisted to session.json
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_status: Option<Vec<ProjectGitStatus>>,
+}
+
+impl SessionInfo {
+    pub fn new(name: &str, path: &Path, electron_app: Option<ElectronApp>) -> Self {
+        let now = Local::now();
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            path: path.to_string_lossy().to_string(),
+            created: now,
+            modified: Some(now),
+            last_launched: None,
+            electron_app,
+            projects: Vec::new(),
+            tags: Vec::new(),
+            notes: None,
+            color: None,
+            icon: None,
+            pinned: false,
+            previous_names: Vec::new(),
+            template: None,
+            recommended_extensions: Vec::new(),
+            disabled_extensions: Vec::new(),
+            launch_args: Vec::new(),
+            env: HashMap::new(),
+            disk_usage: None,
+            git_status: None,
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ArchiveInfo {
-    name: String,
-    path: String,
-    created: String,
-    original_session: String,
+// Partial update of the user-editable session metadata, unset fields are left alone
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SessionMetadataUpdate {
+    pub tags: Option<Vec<String>>,
+    pub notes: Option<String>,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub pinned: Option<bool>,
+}
+
+// Session names become directory names, so keep them to a single plain path component
+pub fn validate_session_name(name: &str) -> Result<(), CursorManagerError> {
+    let invalid = |message: &str| Err(CursorManagerError::InvalidSessionName { message: message.to_string() });
+
+    if name.trim().is_empty() {
+        return invalid("Session name cannot be empty");
+    }
+    if name == "." || name == ".." || name.contains('/') || name.contains('\\') {
+        return invalid("Session name cannot contain path separators");
+    }
+    if name.starts_with('.') {
+        return invalid("Session name cannot start with '.'");
+    }
+    if name.chars().any(|c| c.is_control()) {
+        return invalid("Session name cannot contain control characters");
+    }
+    Ok(())
+}
+
+// Archive directories are named "<session>-%Y%m%d-%H%M%S"; returns the session and timestamp parts
+pub fn split_archive_name(archive: &str) -> Option<(&str, &str)> {
+    const SUFFIX_LEN: usize = "-20250101-120000".len();
+    if archive.len() <= SUFFIX_LEN || !archive.is_char_boundary(archive.len() - SUFFIX_LEN) {
+        return None;
+    }
+    let (session, suffix) = archive.split_at(archive.len() - SUFFIX_LEN);
+    let timestamp = &suffix[1..];
+    let well_formed = suffix.starts_with('-')
+        && timestamp.char_indices().all(|(i, c)| if i == 8 { c == '-' } else { c.is_ascii_digit() });
+    if well_formed {
+        Some((session, timestamp))
+    } else {
+        None
+    }
+}
+
+// Replace a leading path prefix in every string of a JSON document
+fn rewrite_path_prefix(value: &mut serde_json::Value, old_prefix: &str, new_prefix: &str) {
+    match value {
+        serde_json::Value::String(text) => {
+            if let Some(rest) = text.strip_prefix(old_prefix) {
+                if rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\') {
+                    *text = format!("{}{}", new_prefix, rest);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                rewrite_path_prefix(item, old_prefix, new_prefix);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for (_, item) in map.iter_mut() {
+                rewrite_path_prefix(item, old_prefix, new_prefix);
+            }
+        }
+        _ => {}
+    }
+}
+
+// True when a process command line was started with --user-data-dir pointing at `session_dir`
+pub fn is_profile_process(cmd: &[String], session_dir: &str) -> bool {
+    let flag = format!("--user-data-dir={}", session_dir);
+    cmd.iter().any(|arg| arg.trim_end_matches('/') == flag)
+        || cmd.windows(2).any(|pair| pair[0] == "--user-data-dir" && pair[1].trim_end_matches('/') == session_dir)
+}
+
+// Accepts RFC 3339 as well as the date-only format older versions wrote
+pub fn parse_timestamp(raw: &str) -> Option<DateTime<Local>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(raw) {
+        return Some(timestamp.with_timezone(&Local));
+    }
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .ok()?
+        .and_hms_opt(0, 0, 0)?
+        .and_local_timezone(Local)
+        .earliest()
+}
+
+fn deserialize_timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Local>, D::Error> {
+    let raw = String::deserialize(deserializer)?;
+    parse_timestamp(&raw).ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp '{}'", raw)))
 }
 
 // System monitoring data
@@ -228,65 +517,146 @@ impl CursorManager {
             let entry = entry?;
             let path = entry.path();
             
-            if path.is_dir() {
-                let session_json_path = path.join("session.json");
-                let session_info = if session_json_path.exists() {
-                    let session_data = fs::read_to_string(&session_json_path)?;
-                    serde_json::from_str(&session_data)?
-                } else {
-                    SessionInfo {
-                        name: path.file_name().unwrap().to_string_lossy().to_string(),
-                        path: path.to_string_lossy().to_string(),
-                        created: Local::now().date_naive().format("%Y-%m-%d").to_string(),
-                        electron_app: None,
-                    }
-                };
-                
-                sessions.push(session_info);
+            // Hidden directories are staging areas, not sessions
+            if path.is_dir() && !entry.file_name().to_string_lossy().starts_with('.') {
+                sessions.push(self.session_info_from_dir(&path)?);
             }
         }
 
+        // Pinned sessions first, then alphabetical
+        sessions.sort_by(|a, b| b.pinned.cmp(&a.pinned).then_with(|| a.name.cmp(&b.name)));
+
         Ok(sessions)
     }
 
-    pub fn list_archives(&self) -> Result<Vec<ArchiveInfo>, Box<dyn Error>> {
-        let archive_dir = Path::new(&self.config.archive_base);
-        let mut archives = Vec::new();
+    // Older or missing records are upgraded in memory only; the next write_session_info persists them
+    fn session_info_from_dir(&self, path: &Path) -> Result<SessionInfo, Box<dyn Error>> {
+        let session_json_path = path.join("session.json");
+        let mut session_info: SessionInfo = if session_json_path.exists() {
+            let session_data = fs::read_to_string(&session_json_path)?;
+            serde_json::from_str(&session_data)?
+        } else {
+            // No session.json at all: use the directory timestamps rather than pretending it was created today
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            let mut session_info = SessionInfo::new(&name, path, None);
+            session_info.id = String::new();
+            if let Ok(metadata) = fs::metadata(path) {
+                if let Ok(created) = metadata.created().or_else(|_| metadata.modified()) {
+                    session_info.created = DateTime::<Local>::from(created);
+                }
+                if let Ok(modified) = metadata.modified() {
+                    session_info.modified = Some(DateTime::<Local>::from(modified));
+                }
+            }
+            session_info
+        };
 
-        if !archive_dir.exists() {
-            return Ok(archives);
+        // Derived from the directory rather than random, so it stays the same on every read until saved
+        if session_info.id.is_empty() {
+            let digest = Sha256::digest(path.to_string_lossy().as_bytes());
+            let mut bytes = [0u8; 16];
+            bytes.copy_from_slice(&digest[..16]);
+            session_info.id = uuid::Builder::from_custom_bytes(bytes).into_uuid().to_string();
+        }
+        if session_info.modified.is_none() {
+            session_info.modified = Some(session_info.created);
         }
 
-        for entry in fs::read_dir(archive_dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            
-            if path.is_dir() {
-                let metadata = fs::metadata(&path)?;
-                let created = metadata.created()
-                    .map(|_time| {
-                        Local::now().date_naive().format("%Y-%m-%d").to_string()
-                    })
-                    .unwrap_or_else(|_| "Unknown".to_string());
-                
-                // Try to determine original session name
-                let name = path.file_name().unwrap().to_string_lossy();
-                let original_session = if let Some(idx) = name.rfind('-') {
-                    name[0..idx].to_string()
-                } else {
-                    "Unknown".to_string()
-                };
-                
-                archives.push(ArchiveInfo {
-                    name: name.to_string(),
-                    path: path.to_string_lossy().to_string(),
-                    created,
-                    original_session,
-                });
-            }
+        Ok(session_info)
+    }
+
+    // Every command naming a session comes through here first, so ".." and the like stop here
+    pub fn read_session_info(&self, session: &str) -> Result<SessionInfo, Box<dyn Error>> {
+        validate_session_name(session)?;
+        let session_dir = Path::new(&self.config.profile_base).join(session);
+        if !session_dir.is_dir() {
+            return Err(Box::new(CursorManagerError::SessionNotFound { name: session.to_string() }));
+        }
+        self.session_info_from_dir(&session_dir)
+    }
+
+    pub fn write_session_info(&self, session_info: &SessionInfo) -> Result<(), Box<dyn Error>> {
+        let session_json_path = Path::new(&self.config.profile_base).join(&session_info.name).join("session.json");
+        let session_json_content = serde_json::to_string_pretty(session_info)?;
+        profile::write_file_atomic(&session_json_path, session_json_content.as_bytes())?;
+        Ok(())
+    }
+
+    // Re-read session.json, apply `update` and write it back, so changes made since the caller's own read
+    // survive. A session removed or renamed in the meantime is not recreated; returns whether it was written
+    pub(crate) fn update_session_info(&self, session: &str, update: impl FnOnce(&mut SessionInfo)) -> Result<bool, Box<dyn Error>> {
+        let session_dir = self.session_dir(session);
+        if !session_dir.is_dir() {
+            return Ok(false);
+        }
+        let mut session_info = self.read_session_info(session)?;
+        update(&mut session_info);
+        let session_json_content = serde_json::to_string_pretty(&session_info)?;
+        match profile::replace_file_atomic(&session_dir.join("session.json"), session_json_content.as_bytes()) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    pub fn workspace_file_path(&self, session: &str) -> PathBuf {
+        Path::new(&self.config.profile_base).join(session).join(format!("{}.code-workspace", session))
+    }
+
+    pub fn write_workspace_file(&self, session_info: &SessionInfo) -> Result<(), Box<dyn Error>> {
+        let folders: Vec<serde_json::Value> = session_info.projects
+            .iter()
+            .map(|project| serde_json::json!({ "path": self.resolve_project_path(project).to_string_lossy() }))
+            .collect();
+        let mut workspace = serde_json::json!({ "folders": folders, "settings": {} });
+        if !session_info.recommended_extensions.is_empty() {
+            workspace["extensions"] = serde_json::json!({ "recommendations": session_info.recommended_extensions });
+        }
+        fs::write(self.workspace_file_path(&session_info.name), serde_json::to_string_pretty(&workspace)?)?;
+        Ok(())
+    }
+
+    // Attach project folders to a session and regenerate its workspace file
+    pub fn set_session_projects(&self, session: &str, projects: Vec<String>) -> Result<String, Box<dyn Error>> {
+        let mut session_info = self.read_session_info(session)?;
+        session_info.projects = projects;
+        session_info.modified = Some(Local::now());
+        session_info.git_status = None;
+        self.write_session_info(&session_info)?;
+        self.write_workspace_file(&session_info)?;
+
+        Ok(format!("Session '{}' now has {} project(s)", session, session_info.projects.len()))
+    }
+
+    pub fn update_session_metadata(&self, session: &str, update: SessionMetadataUpdate) -> Result<SessionInfo, Box<dyn Error>> {
+        let mut session_info = self.read_session_info(session)?;
+
+        if let Some(tags) = update.tags {
+            let mut tags: Vec<String> = tags.into_iter()
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect();
+            tags.sort();
+            tags.dedup();
+            session_info.tags = tags;
+        }
+        // Empty strings clear the optional text fields
+        if let Some(notes) = update.notes {
+            session_info.notes = if notes.is_empty() { None } else { Some(notes) };
+        }
+        if let Some(color) = update.color {
+            session_info.color = if color.is_empty() { None } else { Some(color) };
+        }
+        if let Some(icon) = update.icon {
+            session_info.icon = if icon.is_empty() { None } else { Some(icon) };
+        }
+        if let Some(pinned) = update.pinned {
+            session_info.pinned = pinned;
         }
 
-        Ok(archives)
+        session_info.modified = Some(Local::now());
+        self.write_session_info(&session_info)?;
+        Ok(session_info)
     }
 
     pub fn random_mac(&self) -> String {
@@ -460,28 +830,42 @@ impl CursorManager {
     }
 
     // Session management functions
-    pub fn create_session(&self, name: &str, electron_app: Option<ElectronApp>) -> Result<String, Box<dyn Error>> {
-        if name.is_empty() {
-            return Err("Session name cannot be empty".into());
-        }
+    pub fn create_session(&self, name: &str, electron_app: Option<ElectronApp>, template: Option<&str>) -> Result<String, Box<dyn Error>> {
+        let template = match template {
+            Some(template_name) => Some(self.load_template(template_name)?),
+            None => None,
+        };
+
+        // An empty name is only allowed when the template can generate one
+        let name = match template.as_ref().and_then(|t| t.expand_name_pattern(self)) {
+            Some(generated) if name.is_empty() => generated,
+            _ => name.to_string(),
+        };
+        let name = name.as_str();
+        validate_session_name(name)?;
         
         let session_dir = Path::new(&self.config.profile_base).join(name);
         if session_dir.exists() {
             return Err(format!("Session '{}' already exists", name).into());
         }
+        let _slot = self.reserve_session_slot(name)?;
         
         fs::create_dir_all(&session_dir)?;
 
-        let session_info = SessionInfo {
-            name: name.to_string(),
-            path: session_dir.to_string_lossy().to_string(),
-            created: Local::now().date_naive().format("%Y-%m-%d").to_string(),
-            electron_app,
-        };
+        let mut session_info = SessionInfo::new(name, &session_dir, electron_app);
+        if let Some(template) = &template {
+            if let Err(e) = self.apply_template(template, &session_dir, &mut session_info) {
+                let _ = fs::remove_dir_all(&session_dir);
+                return Err(e);
+            }
+        }
 
         let session_json_path = session_dir.join("session.json");
         let session_json_content = serde_json::to_string_pretty(&session_info)?;
         fs::write(&session_json_path, session_json_content)?;
+        if !session_info.projects.is_empty() || !session_info.recommended_extensions.is_empty() {
+            self.write_workspace_file(&session_info)?;
+        }
 
         Ok(format!("Session '{}' created at {}", name, session_dir.to_string_lossy()))
     }
@@ -496,18 +880,11 @@ impl CursorManager {
             return Err(error_msg.into());
         }
         
-        // Read session.json for electron_app info
-        let session_json_path = session_dir.join("session.json");
-        let electron_app_exec = if session_json_path.exists() {
-            let session_data = fs::read_to_string(&session_json_path)?;
-            let session_info: SessionInfo = serde_json::from_str(&session_data)?;
-            if let Some(app) = session_info.electron_app {
-                app.exec_path
-            } else {
-                self.config.cursor_app.clone()
-            }
-        } else {
-            self.config.cursor_app.clone()
+        // Read session.json for electron_app info and launch settings
+        let session_info = self.read_session_info(session)?;
+        let electron_app_exec = match &session_info.electron_app {
+            Some(app) => app.exec_path.clone(),
+            None => self.config.cursor_app.clone(),
         };
         
         println!("[DEBUG] Session directory found: {}", session_dir.to_string_lossy());
@@ -536,10 +913,19 @@ impl CursorManager {
         }
         
         // Launch Electron app with the profile directory
-        let args = vec![
+        let mut args = vec![
             "--user-data-dir=".to_string() + &session_dir.to_string_lossy(),
             "--new-window".to_string(),
         ];
+        // Sessions with their own extensions folder get an isolated extension set
+        let extensions_dir = session_dir.join(profile::EXTENSIONS_DIR);
+        if extensions_dir.is_dir() {
+            args.push("--extensions-dir=".to_string() + &extensions_dir.to_string_lossy());
+        }
+        for extension_id in &session_info.disabled_extensions {
+            args.push(format!("--disable-extension={}", extension_id));
+        }
+        args.extend(session_info.launch_args.iter().cloned());
         
         println!("[DEBUG] Launching Electron app with args: {:?}", args);
         println!("[DEBUG] Electron app path: {}", electron_app_exec);
@@ -548,10 +934,20 @@ impl CursorManager {
         match app.shell()
             .command(&electron_app_exec)
             .args(args)
+            .envs(session_info.env.clone())
             .spawn() {
             Ok(cursor_result) => {
                 let pid = cursor_result.1.pid();
                 println!("[DEBUG] Electron app launched successfully with PID: {:?}", pid);
+                if let Some(registry) = app.try_state::<activity::ProcessRegistry>() {
+                    registry.register(session, Some(pid));
+                }
+                if let Ok(mut session_info) = self.read_session_info(session) {
+                    session_info.last_launched = Some(Local::now());
+                    if let Err(e) = self.write_session_info(&session_info) {
+                        tracing::warn!("Failed to record launch time for session {}: {}", session, e);
+                    }
+                }
         result.push_str(&format!("Launched Electron app with session '{}'\n", session));
                 result.push_str(&format!("PID: {:?}", pid));
         Ok(result)
@@ -564,83 +960,244 @@ impl CursorManager {
         }
     }
 
-    pub fn archive_session(&self, session: &str) -> Result<String, Box<dyn Error>> {
+    pub fn delete_session(&self, session: &str) -> Result<String, Box<dyn Error>> {
         let session_dir = Path::new(&self.config.profile_base).join(session);
         if !session_dir.exists() {
             return Err(format!("Session '{}' not found", session).into());
         }
         
-        let timestamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
-        let archive_name = format!("{}-{}", session, timestamp);
-        let archive_dir = Path::new(&self.config.archive_base).join(&archive_name);
-        
-        if archive_dir.exists() {
-            return Err(format!("Archive destination already exists: {}", archive_dir.to_string_lossy()).into());
+        fs::remove_dir_all(&session_dir)?;
+        Ok(format!("Session '{}' deleted", session))
+    }
+
+    // Electron passes --user-data-dir down to every helper process, so any match means the profile is in use
+    pub fn is_session_running(&self, session: &str) -> bool {
+        let session_dir = Path::new(&self.config.profile_base).join(session);
+        let session_dir = session_dir.to_string_lossy();
+
+        let mut system = System::new();
+        system.refresh_processes();
+        system.processes().values().any(|process| is_profile_process(process.cmd(), &session_dir))
+    }
+
+    pub fn ensure_not_running(&self, session: &str) -> Result<(), Box<dyn Error>> {
+        if self.is_session_running(session) {
+            return Err(Box::new(CursorManagerError::SessionRunning { name: session.to_string() }));
         }
-        
-        // Create the archive directory
-        fs::create_dir_all(&archive_dir)?;
-        
-        // Copy all files from session to archive
-        let mut copy_options = fs_extra::dir::CopyOptions::new();
-        copy_options.copy_inside = true;
-        fs_extra::dir::copy(&session_dir, &archive_dir, &copy_options)?;
-        
-        Ok(format!("Session '{}' archived to '{}'", session, archive_name))
+        Ok(())
     }
 
-    pub fn restore_archive(&self, archive: &str, new_session_name: Option<&str>) -> Result<String, Box<dyn Error>> {
-        let archive_dir = Path::new(&self.config.archive_base).join(archive);
-        if !archive_dir.exists() {
-            return Err(format!("Archive '{}' not found", archive).into());
+    pub fn rename_session(&self, old_name: &str, new_name: &str) -> Result<String, Box<dyn Error>> {
+        // The old name is joined onto profile_base too, so it must not escape it either
+        validate_session_name(old_name)?;
+        validate_session_name(new_name)?;
+        if old_name == new_name {
+            return Ok(format!("Session '{}' already has that name", old_name));
         }
-        
-        // Determine the target session name
-        let session_name = if let Some(name) = new_session_name {
-            name.to_string()
-        } else {
-            // Extract the original session name from the archive name
-            if let Some(idx) = archive.rfind('-') {
-                archive[0..idx].to_string()
-            } else {
-                return Err(format!("Cannot determine original session name from archive '{}'", archive).into());
+
+        let old_dir = Path::new(&self.config.profile_base).join(old_name);
+        let new_dir = Path::new(&self.config.profile_base).join(new_name);
+        if !old_dir.is_dir() {
+            return Err(Box::new(CursorManagerError::SessionNotFound { name: old_name.to_string() }));
+        }
+        if new_dir.exists() {
+            return Err(Box::new(CursorManagerError::SessionExists { name: new_name.to_string() }));
+        }
+        if self.is_session_running(old_name) {
+            return Err(Box::new(CursorManagerError::SessionRunning { name: old_name.to_string() }));
+        }
+
+        let mut session_info = self.read_session_info(old_name)?;
+
+        // Same parent directory, so this is a single atomic rename
+        fs::rename(&old_dir, &new_dir)?;
+
+        session_info.previous_names.push(old_name.to_string());
+        session_info.name = new_name.to_string();
+        session_info.path = new_dir.to_string_lossy().to_string();
+        session_info.modified = Some(Local::now());
+        if let Err(e) = self.write_session_info(&session_info) {
+            // Put the directory back so session.json and the directory name never disagree
+            if let Err(rollback) = fs::rename(&new_dir, &old_dir) {
+                error!("Rollback of rename '{}' -> '{}' failed: {}", old_name, new_name, rollback);
             }
-        };
-        
-        let session_dir = Path::new(&self.config.profile_base).join(&session_name);
-        if session_dir.exists() {
-            return Err(format!("Session '{}' already exists, cannot restore to this name", session_name).into());
+            return Err(e);
         }
-        
-        // Create the session directory
-        fs::create_dir_all(&session_dir)?;
-        
-        // Copy all files from archive to session
-        let mut copy_options = fs_extra::dir::CopyOptions::new();
-        copy_options.copy_inside = true;
-        fs_extra::dir::copy(&archive_dir, &session_dir, &copy_options)?;
-        
-        Ok(format!("Archive '{}' restored to session '{}'", archive, session_name))
+
+        let mut warnings = Vec::new();
+        if let Err(e) = self.rename_workspace_file(old_name, new_name, &old_dir, &new_dir) {
+            warnings.push(format!("workspace file: {}", e));
+        }
+        if let Err(e) = self.relink_archives(old_name, new_name) {
+            warnings.push(format!("archives: {}", e));
+        }
+
+        let mut result = format!("Session '{}' renamed to '{}'", old_name, new_name);
+        for warning in warnings {
+            tracing::warn!("rename_session {} -> {}: {}", old_name, new_name, warning);
+            result.push_str(&format!("\nWarning: {}", warning));
+        }
+        Ok(result)
     }
 
-    pub fn delete_session(&self, session: &str) -> Result<String, Box<dyn Error>> {
-        let session_dir = Path::new(&self.config.profile_base).join(session);
-        if !session_dir.exists() {
-            return Err(format!("Session '{}' not found", session).into());
+    // Moves <old>.code-workspace to <new>.code-workspace and rewrites any paths that pointed into the old profile
+    fn rename_workspace_file(&self, old_name: &str, new_name: &str, old_dir: &Path, new_dir: &Path) -> Result<(), Box<dyn Error>> {
+        let old_file = new_dir.join(format!("{}.code-workspace", old_name));
+        if !old_file.exists() {
+            return Ok(());
         }
-        
-        fs::remove_dir_all(&session_dir)?;
-        Ok(format!("Session '{}' deleted", session))
+
+        let old_prefix = old_dir.to_string_lossy().to_string();
+        let new_prefix = new_dir.to_string_lossy().to_string();
+        let mut workspace: serde_json::Value = serde_json::from_str(&fs::read_to_string(&old_file)?)?;
+        rewrite_path_prefix(&mut workspace, &old_prefix, &new_prefix);
+
+        fs::write(self.workspace_file_path(new_name), serde_json::to_string_pretty(&workspace)?)?;
+        fs::remove_file(&old_file)?;
+        Ok(())
     }
 
-    pub fn delete_archive(&self, archive: &str) -> Result<String, Box<dyn Error>> {
-        let archive_dir = Path::new(&self.config.archive_base).join(archive);
+    // Archives are tied to their session by name, so carry them over to the new name
+    fn relink_archives(&self, old_name: &str, new_name: &str) -> Result<usize, Box<dyn Error>> {
+        let archive_dir = Path::new(&self.config.archive_base);
         if !archive_dir.exists() {
-            return Err(format!("Archive '{}' not found", archive).into());
+            return Ok(0);
         }
-        
-        fs::remove_dir_all(&archive_dir)?;
-        Ok(format!("Archive '{}' deleted", archive))
+
+        let mut relinked = 0;
+        for entry in self.archive_entries()? {
+            if entry.origin().0 != old_name {
+                continue;
+            }
+            // Keep the timestamp suffix; archives without one just get the new session name in front
+            let new_archive = match split_archive_name(&entry.name) {
+                Some((_, timestamp)) => format!("{}-{}", new_name, timestamp),
+                None => match entry.name.strip_prefix(old_name) {
+                    Some(rest) => format!("{}{}", new_name, rest),
+                    None => format!("{}-{}", new_name, entry.name),
+                },
+            };
+            if self.rename_archive_entry(&entry, new_name, &new_archive)? {
+                relinked += 1;
+            }
+        }
+        Ok(relinked)
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{manager, session};
+
+    #[test]
+    fn split_archive_name_takes_the_timestamp_off_the_end() {
+        assert_eq!(split_archive_name("work-20250101-120000"), Some(("work", "20250101-120000")));
+        // Dashes and digits in the session name stay with the session
+        assert_eq!(split_archive_name("work-2-20241231-235959"), Some(("work-2", "20241231-235959")));
+        assert_eq!(split_archive_name("日本-20250101-120000"), Some(("日本", "20250101-120000")));
+    }
+
+    #[test]
+    fn split_archive_name_rejects_names_without_a_timestamp() {
+        assert_eq!(split_archive_name("work"), None);
+        assert_eq!(split_archive_name("-20250101-120000"), None);
+        assert_eq!(split_archive_name("work-20250101_120000"), None);
+        assert_eq!(split_archive_name("work-2025010a-120000"), None);
+        assert_eq!(split_archive_name("work_20250101-120000"), None);
+        // The suffix would start inside a multibyte character
+        assert_eq!(split_archive_name("é20250101-120000"), None);
+    }
+
+    #[test]
+    fn reading_old_records_does_not_write_them() {
+        let (_dir, manager) = manager();
+        let bare = manager.session_dir("bare");
+        fs::create_dir_all(&bare).unwrap();
+        let legacy = manager.session_dir("legacy");
+        fs::create_dir_all(&legacy).unwrap();
+        let record = format!(r#"{{"name":"legacy","path":"{}","created":"2024-01-01T00:00:00+00:00","electron_app":null}}"#, legacy.to_string_lossy());
+        fs::write(legacy.join("session.json"), &record).unwrap();
+
+        let first = manager.read_session_info("bare").unwrap();
+        assert!(!bare.join("session.json").exists());
+        assert_eq!(manager.read_session_info("bare").unwrap().id, first.id);
+
+        let upgraded = manager.read_session_info("legacy").unwrap();
+        assert!(!upgraded.id.is_empty());
+        assert_eq!(upgraded.modified, Some(upgraded.created));
+        assert_eq!(fs::read_to_string(legacy.join("session.json")).unwrap(), record);
+
+        // The next real write keeps the id handed out so far
+        manager.update_session_metadata("legacy", SessionMetadataUpdate { pinned: Some(true), ..Default::default() }).unwrap();
+        assert_eq!(manager.read_session_info("legacy").unwrap().id, upgraded.id);
+    }
+
+    #[test]
+    fn config_validation_rejects_out_of_range_limits() {
+        assert!(Config::default().validate().is_ok());
+        assert!(Config { max_sessions: 0, ..Config::default() }.validate().is_err());
+        assert!(Config { archive_compression_level: 0, ..Config::default() }.validate().is_err());
+        assert!(Config { archive_compression_level: 23, ..Config::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn archive_rejects_an_out_of_range_compression_level() {
+        let (_dir, manager) = manager();
+        session(&manager, "work", &[("User/settings.json", "{}")]);
+        for level in [0, 23, -5] {
+            let options = ArchiveOptions { compression_level: Some(level), ..Default::default() };
+            assert!(manager.create_archive("work", &options).is_err(), "{}", level);
+        }
+        assert!(manager.archive_entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn session_names_cannot_reach_outside_the_profile_base() {
+        let (dir, manager) = manager();
+        session(&manager, "work", &[]);
+        // A cache directory beside profile_base, where ".." would point
+        fs::create_dir_all(dir.path().join("Cache")).unwrap();
+        fs::write(dir.path().join("Cache/data"), "keep").unwrap();
+
+        for name in ["..", ".", "../profiles", "work/.."] {
+            assert!(manager.read_session_info(name).is_err(), "{}", name);
+            assert!(manager.clean_session(name, &[], false).is_err(), "{}", name);
+            assert!(manager.session_disk_usage(name, |_| {}).is_err(), "{}", name);
+            assert!(manager.session_git_status(name).is_err(), "{}", name);
+            assert!(manager.list_session_extensions(name).is_err(), "{}", name);
+        }
+        assert!(dir.path().join("Cache/data").exists());
+    }
+
+    #[test]
+    fn rename_session_rejects_an_invalid_old_name() {
+        let (_dir, manager) = manager();
+        assert!(manager.rename_session("../profiles", "new").is_err());
+        assert!(manager.rename_session("missing", "new").is_err());
+    }
+
+    #[test]
+    fn rename_session_relinks_archives_with_and_without_a_timestamp() {
+        let (_dir, manager) = manager();
+        session(&manager, "old", &[]);
+        let options = ArchiveOptions { mode: Some(ArchiveMode::Full), ..Default::default() };
+        let (bare, _) = manager.create_archive("old", &options).unwrap();
+        let bare = manager.resolve_archive(&bare.name).unwrap();
+        manager.rename_archive_entry(&bare, "old", "old").unwrap();
+        let stamped = manager.create_archive("old", &options).unwrap().0;
+
+        manager.rename_session("old", "new").unwrap();
+
+        let (_, timestamp) = split_archive_name(&stamped.name).unwrap();
+        for name in [format!("new-{}", timestamp), "new".to_string()] {
+            let manifest = manager.resolve_archive(&name).unwrap().read_manifest().unwrap();
+            assert_eq!(manifest.archive, name);
+            assert_eq!(manifest.session, "new");
+            let session_info = manifest.session_info.unwrap();
+            assert_eq!(session_info.name, "new");
+            assert_eq!(Path::new(&session_info.path), manager.session_dir("new"));
+        }
+    }
+}
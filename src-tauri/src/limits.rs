@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::{CursorManager, CursorManagerError};
+
+// Profile directories that are being created right now. CursorManager is rebuilt for every command,
+// so the reservations have to live outside of it
+static PENDING_SESSIONS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+// Holds a place under Config.max_sessions until the new session exists on disk (or creation failed)
+pub struct SessionSlot {
+    dir: PathBuf,
+}
+
+impl Drop for SessionSlot {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = PENDING_SESSIONS.lock() {
+            pending.retain(|dir| dir != &self.dir);
+        }
+    }
+}
+
+// Session directories on disk plus reservations under the same profile base
+fn occupied_names(profile_base: &Path, pending: &[PathBuf]) -> HashSet<String> {
+    let mut names: HashSet<String> = fs::read_dir(profile_base)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                // Hidden directories are staging areas, not sessions
+                .filter(|name| !name.starts_with('.'))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.extend(
+        pending
+            .iter()
+            .filter(|dir| dir.parent() == Some(profile_base))
+            .filter_map(|dir| dir.file_name().map(|n| n.to_string_lossy().to_string())),
+    );
+    names
+}
+
+impl CursorManager {
+    pub fn occupied_session_count(&self) -> usize {
+        let pending = PENDING_SESSIONS.lock().map(|pending| pending.clone()).unwrap_or_default();
+        occupied_names(Path::new(&self.config.profile_base), &pending).len()
+    }
+
+    // Check the cap and reserve `name` in one step so concurrent creations cannot both take the last slot
+    pub fn reserve_session_slot(&self, name: &str) -> Result<SessionSlot, Box<dyn Error>> {
+        let dir = Path::new(&self.config.profile_base).join(name);
+        let mut pending = PENDING_SESSIONS.lock().map_err(|_| "Session reservations are unavailable")?;
+        if pending.contains(&dir) {
+            return Err(Box::new(CursorManagerError::SessionExists { name: name.to_string() }));
+        }
+
+        let current = occupied_names(Path::new(&self.config.profile_base), &pending).len();
+        let limit = self.config.max_sessions as usize;
+        if current >= limit {
+            return Err(Box::new(CursorManagerError::SessionLimitReached { limit, current }));
+        }
+
+        pending.push(dir.clone());
+        Ok(SessionSlot { dir })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::test_support::{manager, session};
+    use crate::{CloneOptions, Config, CursorManager, CursorManagerError};
+
+    fn capped(manager: &CursorManager, max_sessions: u32) -> CursorManager {
+        CursorManager::new(Some(Config { max_sessions, ..manager.config.clone() }))
+    }
+
+    fn is_limit(error: Box<dyn std::error::Error>) -> bool {
+        matches!(error.downcast_ref::<CursorManagerError>(), Some(CursorManagerError::SessionLimitReached { limit: 2, current: 2 }))
+    }
+
+    #[test]
+    fn max_sessions_blocks_creating_and_cloning_past_the_cap() {
+        let (_dir, manager) = manager();
+        let manager = capped(&manager, 2);
+        manager.create_session("a", None, None).unwrap();
+        // Staging areas are not sessions
+        fs::create_dir_all(manager.session_dir(".b.staging")).unwrap();
+        manager.create_session("b", None, None).unwrap();
+
+        assert!(is_limit(manager.create_session("c", None, None).unwrap_err()));
+        assert!(is_limit(manager.clone_session("a", "c", CloneOptions::default()).unwrap_err()));
+        assert!(!manager.session_dir("c").exists());
+    }
+
+    #[test]
+    fn a_reserved_slot_counts_until_it_is_dropped() {
+        let (_dir, manager) = manager();
+        let manager = capped(&manager, 2);
+        session(&manager, "a", &[]);
+        let slot = manager.reserve_session_slot("b").unwrap();
+        assert_eq!(manager.occupied_session_count(), 2);
+        assert!(is_limit(manager.create_session("c", None, None).unwrap_err()));
+
+        drop(slot);
+        manager.create_session("c", None, None).unwrap();
+    }
+}
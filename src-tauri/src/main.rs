@@ -1,7 +1,7 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use cursor_manager::Config;
+use cursor_manager::{Config, ProcessRegistry};
 use cursor_manager::commands::*;
 use std::sync::Mutex;
 use tauri_plugin_shell::ShellExt;
@@ -21,11 +21,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .setup(|app| {
             // Initialize shell plugin with error handling
             let _shell = app.shell();
+            // Leftovers of archive, restore and clone operations cut short by a crash
+            cursor_manager::staging::clean_staging_on_startup(app.handle());
+            // Background idle timeout and archive retention
+            cursor_manager::activity::start_idle_monitor(app.handle().clone());
+            cursor_manager::retention::start_retention_scheduler(app.handle().clone());
             tracing::info!("Tauri application setup completed successfully");
             Ok(())
         })
-        .manage(Config::default())
-        .manage(Mutex::new(Config::default())) // For mutable state
+        // The one config state; update_config replaces it at runtime
+        .manage(Mutex::new(Config::default()))
+        .manage(ProcessRegistry::default())
         .invoke_handler(tauri::generate_handler![
             // Config commands
             get_config,
@@ -35,13 +41,60 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             list_sessions,
             create_session,
             delete_session,
+            rename_session,
+            clone_session,
             launch_session_cmd,
+            set_session_projects,
+            update_session_metadata,
+            session_git_status,
+            touch_session,
+            list_session_activity,
+            list_idle_events,
+            export_session,
+            read_session_bundle,
+            import_session,
+            
+            // Template commands
+            list_templates,
+            save_template,
+            create_template_from_session,
+            delete_template,
+            
+            // Settings and keybindings commands
+            read_user_file,
+            write_user_file,
+            patch_user_file,
+            patch_user_file_bulk,
+            
+            // Extension commands
+            list_session_extensions,
+            find_extension_sessions,
+            extension_overview,
+            set_extension_enabled,
+            list_vsix_cache,
+            add_to_vsix_cache,
+            install_vsix,
+            sync_extensions,
+            
+            // Storage commands
+            session_disk_usage,
+            clean_session,
+            clean_sessions,
             
             // Archive commands
             list_archives,
             archive_session,
             restore_archive,
+            verify_archive,
+            verify_archives,
+            list_archive_contents,
+            extract_from_archive,
+            diff_archive,
+            generate_archive_key,
             delete_archive,
+            set_archive_pinned,
+            apply_archive_retention,
+            gc_archives,
             
             // MAC address commands
             spoof_mac_cmd,
@@ -52,6 +105,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             get_running_apps,
             get_mcp_servers,
             list_electron_apps,
+
+            // Background jobs
+            start_job,
+            start_archive_job,
+            start_restore_job,
+            start_clone_job,
+            start_clean_job,
+            start_verify_job,
+            start_disk_usage_job,
+            cancel_job,
+            list_jobs,
         ])
         .run(tauri::generate_context!());
 
@@ -0,0 +1,292 @@
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use crate::disk_space::unpacked_size;
+use crate::jobs;
+use crate::staging::{commit_staged, staging_path};
+use crate::{rewrite_path_prefix, validate_session_name, CursorManager, CursorManagerError, SessionInfo};
+
+// Well-known locations inside an Electron / VS Code style --user-data-dir, relative to the profile root
+pub const SESSION_FILE: &str = "session.json";
+pub const USER_DIR: &str = "User";
+pub const SETTINGS_FILE: &str = "User/settings.json";
+pub const KEYBINDINGS_FILE: &str = "User/keybindings.json";
+pub const SNIPPETS_DIR: &str = "User/snippets";
+pub const GLOBAL_STORAGE_DIR: &str = "User/globalStorage";
+pub const WORKSPACE_STORAGE_DIR: &str = "User/workspaceStorage";
+pub const EXTENSIONS_DIR: &str = "extensions";
+
+fn default_clone_include() -> Vec<String> {
+    vec![
+        SETTINGS_FILE.to_string(),
+        KEYBINDINGS_FILE.to_string(),
+        SNIPPETS_DIR.to_string(),
+        EXTENSIONS_DIR.to_string(),
+    ]
+}
+
+// Login state, history and anything the app regenerates on its own
+fn default_clone_exclude() -> Vec<String> {
+    [
+        "Local Storage",
+        "Session Storage",
+        "Cookies",
+        "Cookies-journal",
+        "IndexedDB",
+        "Cache",
+        "Code Cache",
+        "GPUCache",
+        "CachedData",
+        "logs",
+        GLOBAL_STORAGE_DIR,
+        WORKSPACE_STORAGE_DIR,
+        "User/History",
+    ]
+    .iter()
+    .map(|entry| entry.to_string())
+    .collect()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CloneOptions {
+    // Paths relative to the profile root that are copied
+    #[serde(default = "default_clone_include")]
+    pub include: Vec<String>,
+    // Paths relative to the profile root that are skipped even when inside an included path
+    #[serde(default = "default_clone_exclude")]
+    pub exclude: Vec<String>,
+    // Also carry over projects, tags, notes, color, icon, launch arguments, environment variables and
+    // disabled extensions from the source session
+    #[serde(default = "default_true")]
+    pub copy_metadata: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for CloneOptions {
+    fn default() -> Self {
+        Self {
+            include: default_clone_include(),
+            exclude: default_clone_exclude(),
+            copy_metadata: true,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CopyStats {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+// Normalise a user supplied relative path to forward slashes without leading or trailing separators
+fn normalize_relative(path: &str) -> String {
+    path.replace('\\', "/").trim_matches('/').to_string()
+}
+
+// True when `relative` is `pattern` itself or lives underneath it
+pub fn path_matches(relative: &str, pattern: &str) -> bool {
+    relative == pattern
+        || (relative.len() > pattern.len() && relative.starts_with(pattern) && relative.as_bytes()[pattern.len()] == b'/')
+}
+
+pub fn relative_string(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Copy `relative` from one profile root to another, skipping excluded paths and symlinks
+pub fn copy_profile_entry(from_root: &Path, to_root: &Path, relative: &Path, exclude: &[String], stats: &mut CopyStats) -> std::io::Result<()> {
+    let relative_str = relative_string(relative);
+    if exclude.iter().any(|pattern| path_matches(&relative_str, pattern)) {
+        return Ok(());
+    }
+
+    let source = from_root.join(relative);
+    let metadata = match fs::symlink_metadata(&source) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let target = to_root.join(relative);
+
+    if metadata.is_dir() {
+        fs::create_dir_all(&target)?;
+        for entry in fs::read_dir(&source)? {
+            let entry = entry?;
+            copy_profile_entry(from_root, to_root, &relative.join(entry.file_name()), exclude, stats)?;
+        }
+    } else if metadata.is_file() {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let bytes = fs::copy(&source, &target)?;
+        jobs::checkpoint(&relative_str, bytes, 1)?;
+        stats.bytes += bytes;
+        stats.files += 1;
+    }
+    Ok(())
+}
+
+impl CursorManager {
+    pub fn session_dir(&self, session: &str) -> PathBuf {
+        Path::new(&self.config.profile_base).join(session)
+    }
+
+    pub fn clone_session(&self, source: &str, new_name: &str, options: CloneOptions) -> Result<String, Box<dyn Error>> {
+        validate_session_name(source)?;
+        validate_session_name(new_name)?;
+
+        let source_info = self.read_session_info(source)?;
+        let source_dir = self.session_dir(source);
+        let target_dir = self.session_dir(new_name);
+        if target_dir.exists() {
+            return Err(Box::new(CursorManagerError::SessionExists { name: new_name.to_string() }));
+        }
+        let _slot = self.reserve_session_slot(new_name)?;
+
+        let include: Vec<String> = options.include.iter().map(|p| normalize_relative(p)).filter(|p| !p.is_empty()).collect();
+        let mut exclude: Vec<String> = options.exclude.iter().map(|p| normalize_relative(p)).filter(|p| !p.is_empty()).collect();
+        // session.json and the workspace file always belong to the new session
+        exclude.push(SESSION_FILE.to_string());
+        exclude.push(format!("{}.code-workspace", source));
+
+        let profile_base = Path::new(&self.config.profile_base);
+        self.ensure_free_space(&[(profile_base, unpacked_size(&source_dir, &include, &exclude))])?;
+
+        // Copied under a hidden name and renamed once complete, so a failed clone never looks like a session
+        let staging = staging_path(profile_base, new_name);
+        let mut stats = CopyStats::default();
+        let staged = (|| -> Result<SessionInfo, Box<dyn Error>> {
+            fs::create_dir_all(&staging)?;
+            for relative in &include {
+                copy_profile_entry(&source_dir, &staging, Path::new(relative), &exclude, &mut stats)?;
+            }
+
+            // extensions.json records absolute install locations inside the source profile
+            let extensions_json = staging.join(EXTENSIONS_DIR).join("extensions.json");
+            if extensions_json.exists() {
+                let mut extensions: serde_json::Value = serde_json::from_str(&fs::read_to_string(&extensions_json)?)?;
+                rewrite_path_prefix(&mut extensions, &source_dir.to_string_lossy(), &target_dir.to_string_lossy());
+                fs::write(&extensions_json, serde_json::to_string(&extensions)?)?;
+            }
+
+            let mut session_info = SessionInfo::new(new_name, &target_dir, source_info.electron_app.clone());
+            if options.copy_metadata {
+                session_info.projects = source_info.projects.clone();
+                session_info.tags = source_info.tags.clone();
+                session_info.notes = source_info.notes.clone();
+                session_info.color = source_info.color.clone();
+                session_info.icon = source_info.icon.clone();
+                session_info.launch_args = source_info.launch_args.clone();
+                session_info.env = source_info.env.clone();
+                session_info.disabled_extensions = source_info.disabled_extensions.clone();
+            }
+            session_info.modified = Some(Local::now());
+            fs::write(staging.join(SESSION_FILE), serde_json::to_string_pretty(&session_info)?)?;
+            commit_staged(&staging, &target_dir)?;
+            Ok(session_info)
+        })();
+        let session_info = match staged {
+            Ok(session_info) => session_info,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&staging);
+                return Err(e);
+            }
+        };
+        if !session_info.projects.is_empty() {
+            self.set_session_projects(new_name, session_info.projects.clone())?;
+        }
+
+        Ok(format!(
+            "Session '{}' cloned to '{}' ({} files, {} bytes)",
+            source, new_name, stats.files, stats.bytes
+        ))
+    }
+}
+
+// Total size of the regular files below `path`, symlinks are not followed
+pub fn dir_size(path: &Path) -> u64 {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if metadata.is_file() {
+        return metadata.len();
+    }
+    if !metadata.is_dir() {
+        return 0;
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|entry| dir_size(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
+// Write through a temporary file in the same directory so readers never see a half written file
+pub fn write_file_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    fs::create_dir_all(path.parent().unwrap_or_else(|| Path::new(".")))?;
+    replace_file_atomic(path, content)
+}
+
+// Like write_file_atomic, but fails with NotFound instead of creating a missing parent directory
+pub fn replace_file_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let temp_path = parent.join(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+
+    let written = (|| {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{manager, session};
+    use crate::CloneOptions;
+
+    #[test]
+    fn a_clone_carries_the_source_metadata() {
+        let (_dir, manager) = manager();
+        session(&manager, "work", &[("User/settings.json", "{}")]);
+        manager.update_session_info("work", |info| {
+            info.tags = vec!["client".to_string()];
+            info.launch_args = vec!["--disable-gpu".to_string()];
+            info.env.insert("NODE_ENV".to_string(), "development".to_string());
+            info.disabled_extensions = vec!["ms-python.python".to_string()];
+        }).unwrap();
+
+        manager.clone_session("work", "copy", CloneOptions::default()).unwrap();
+        let info = manager.read_session_info("copy").unwrap();
+        assert_eq!(info.tags, vec!["client"]);
+        assert_eq!(info.launch_args, vec!["--disable-gpu"]);
+        assert_eq!(info.env["NODE_ENV"], "development");
+        assert_eq!(info.disabled_extensions, vec!["ms-python.python"]);
+
+        manager.clone_session("work", "bare", CloneOptions { copy_metadata: false, ..Default::default() }).unwrap();
+        let info = manager.read_session_info("bare").unwrap();
+        assert!(info.launch_args.is_empty() && info.env.is_empty() && info.disabled_extensions.is_empty());
+    }
+
+    #[test]
+    fn the_clone_source_must_be_a_session_name() {
+        let (_dir, manager) = manager();
+        session(&manager, "work", &[]);
+        assert!(manager.clone_session("../work", "copy", CloneOptions::default()).is_err());
+        assert!(manager.clone_session("", "copy", CloneOptions::default()).is_err());
+        assert!(!manager.session_dir("copy").exists());
+    }
+}
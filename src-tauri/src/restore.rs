@@ -0,0 +1,366 @@
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::archive::{ArchiveMode, ArchiveOptions};
+use crate::jobs;
+use crate::profile::{path_matches, relative_string, EXTENSIONS_DIR, GLOBAL_STORAGE_DIR, KEYBINDINGS_FILE, SESSION_FILE, SETTINGS_FILE, SNIPPETS_DIR, WORKSPACE_STORAGE_DIR};
+use crate::staging::{commit_staged, staging_path, sync_dir, sync_tree};
+use crate::{rewrite_path_prefix, CursorManager, SessionInfo};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum RestoreMode {
+    // Restore into a session that does not exist yet
+    #[default]
+    New,
+    // Swap the existing session's files for the archive's, after a safety snapshot
+    Replace,
+    // Copy the archive's files into the existing session, after a safety snapshot
+    Merge,
+}
+
+// Which copy wins when a file exists both in the archive and in the live session
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum MergePolicy {
+    #[default]
+    PreferArchive,
+    PreferLive,
+    NewestWins,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RestoreCategory {
+    Settings,
+    Keybindings,
+    Snippets,
+    Extensions,
+    // globalStorage and workspaceStorage
+    State,
+}
+
+impl RestoreCategory {
+    pub fn paths(&self) -> &'static [&'static str] {
+        match self {
+            RestoreCategory::Settings => &[SETTINGS_FILE],
+            RestoreCategory::Keybindings => &[KEYBINDINGS_FILE],
+            RestoreCategory::Snippets => &[SNIPPETS_DIR],
+            RestoreCategory::Extensions => &[EXTENSIONS_DIR],
+            RestoreCategory::State => &[GLOBAL_STORAGE_DIR, WORKSPACE_STORAGE_DIR],
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RestoreOptions {
+    #[serde(default)]
+    pub mode: RestoreMode,
+    #[serde(default)]
+    pub merge_policy: MergePolicy,
+    // None or empty restores everything
+    #[serde(default)]
+    pub categories: Option<Vec<RestoreCategory>>,
+}
+
+impl RestoreOptions {
+    fn selected_paths(&self) -> Option<Vec<&'static str>> {
+        match &self.categories {
+            Some(categories) if !categories.is_empty() => Some(categories.iter().flat_map(|category| category.paths().iter().copied()).collect()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MergeStats {
+    taken: u64,
+    kept: u64,
+}
+
+fn remove_path(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+// Move `relative` from the staging area over whatever the target has there. Staging sits next to the
+// target under the profile base, so these are renames of synced files
+fn move_staged(staging: &Path, target: &Path, relative: &str) -> io::Result<()> {
+    let to = target.join(relative);
+    remove_path(&to)?;
+    let from = staging.join(relative);
+    if !from.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(from, to)
+}
+
+// Drop everything from a staged profile that is not one of `keep` or below one
+fn prune_staged(staging: &Path, relative: &Path, keep: &[&str]) -> io::Result<()> {
+    for entry in fs::read_dir(staging.join(relative))? {
+        let entry = entry?;
+        let entry_relative = relative.join(entry.file_name());
+        let path = relative_string(&entry_relative);
+        if keep.iter().any(|kept| path_matches(&path, kept)) {
+            continue;
+        }
+        if keep.iter().any(|kept| path_matches(kept, &path)) {
+            prune_staged(staging, &entry_relative, keep)?;
+        } else {
+            remove_path(&entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+fn merge_staged(staging: &Path, target: &Path, relative: &Path, policy: MergePolicy, stats: &mut MergeStats) -> io::Result<()> {
+    let from = staging.join(relative);
+    let to = target.join(relative);
+    let metadata = match fs::symlink_metadata(&from) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    if metadata.is_dir() {
+        if to.is_file() {
+            // The live side has a file where the archive has a directory
+            if policy == MergePolicy::PreferLive {
+                stats.kept += 1;
+                return Ok(());
+            }
+            fs::remove_file(&to)?;
+        }
+        fs::create_dir_all(&to)?;
+        for entry in fs::read_dir(&from)? {
+            merge_staged(staging, target, &relative.join(entry?.file_name()), policy, stats)?;
+        }
+        return Ok(());
+    }
+
+    jobs::checkpoint(&relative_string(relative), 0, 0)?;
+    let take = match fs::symlink_metadata(&to) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => true,
+        Err(e) => return Err(e),
+        Ok(live) => match policy {
+            MergePolicy::PreferArchive => true,
+            MergePolicy::PreferLive => false,
+            MergePolicy::NewestWins => match (metadata.modified(), live.modified()) {
+                (Ok(archived), Ok(live)) => archived > live,
+                _ => false,
+            },
+        },
+    };
+    if !take {
+        stats.kept += 1;
+        return Ok(());
+    }
+    remove_path(&to)?;
+    fs::rename(&from, &to)?;
+    stats.taken += 1;
+    Ok(())
+}
+
+impl CursorManager {
+    // Point extensions.json of an unpacked profile at the directory it is about to live in
+    pub(crate) fn retarget_staged_profile(&self, staging: &Path, session_dir: &Path) -> Result<(), Box<dyn Error>> {
+        let session_file = staging.join(SESSION_FILE);
+        let extensions_json = staging.join(EXTENSIONS_DIR).join("extensions.json");
+        if !session_file.exists() || !extensions_json.exists() {
+            return Ok(());
+        }
+        let session_info: SessionInfo = serde_json::from_str(&fs::read_to_string(&session_file)?)?;
+        let new_dir = session_dir.to_string_lossy().to_string();
+        if session_info.path != new_dir {
+            let mut extensions: serde_json::Value = serde_json::from_str(&fs::read_to_string(&extensions_json)?)?;
+            rewrite_path_prefix(&mut extensions, &session_info.path, &new_dir);
+            fs::write(&extensions_json, serde_json::to_string(&extensions)?)?;
+        }
+        Ok(())
+    }
+
+    // Snapshots are cheap, but cannot be encrypted, so a configured key file makes this a full archive
+    pub(crate) fn safety_archive_mode(&self) -> ArchiveMode {
+        if self.config.archive_key_file.is_some() { ArchiveMode::Full } else { ArchiveMode::Snapshot }
+    }
+
+    // Archive the live session before it is overwritten
+    fn safety_archive(&self, session: &str) -> Result<String, Box<dyn Error>> {
+        let (entry, _) = self.create_archive(session, &ArchiveOptions { mode: Some(self.safety_archive_mode()), ..Default::default() })?;
+        Ok(entry.name)
+    }
+
+    // Put an unpacked profile in place as `session`, returning what happened to an existing one. `staging`
+    // is consumed for new and replace, and may hold leftovers after a merge
+    pub(crate) fn place_restored_profile(&self, staging: &Path, session: &str, existing: bool, options: &RestoreOptions) -> Result<String, Box<dyn Error>> {
+        let session_dir = self.session_dir(session);
+        let selected = options.selected_paths();
+
+        // A new session appears under its final name in one rename, complete or not at all
+        if !existing {
+            if let Some(paths) = &selected {
+                let keep: Vec<&str> = paths.iter().copied().chain(std::iter::once(SESSION_FILE)).collect();
+                prune_staged(staging, Path::new(""), &keep)?;
+            }
+            commit_staged(staging, &session_dir)?;
+            return Ok(String::new());
+        }
+
+        sync_tree(staging)?;
+
+        let safety = self.safety_archive(session)?;
+        // Merging and replacing categories change the live session file by file, so a failure or
+        // cancel part way leaves a mix that only the safety archive can undo
+        let partial = |e: io::Error| -> Box<dyn Error> {
+            format!("{}; the session was partly restored, its previous state is in safety archive '{}'", e, safety).into()
+        };
+        match (options.mode, &selected) {
+            (RestoreMode::Merge, _) => {
+                // The live session keeps its own identity
+                if session_dir.join(SESSION_FILE).exists() {
+                    fs::remove_file(staging.join(SESSION_FILE)).or_else(|e| if e.kind() == io::ErrorKind::NotFound { Ok(()) } else { Err(e) })?;
+                }
+                let mut stats = MergeStats::default();
+                match &selected {
+                    None => merge_staged(staging, &session_dir, Path::new(""), options.merge_policy, &mut stats).map_err(partial)?,
+                    Some(paths) => {
+                        for path in paths {
+                            merge_staged(staging, &session_dir, Path::new(path), options.merge_policy, &mut stats).map_err(partial)?;
+                        }
+                    }
+                }
+                Ok(format!("merged {} file(s) from the archive, kept {} live file(s), safety archive '{}'", stats.taken, stats.kept, safety))
+            }
+            (_, Some(paths)) => {
+                for path in paths {
+                    jobs::checkpoint(path, 0, 0).and_then(|_| move_staged(staging, &session_dir, path)).map_err(partial)?;
+                }
+                Ok(format!("replaced selected categories, safety archive '{}'", safety))
+            }
+            (_, None) => {
+                let profile_base = Path::new(&self.config.profile_base);
+                let replaced = staging_path(profile_base, &format!("{}.replaced", session));
+                // The last point a cancel leaves the live session untouched
+                jobs::check_cancelled()?;
+                fs::rename(&session_dir, &replaced)?;
+                if let Err(e) = fs::rename(staging, &session_dir) {
+                    let _ = fs::rename(&replaced, &session_dir);
+                    return Err(e.into());
+                }
+                sync_dir(profile_base)?;
+                if let Err(e) = fs::remove_dir_all(&replaced) {
+                    tracing::warn!("Could not remove the replaced profile {}: {}", replaced.to_string_lossy(), e);
+                }
+                Ok(format!("replaced the existing session, safety archive '{}'", safety))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::{MergePolicy, RestoreCategory, RestoreMode, RestoreOptions};
+    use crate::test_support::{manager, session};
+    use crate::{ArchiveMode, ArchiveOptions, CursorManager};
+
+    const ARCHIVED: [(&str, &str); 3] = [
+        ("User/settings.json", "{ \"from\": \"archive\" }"),
+        ("User/keybindings.json", "[\"archive\"]"),
+        ("User/snippets/archived.json", "{}"),
+    ];
+
+    // Archive `live` as it is now under the name "before", then change it: settings and keybindings are
+    // edited, one snippet is deleted and another one appears
+    fn archive_then_edit(manager: &CursorManager) {
+        let dir = session(manager, "live", &ARCHIVED);
+        let archived = manager.create_archive("live", &ArchiveOptions { mode: Some(ArchiveMode::Full), ..Default::default() }).unwrap().0;
+        let archived = manager.resolve_archive(&archived.name).unwrap();
+        // Out of the way of the safety archive, which may get the same timestamp
+        manager.rename_archive_entry(&archived, "live", "before").unwrap();
+
+        fs::write(dir.join("User/settings.json"), "{ \"from\": \"live\" }").unwrap();
+        fs::write(dir.join("User/keybindings.json"), "[\"live\"]").unwrap();
+        fs::remove_file(dir.join("User/snippets/archived.json")).unwrap();
+        fs::write(dir.join("User/snippets/live.json"), "{}").unwrap();
+    }
+
+    fn restore(manager: &CursorManager, options: RestoreOptions) -> String {
+        manager.restore_archive("before", Some("live"), true, None, &options).unwrap()
+    }
+
+    fn read(manager: &CursorManager, relative: &str) -> Option<String> {
+        fs::read_to_string(manager.session_dir("live").join(relative)).ok()
+    }
+
+    fn safety_archives(manager: &CursorManager) -> usize {
+        manager.archive_entries().unwrap().iter().filter(|entry| entry.name != "before").count()
+    }
+
+    #[test]
+    fn replace_swaps_in_the_archived_profile() {
+        let (_dir, manager) = manager();
+        archive_then_edit(&manager);
+
+        let message = restore(&manager, RestoreOptions { mode: RestoreMode::Replace, ..Default::default() });
+        assert!(message.contains("safety archive"));
+        assert_eq!(read(&manager, "User/settings.json").unwrap(), "{ \"from\": \"archive\" }");
+        assert_eq!(read(&manager, "User/keybindings.json").unwrap(), "[\"archive\"]");
+        assert!(read(&manager, "User/snippets/archived.json").is_some());
+        assert!(read(&manager, "User/snippets/live.json").is_none());
+        assert_eq!(safety_archives(&manager), 1);
+        assert_eq!(manager.read_session_info("live").unwrap().name, "live");
+    }
+
+    #[test]
+    fn replacing_categories_leaves_the_others_alone() {
+        let (_dir, manager) = manager();
+        archive_then_edit(&manager);
+
+        let categories = Some(vec![RestoreCategory::Settings]);
+        restore(&manager, RestoreOptions { mode: RestoreMode::Replace, categories, ..Default::default() });
+        assert_eq!(read(&manager, "User/settings.json").unwrap(), "{ \"from\": \"archive\" }");
+        assert_eq!(read(&manager, "User/keybindings.json").unwrap(), "[\"live\"]");
+        assert!(read(&manager, "User/snippets/archived.json").is_none());
+        assert!(read(&manager, "User/snippets/live.json").is_some());
+    }
+
+    #[test]
+    fn merge_adds_archived_files_and_resolves_conflicts_by_policy() {
+        let (_dir, manager) = manager();
+        archive_then_edit(&manager);
+
+        let message = restore(&manager, RestoreOptions { mode: RestoreMode::Merge, merge_policy: MergePolicy::PreferLive, categories: None });
+        assert!(message.starts_with("Archive 'before' restored to session 'live' (merged 1 file(s) from the archive, kept 2 live file(s)"), "{}", message);
+        assert_eq!(read(&manager, "User/settings.json").unwrap(), "{ \"from\": \"live\" }");
+        assert!(read(&manager, "User/snippets/archived.json").is_some());
+        assert!(read(&manager, "User/snippets/live.json").is_some());
+
+        let safety = manager.archive_entries().unwrap().into_iter().find(|entry| entry.name != "before").unwrap();
+        manager.rename_archive_entry(&safety, "live", "first-safety").unwrap();
+        restore(&manager, RestoreOptions { mode: RestoreMode::Merge, merge_policy: MergePolicy::PreferArchive, categories: None });
+        assert_eq!(read(&manager, "User/settings.json").unwrap(), "{ \"from\": \"archive\" }");
+        assert_eq!(read(&manager, "User/keybindings.json").unwrap(), "[\"archive\"]");
+        // Merging never deletes
+        assert!(read(&manager, "User/snippets/live.json").is_some());
+    }
+
+    #[test]
+    fn a_new_restore_does_not_touch_an_existing_session() {
+        let (_dir, manager) = manager();
+        archive_then_edit(&manager);
+
+        assert!(manager.restore_archive("before", Some("live"), true, None, &RestoreOptions::default()).is_err());
+        assert_eq!(read(&manager, "User/settings.json").unwrap(), "{ \"from\": \"live\" }");
+        assert_eq!(safety_archives(&manager), 0);
+    }
+}
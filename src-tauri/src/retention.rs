@@ -0,0 +1,397 @@
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use chrono::{DateTime, Datelike, Local};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Runtime};
+use crate::profile::write_file_atomic;
+use crate::activity::current_config;
+use crate::archive::parse_archive_timestamp;
+use crate::{split_archive_name, CursorManager};
+
+pub const ARCHIVE_RETENTION_EVENT: &str = "archive-retention";
+// Names of pinned archives, kept beside the archives themselves
+const PINS_FILE: &str = ".pins.json";
+const FIRST_RUN_DELAY: Duration = Duration::from_secs(60);
+const RUN_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+// Rules are evaluated per source session. An archive survives when any keep rule selects it,
+// then max_age_days and max_total_bytes remove what is still too old or too much. Pinned archives are never deleted.
+// The default sets no rules and deletes nothing; retention only starts once a policy is saved
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub keep_last: Option<u32>,
+    #[serde(default)]
+    pub keep_daily: Option<u32>,
+    #[serde(default)]
+    pub keep_weekly: Option<u32>,
+    #[serde(default)]
+    pub keep_monthly: Option<u32>,
+    #[serde(default)]
+    pub max_total_bytes: Option<u64>,
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+}
+
+impl RetentionPolicy {
+    fn has_keep_rules(&self) -> bool {
+        self.keep_last.is_some() || self.keep_daily.is_some() || self.keep_weekly.is_some() || self.keep_monthly.is_some()
+    }
+
+    // Nothing can ever be deleted under this policy
+    pub fn is_noop(&self) -> bool {
+        !self.has_keep_rules() && self.max_total_bytes.is_none() && self.max_age_days.is_none()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchiveRecord {
+    pub name: String,
+    pub path: String,
+    pub session: String,
+    pub created: DateTime<Local>,
+    pub size: u64,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetentionDecision {
+    pub archive: String,
+    pub session: String,
+    pub created: DateTime<Local>,
+    pub size: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub kept: Vec<RetentionDecision>,
+    pub deleted: Vec<RetentionDecision>,
+    pub freed_bytes: u64,
+    pub errors: Vec<String>,
+}
+
+// Keep the newest archive of each of the `count` most recent periods that have one
+fn keep_per_period<K: PartialEq>(archives: &[&ArchiveRecord], count: Option<u32>, period: impl Fn(&DateTime<Local>) -> K, label: &str, keep: &mut BTreeMap<String, String>) {
+    let count = match count {
+        Some(count) => count as usize,
+        None => return,
+    };
+    let mut last_period: Option<K> = None;
+    let mut kept = 0;
+    for archive in archives {
+        if kept >= count {
+            break;
+        }
+        let key = period(&archive.created);
+        if last_period.as_ref() != Some(&key) {
+            keep.entry(archive.name.clone()).or_insert_with(|| label.to_string());
+            last_period = Some(key);
+            kept += 1;
+        }
+    }
+}
+
+// Which archives to delete under `policy`, with a reason for every archive
+pub fn plan_retention(archives: &[ArchiveRecord], policy: &RetentionPolicy, now: DateTime<Local>) -> (Vec<RetentionDecision>, Vec<RetentionDecision>) {
+    let decision = |archive: &ArchiveRecord, reason: String| RetentionDecision {
+        archive: archive.name.clone(),
+        session: archive.session.clone(),
+        created: archive.created,
+        size: archive.size,
+        reason,
+    };
+
+    let mut by_session: BTreeMap<&str, Vec<&ArchiveRecord>> = BTreeMap::new();
+    for archive in archives {
+        by_session.entry(archive.session.as_str()).or_default().push(archive);
+    }
+
+    // archive name -> rule that keeps it
+    let mut keep: BTreeMap<String, String> = BTreeMap::new();
+    for session_archives in by_session.values_mut() {
+        session_archives.sort_by_key(|archive| std::cmp::Reverse(archive.created));
+        if let Some(count) = policy.keep_last {
+            for archive in session_archives.iter().take(count as usize) {
+                keep.entry(archive.name.clone()).or_insert_with(|| "keep last".to_string());
+            }
+        }
+        keep_per_period(session_archives, policy.keep_daily, |t| t.date_naive(), "daily", &mut keep);
+        keep_per_period(session_archives, policy.keep_weekly, |t| t.iso_week(), "weekly", &mut keep);
+        keep_per_period(session_archives, policy.keep_monthly, |t| (t.year(), t.month()), "monthly", &mut keep);
+    }
+
+    let mut kept = Vec::new();
+    let mut deleted = Vec::new();
+    for archive in archives {
+        if archive.pinned {
+            kept.push(decision(archive, "pinned".to_string()));
+            continue;
+        }
+        let age_days = (now - archive.created).num_days();
+        if let Some(max_age) = policy.max_age_days {
+            if age_days > max_age as i64 {
+                deleted.push(decision(archive, format!("older than {} days", max_age)));
+                continue;
+            }
+        }
+        match keep.get(&archive.name) {
+            Some(rule) => kept.push(decision(archive, rule.clone())),
+            None if !policy.has_keep_rules() => kept.push(decision(archive, "no keep rules".to_string())),
+            None => deleted.push(decision(archive, "not selected by any keep rule".to_string())),
+        }
+    }
+
+    // Size cap: drop the oldest unpinned survivors until the rest fits
+    if let Some(max_total) = policy.max_total_bytes {
+        let pinned: HashSet<&str> = archives.iter().filter(|a| a.pinned).map(|a| a.name.as_str()).collect();
+        let mut total: u64 = kept.iter().map(|k| k.size).sum();
+        kept.sort_by_key(|decision| decision.created);
+        let mut index = 0;
+        while total > max_total && index < kept.len() {
+            if pinned.contains(kept[index].archive.as_str()) {
+                index += 1;
+                continue;
+            }
+            let mut removed = kept.remove(index);
+            total -= removed.size;
+            removed.reason = format!("total size above {} bytes", max_total);
+            deleted.push(removed);
+        }
+    }
+
+    kept.sort_by(|a, b| a.archive.cmp(&b.archive));
+    deleted.sort_by(|a, b| a.archive.cmp(&b.archive));
+    (kept, deleted)
+}
+
+impl CursorManager {
+    fn pins_path(&self) -> PathBuf {
+        Path::new(&self.config.archive_base).join(PINS_FILE)
+    }
+
+    pub fn pinned_archives(&self) -> HashSet<String> {
+        fs::read_to_string(self.pins_path())
+            .ok()
+            .and_then(|data| serde_json::from_str::<HashSet<String>>(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn write_pins(&self, pins: &HashSet<String>) -> Result<(), Box<dyn Error>> {
+        let mut sorted: Vec<&String> = pins.iter().collect();
+        sorted.sort();
+        write_file_atomic(&self.pins_path(), serde_json::to_string_pretty(&sorted)?.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn set_archive_pinned(&self, archive: &str, pinned: bool) -> Result<String, Box<dyn Error>> {
+        let archive = self.resolve_archive(archive)?.name;
+        let mut pins = self.pinned_archives();
+        if pinned {
+            pins.insert(archive.clone());
+        } else {
+            pins.remove(&archive);
+        }
+        self.write_pins(&pins)?;
+        Ok(format!("Archive '{}' {}", archive, if pinned { "pinned" } else { "unpinned" }))
+    }
+
+    // Keep the pin list in step with renamed or deleted archives
+    pub(crate) fn move_archive_pin(&self, old_name: &str, new_name: Option<&str>) {
+        let mut pins = self.pinned_archives();
+        if !pins.remove(old_name) {
+            return;
+        }
+        if let Some(new_name) = new_name {
+            pins.insert(new_name.to_string());
+        }
+        if let Err(e) = self.write_pins(&pins) {
+            tracing::warn!("Could not update archive pins: {}", e);
+        }
+    }
+
+    // Archives with a manifest or a name carrying session and timestamp; anything else is left alone by retention
+    pub fn archive_records(&self) -> Result<Vec<ArchiveRecord>, Box<dyn Error>> {
+        let pins = self.pinned_archives();
+        let mut records = Vec::new();
+
+        for entry in self.archive_entries()? {
+            let origin = match entry.read_manifest() {
+                Some(manifest) => Some((manifest.session, manifest.created)),
+                None => split_archive_name(&entry.name).and_then(|(session, ts)| Some((session.to_string(), parse_archive_timestamp(ts)?))),
+            };
+            let (session, created) = match origin {
+                Some(origin) => origin,
+                None => continue,
+            };
+            records.push(ArchiveRecord {
+                path: entry.path.to_string_lossy().to_string(),
+                size: self.archive_size(&entry),
+                pinned: pins.contains(&entry.name),
+                name: entry.name,
+                session,
+                created,
+            });
+        }
+        Ok(records)
+    }
+
+    pub fn apply_archive_retention(&self, policy: &RetentionPolicy, dry_run: bool) -> Result<RetentionReport, Box<dyn Error>> {
+        let (kept, deleted) = plan_retention(&self.archive_records()?, policy, Local::now());
+        let mut report = RetentionReport {
+            dry_run,
+            kept,
+            deleted: Vec::new(),
+            freed_bytes: 0,
+            errors: Vec::new(),
+        };
+
+        for decision in deleted {
+            if !dry_run {
+                if let Err(e) = self.delete_archive(&decision.archive) {
+                    report.errors.push(format!("{}: {}", decision.archive, e));
+                    continue;
+                }
+                tracing::info!("Retention deleted archive '{}' ({})", decision.archive, decision.reason);
+            }
+            report.freed_bytes += decision.size;
+            report.deleted.push(decision);
+        }
+        Ok(report)
+    }
+}
+
+// Background thread that applies Config.archive_retention while auto_cleanup_archives is on. The first run
+// of every policy it has not seen yet is a dry run that is only reported, so nothing is deleted until the
+// user has had a chance to see what the policy would remove
+pub fn start_retention_scheduler<R: Runtime>(app: tauri::AppHandle<R>) {
+    std::thread::spawn(move || {
+        std::thread::sleep(FIRST_RUN_DELAY);
+        let mut previewed: Option<RetentionPolicy> = None;
+        loop {
+            let config = current_config(&app);
+            let policy = &config.archive_retention;
+            if config.auto_cleanup_archives && !policy.is_noop() {
+                let dry_run = previewed.as_ref() != Some(policy);
+                let manager = CursorManager::new(Some(config.clone()));
+                match manager.apply_archive_retention(policy, dry_run) {
+                    Ok(report) => {
+                        previewed = Some(policy.clone());
+                        if !report.deleted.is_empty() || !report.errors.is_empty() {
+                            if dry_run {
+                                tracing::info!("Archive retention would remove {} archive(s), {} bytes", report.deleted.len(), report.freed_bytes);
+                            } else {
+                                tracing::info!("Archive retention removed {} archive(s), {} bytes", report.deleted.len(), report.freed_bytes);
+                            }
+                            let _ = app.emit(ARCHIVE_RETENTION_EVENT, report);
+                        }
+                    }
+                    Err(e) => tracing::warn!("Archive retention failed: {}", e),
+                }
+            }
+            std::thread::sleep(RUN_INTERVAL);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 3, day, hour, 0, 0).unwrap()
+    }
+
+    fn record(name: &str, created: DateTime<Local>, pinned: bool) -> ArchiveRecord {
+        ArchiveRecord {
+            name: name.to_string(),
+            path: format!("/archives/{}", name),
+            session: name.split('-').next().unwrap().to_string(),
+            created,
+            size: 100,
+            pinned,
+        }
+    }
+
+    fn names(decisions: &[RetentionDecision]) -> Vec<&str> {
+        decisions.iter().map(|decision| decision.archive.as_str()).collect()
+    }
+
+    #[test]
+    fn default_policy_deletes_nothing() {
+        let archives = vec![record("a-1", at(1, 10), false), record("a-2", at(2, 10), false)];
+        let policy = RetentionPolicy::default();
+        assert!(policy.is_noop());
+        let (kept, deleted) = plan_retention(&archives, &policy, at(20, 0));
+        assert_eq!(names(&kept), ["a-1", "a-2"]);
+        assert!(deleted.is_empty());
+    }
+
+    #[test]
+    fn keep_last_is_per_session() {
+        let archives = vec![
+            record("a-1", at(1, 10), false),
+            record("a-2", at(2, 10), false),
+            record("b-1", at(1, 10), false),
+        ];
+        let policy = RetentionPolicy { keep_last: Some(1), ..Default::default() };
+        let (kept, deleted) = plan_retention(&archives, &policy, at(3, 0));
+        assert_eq!(names(&kept), ["a-2", "b-1"]);
+        assert_eq!(names(&deleted), ["a-1"]);
+    }
+
+    #[test]
+    fn daily_buckets_keep_newest_of_each_day_and_pins_survive() {
+        let archives = vec![
+            record("a-1", at(1, 9), true),
+            record("a-2", at(2, 9), false),
+            record("a-3", at(3, 9), false),
+            record("a-4", at(3, 18), false),
+            record("a-5", at(4, 9), false),
+        ];
+        let policy = RetentionPolicy { keep_daily: Some(2), ..Default::default() };
+        let (kept, deleted) = plan_retention(&archives, &policy, at(5, 0));
+        // Day 4 and day 3 (its later archive) fill the two daily slots; the pinned day 1 archive stays anyway
+        assert_eq!(names(&kept), ["a-1", "a-4", "a-5"]);
+        assert_eq!(names(&deleted), ["a-2", "a-3"]);
+        assert_eq!(kept[0].reason, "pinned");
+        assert_eq!(kept[1].reason, "daily");
+    }
+
+    #[test]
+    fn weekly_and_monthly_buckets_add_to_daily() {
+        let archives = vec![
+            record("a-1", at(3, 9), false),
+            record("a-2", at(10, 9), false),
+            record("a-3", at(17, 9), false),
+            record("a-4", at(18, 9), false),
+        ];
+        let policy = RetentionPolicy { keep_daily: Some(1), keep_weekly: Some(2), keep_monthly: Some(1), ..Default::default() };
+        let (kept, deleted) = plan_retention(&archives, &policy, at(20, 0));
+        // a-4 is the daily and the newest of its week and month, a-2 the newest of the previous week
+        assert_eq!(names(&kept), ["a-2", "a-4"]);
+        assert_eq!(names(&deleted), ["a-1", "a-3"]);
+    }
+
+    #[test]
+    fn max_age_and_size_cap_skip_pinned_archives() {
+        let archives = vec![
+            record("a-1", at(1, 9), true),
+            record("a-2", at(2, 9), false),
+            record("a-3", at(15, 9), false),
+            record("a-4", at(16, 9), false),
+            record("a-5", at(17, 9), false),
+        ];
+        let policy = RetentionPolicy { keep_last: Some(10), max_age_days: Some(10), max_total_bytes: Some(300), ..Default::default() };
+        let (kept, deleted) = plan_retention(&archives, &policy, at(18, 0));
+        assert_eq!(names(&kept), ["a-1", "a-4", "a-5"]);
+        assert_eq!(names(&deleted), ["a-2", "a-3"]);
+        assert!(deleted[0].reason.starts_with("older than"));
+        assert!(deleted[1].reason.starts_with("total size"));
+    }
+}
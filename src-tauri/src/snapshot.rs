@@ -0,0 +1,564 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, UNIX_EPOCH};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::archive::{ArchiveFormat, ArchivedFile, WrittenArchive};
+use crate::jobs;
+use crate::profile::{path_matches, relative_string, write_file_atomic, CopyStats};
+use crate::CursorManager;
+
+pub const SNAPSHOT_EXTENSION: &str = ".snapshot.json";
+// Content-addressed chunks shared by all snapshots, hidden so it is never listed as an archive
+const STORE_DIR: &str = ".store";
+const CHUNKS_DIR: &str = "chunks";
+// chunk hash -> number of snapshots referencing it
+const REFCOUNTS_FILE: &str = "refcounts.json";
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+// Snapshot writes, deletes and gc must not interleave. CursorManager is rebuilt for every command,
+// so the lock lives outside of it
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+pub(crate) fn lock_store() -> MutexGuard<'static, ()> {
+    STORE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotFile {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    // Unix seconds
+    #[serde(default)]
+    pub modified: Option<i64>,
+    #[serde(default)]
+    pub mode: Option<u32>,
+    pub chunks: Vec<String>,
+}
+
+// The snapshot itself: which chunks make up each file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotIndex {
+    pub archive: String,
+    pub created: DateTime<Local>,
+    pub dirs: Vec<String>,
+    pub files: Vec<SnapshotFile>,
+}
+
+impl SnapshotIndex {
+    fn chunk_set(&self) -> BTreeSet<&str> {
+        self.files.iter().flat_map(|file| file.chunks.iter().map(|chunk| chunk.as_str())).collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotStats {
+    pub chunk_count: usize,
+    // Uncompressed size of the files in the snapshot
+    pub logical_bytes: u64,
+    // Stored bytes no other snapshot references; deleting the snapshot frees these
+    pub unique_bytes: u64,
+    pub shared_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub snapshots: usize,
+    pub chunks_kept: usize,
+    pub removed_chunks: usize,
+    pub freed_bytes: u64,
+    // Chunks whose stored reference count did not match the snapshots on disk
+    pub corrected_counts: usize,
+    pub errors: Vec<String>,
+}
+
+pub fn read_snapshot_index(path: &Path) -> Result<SnapshotIndex, Box<dyn Error>> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+// Read until `buf` is full or the reader is exhausted
+fn fill(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn modified_secs(metadata: &fs::Metadata) -> Option<i64> {
+    let modified = metadata.modified().ok()?;
+    modified.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs() as i64)
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode())
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+pub(crate) fn set_file_mode(path: &Path, mode: Option<u32>) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    match mode {
+        Some(mode) => fs::set_permissions(path, fs::Permissions::from_mode(mode)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn set_file_mode(_path: &Path, _mode: Option<u32>) -> io::Result<()> {
+    Ok(())
+}
+
+// Index paths come from disk; refuse anything that could land outside the restore target
+pub(crate) fn safe_relative(path: &str) -> Result<&Path, Box<dyn Error>> {
+    let relative = Path::new(path);
+    if path.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!("Unsafe archive path: {}", path).into());
+    }
+    Ok(relative)
+}
+
+// Chunks written by the snapshot in progress
+#[derive(Default)]
+struct AddedChunks {
+    bytes: u64,
+    paths: Vec<PathBuf>,
+}
+
+impl CursorManager {
+    fn store_dir(&self) -> PathBuf {
+        Path::new(&self.config.archive_base).join(STORE_DIR)
+    }
+
+    fn chunk_path(&self, hash: &str) -> PathBuf {
+        self.store_dir().join(CHUNKS_DIR).join(&hash[..2.min(hash.len())]).join(hash)
+    }
+
+    // None when the store has no counts file yet. A file that cannot be read is an error rather than
+    // empty counts, which would make every chunk look unreferenced
+    fn read_refcounts(&self) -> Result<Option<BTreeMap<String, u32>>, Box<dyn Error>> {
+        let path = self.store_dir().join(REFCOUNTS_FILE);
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Cannot read snapshot reference counts {}: {}", path.to_string_lossy(), e).into()),
+        };
+        match serde_json::from_str(&data) {
+            Ok(refcounts) => Ok(Some(refcounts)),
+            Err(e) => Err(format!("Snapshot reference counts {} are corrupt, run gc to rebuild them: {}", path.to_string_lossy(), e).into()),
+        }
+    }
+
+    // Reference counts as the snapshot indexes on disk have them, leaving out the snapshot at `except`.
+    // Fails when any index cannot be read, since its chunks would look unreferenced
+    fn count_references(&self, except: Option<&Path>) -> Result<(usize, BTreeMap<String, u32>), Box<dyn Error>> {
+        let mut snapshots = 0;
+        let mut counts: BTreeMap<String, u32> = BTreeMap::new();
+        for entry in self.archive_entries()? {
+            if entry.format != ArchiveFormat::Snapshot || Some(entry.path.as_path()) == except {
+                continue;
+            }
+            let index = read_snapshot_index(&entry.path).map_err(|e| format!("Cannot read snapshot '{}': {}", entry.name, e))?;
+            snapshots += 1;
+            for chunk in index.chunk_set() {
+                *counts.entry(chunk.to_string()).or_insert(0) += 1;
+            }
+        }
+        Ok((snapshots, counts))
+    }
+
+    fn write_refcounts(&self, refcounts: &BTreeMap<String, u32>) -> Result<(), Box<dyn Error>> {
+        write_file_atomic(&self.store_dir().join(REFCOUNTS_FILE), serde_json::to_string(refcounts)?.as_bytes())?;
+        Ok(())
+    }
+
+    fn store_chunk(&self, data: &[u8], level: i32, added: &mut AddedChunks) -> Result<String, Box<dyn Error>> {
+        let hash = format!("{:x}", Sha256::digest(data));
+        let path = self.chunk_path(&hash);
+        if !path.exists() {
+            let compressed = zstd::bulk::compress(data, level)?;
+            write_file_atomic(&path, &compressed)?;
+            added.bytes += compressed.len() as u64;
+            added.paths.push(path);
+        }
+        Ok(hash)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn snapshot_tree(&self, root: &Path, relative: &Path, exclude: &[String], level: i32, index: &mut SnapshotIndex, stats: &mut CopyStats, added: &mut AddedChunks) -> Result<(), Box<dyn Error>> {
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        for entry in fs::read_dir(root.join(relative))? {
+            let entry = entry?;
+            let entry_relative = relative.join(entry.file_name());
+            let relative_path = relative_string(&entry_relative);
+            if exclude.iter().any(|pattern| path_matches(&relative_path, pattern)) {
+                continue;
+            }
+            // Symlinks are skipped, like in full archives
+            let metadata = fs::symlink_metadata(entry.path())?;
+            if metadata.is_dir() {
+                index.dirs.push(relative_path);
+                self.snapshot_tree(root, &entry_relative, exclude, level, index, stats, added)?;
+            } else if metadata.is_file() {
+                let mut reader = File::open(entry.path())?.take(metadata.len());
+                let mut hasher = Sha256::new();
+                let mut size = 0;
+                let mut chunks = Vec::new();
+                loop {
+                    let read = fill(&mut reader, &mut buf)?;
+                    if read == 0 {
+                        break;
+                    }
+                    hasher.update(&buf[..read]);
+                    size += read as u64;
+                    chunks.push(self.store_chunk(&buf[..read], level, added)?);
+                }
+                index.files.push(SnapshotFile {
+                    path: relative_path,
+                    size,
+                    sha256: format!("{:x}", hasher.finalize()),
+                    modified: modified_secs(&metadata),
+                    mode: file_mode(&metadata),
+                    chunks,
+                });
+                jobs::checkpoint(&index.files[index.files.len() - 1].path, size, 1)?;
+                stats.files += 1;
+                stats.bytes += size;
+            }
+        }
+        Ok(())
+    }
+
+    // Chunk a profile into the store and write its index to `target`. Caller holds lock_store() until
+    // the snapshot is retained and in place
+    pub(crate) fn write_snapshot(&self, source: &Path, target: &Path, archive: &str, level: i32, exclude: &[String]) -> Result<(SnapshotIndex, WrittenArchive), Box<dyn Error>> {
+        let mut index = SnapshotIndex {
+            archive: archive.to_string(),
+            created: Local::now(),
+            dirs: Vec::new(),
+            files: Vec::new(),
+        };
+        let mut stats = CopyStats::default();
+        let mut added = AddedChunks::default();
+        // Chunks only this snapshot wrote are unreferenced if it fails or is cancelled
+        if let Err(e) = self.snapshot_tree(source, Path::new(""), exclude, level, &mut index, &mut stats, &mut added) {
+            for path in &added.paths {
+                let _ = fs::remove_file(path);
+            }
+            return Err(e);
+        }
+
+        let data = serde_json::to_vec(&index)?;
+        write_file_atomic(target, &data)?;
+        let files = index
+            .files
+            .iter()
+            .map(|file| ArchivedFile { path: file.path.clone(), size: file.size, sha256: file.sha256.clone() })
+            .collect();
+        let written = WrittenArchive {
+            stats,
+            files,
+            // What this snapshot added to the store
+            archive_bytes: added.bytes + data.len() as u64,
+            content_hash: format!("sha256:{:x}", Sha256::digest(&data)),
+        };
+        Ok((index, written))
+    }
+
+    // Caller holds lock_store() and has not yet put the snapshot under its final name
+    pub(crate) fn retain_snapshot(&self, index: &SnapshotIndex) -> Result<(), Box<dyn Error>> {
+        // Without a counts file, start from what the existing snapshots reference
+        let mut refcounts = match self.read_refcounts()? {
+            Some(refcounts) => refcounts,
+            None => self.count_references(None)?.1,
+        };
+        for chunk in index.chunk_set() {
+            *refcounts.entry(chunk.to_string()).or_insert(0) += 1;
+        }
+        self.write_refcounts(&refcounts)
+    }
+
+    // Remove a snapshot and the chunks only it referenced; returns the freed bytes. Counts are rebuilt from
+    // the remaining snapshots first, and a chunk is only deleted when the stored counts also knew about it
+    pub(crate) fn delete_snapshot(&self, path: &Path) -> Result<u64, Box<dyn Error>> {
+        let _store = lock_store();
+        let recorded = self.read_refcounts()?.unwrap_or_default();
+        let index = read_snapshot_index(path);
+        let remaining = self.count_references(Some(path));
+        fs::remove_file(path)?;
+        let index = match index {
+            Ok(index) => index,
+            Err(e) => {
+                tracing::warn!("Deleted unreadable snapshot {}; run gc to reclaim its chunks: {}", path.to_string_lossy(), e);
+                return Ok(0);
+            }
+        };
+        let (_, refcounts) = match remaining {
+            Ok(remaining) => remaining,
+            Err(e) => {
+                tracing::warn!("Deleted snapshot {} but kept its chunks; run gc once this is fixed: {}", path.to_string_lossy(), e);
+                return Ok(0);
+            }
+        };
+
+        let mut freed = 0;
+        for chunk in index.chunk_set() {
+            if refcounts.contains_key(chunk) {
+                continue;
+            }
+            if !recorded.contains_key(chunk) {
+                tracing::warn!("Chunk {} has no recorded reference count, leaving it for gc", chunk);
+                continue;
+            }
+            let chunk_path = self.chunk_path(chunk);
+            freed += fs::metadata(&chunk_path).map(|m| m.len()).unwrap_or(0);
+            let _ = fs::remove_file(&chunk_path);
+        }
+        self.write_refcounts(&refcounts)?;
+        Ok(freed)
+    }
+
+    // Write the contents of one snapshot file by concatenating its chunks
+    pub(crate) fn read_snapshot_file(&self, file: &SnapshotFile, writer: &mut impl Write) -> Result<(), Box<dyn Error>> {
+        for chunk in &file.chunks {
+            let chunk_file = File::open(self.chunk_path(chunk)).map_err(|e| format!("chunk {} of {} is unavailable: {}", chunk, file.path, e))?;
+            io::copy(&mut zstd::Decoder::new(chunk_file)?, writer)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn restore_snapshot(&self, path: &Path, target: &Path) -> Result<(), Box<dyn Error>> {
+        let _store = lock_store();
+        let index = read_snapshot_index(path)?;
+        for dir in &index.dirs {
+            fs::create_dir_all(target.join(safe_relative(dir)?))?;
+        }
+        for file in &index.files {
+            let destination = target.join(safe_relative(&file.path)?);
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut output = File::create(&destination)?;
+            self.read_snapshot_file(file, &mut output)?;
+            if let Some(modified) = file.modified.filter(|secs| *secs >= 0) {
+                let _ = output.set_modified(UNIX_EPOCH + Duration::from_secs(modified as u64));
+            }
+            drop(output);
+            set_file_mode(&destination, file.mode)?;
+            jobs::checkpoint(&file.path, file.size, 1)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn snapshot_stats(&self, index: &SnapshotIndex, refcounts: &BTreeMap<String, u32>) -> SnapshotStats {
+        let chunks = index.chunk_set();
+        let mut stats = SnapshotStats {
+            chunk_count: chunks.len(),
+            logical_bytes: index.files.iter().map(|file| file.size).sum(),
+            unique_bytes: 0,
+            shared_bytes: 0,
+        };
+        for chunk in chunks {
+            let size = fs::metadata(self.chunk_path(chunk)).map(|m| m.len()).unwrap_or(0);
+            if refcounts.get(chunk).copied().unwrap_or(0) > 1 {
+                stats.shared_bytes += size;
+            } else {
+                stats.unique_bytes += size;
+            }
+        }
+        stats
+    }
+
+    pub fn archive_snapshot_stats(&self, path: &Path) -> Option<SnapshotStats> {
+        let index = read_snapshot_index(path).ok()?;
+        Some(self.snapshot_stats(&index, &self.read_refcounts().ok().flatten().unwrap_or_default()))
+    }
+
+    // Rebuild reference counts from the snapshots on disk and remove chunks nothing references,
+    // e.g. left behind by an interrupted snapshot
+    pub fn gc_archives(&self, dry_run: bool) -> Result<GcReport, Box<dyn Error>> {
+        let _store = lock_store();
+        let mut report = GcReport {
+            dry_run,
+            snapshots: 0,
+            chunks_kept: 0,
+            removed_chunks: 0,
+            freed_bytes: 0,
+            corrected_counts: 0,
+            errors: Vec::new(),
+        };
+
+        // A snapshot whose chunks cannot be told apart from garbage means nothing is collected this run
+        let (snapshots, actual) = self.count_references(None).map_err(|e| format!("{}, not collecting", e))?;
+        report.snapshots = snapshots;
+
+        // A missing or corrupt counts file is what gc repairs
+        let stored = self.read_refcounts();
+        let mut rewrite = !matches!(stored, Ok(Some(_)));
+        let stored = stored.ok().flatten().unwrap_or_default();
+        let keys: BTreeSet<&String> = stored.keys().chain(actual.keys()).collect();
+        report.corrected_counts = keys.into_iter().filter(|chunk| stored.get(*chunk) != actual.get(*chunk)).count();
+        rewrite |= report.corrected_counts > 0;
+
+        let chunks_dir = self.store_dir().join(CHUNKS_DIR);
+        if let Ok(prefixes) = fs::read_dir(&chunks_dir) {
+            for prefix in prefixes.flatten() {
+                for chunk in fs::read_dir(prefix.path()).into_iter().flatten().flatten() {
+                    let name = chunk.file_name().to_string_lossy().to_string();
+                    if actual.contains_key(&name) {
+                        report.chunks_kept += 1;
+                        continue;
+                    }
+                    let size = chunk.metadata().map(|m| m.len()).unwrap_or(0);
+                    if !dry_run {
+                        if let Err(e) = fs::remove_file(chunk.path()) {
+                            report.errors.push(format!("{}: {}", name, e));
+                            continue;
+                        }
+                    }
+                    report.removed_chunks += 1;
+                    report.freed_bytes += size;
+                }
+            }
+        }
+        for chunk in actual.keys() {
+            if !self.chunk_path(chunk).exists() {
+                report.errors.push(format!("chunk {} is referenced but missing", chunk));
+            }
+        }
+
+        if !dry_run && rewrite {
+            self.write_refcounts(&actual)?;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{manager, session};
+    use crate::{ArchiveMode, ArchiveOptions};
+
+    fn chunk_of(data: &str) -> String {
+        format!("{:x}", Sha256::digest(data.as_bytes()))
+    }
+
+    // Snapshot a session holding one file shared by every session and one of its own, besides session.json
+    fn snapshot(manager: &CursorManager, name: &str) -> String {
+        session(manager, name, &[("shared.txt", "shared"), ("own.txt", &format!("only in {}", name))]);
+        let options = ArchiveOptions { mode: Some(ArchiveMode::Snapshot), ..Default::default() };
+        manager.create_archive(name, &options).unwrap().0.name
+    }
+
+    fn refcounts(manager: &CursorManager) -> BTreeMap<String, u32> {
+        manager.read_refcounts().unwrap().unwrap()
+    }
+
+    #[test]
+    fn shared_chunks_survive_deleting_one_snapshot() {
+        let (_dir, manager) = manager();
+        let manager = &manager;
+        let a = snapshot(manager, "a");
+        let b = snapshot(manager, "b");
+        assert_eq!(refcounts(manager).get(&chunk_of("shared")), Some(&2));
+
+        manager.delete_archive(&a).unwrap();
+        let counts = refcounts(manager);
+        assert_eq!(counts.get(&chunk_of("shared")), Some(&1));
+        assert!(!counts.contains_key(&chunk_of("only in a")));
+        assert!(!manager.chunk_path(&chunk_of("only in a")).exists());
+
+        let restored = tempfile::tempdir().unwrap();
+        manager.restore_snapshot(&manager.resolve_archive(&b).unwrap().path, restored.path()).unwrap();
+        assert_eq!(fs::read_to_string(restored.path().join("shared.txt")).unwrap(), "shared");
+    }
+
+    #[test]
+    fn corrupt_refcounts_stop_deletes() {
+        let (_dir, manager) = manager();
+        let manager = &manager;
+        let a = snapshot(manager, "a");
+        snapshot(manager, "b");
+        fs::write(manager.store_dir().join(REFCOUNTS_FILE), "{ not json").unwrap();
+
+        assert!(manager.delete_archive(&a).is_err());
+        assert!(manager.resolve_archive(&a).is_ok());
+        assert!(manager.chunk_path(&chunk_of("shared")).exists());
+        assert!(manager.chunk_path(&chunk_of("only in a")).exists());
+    }
+
+    #[test]
+    fn unrecorded_chunks_are_left_for_gc() {
+        let (_dir, manager) = manager();
+        let manager = &manager;
+        let a = snapshot(manager, "a");
+        snapshot(manager, "b");
+        fs::remove_file(manager.store_dir().join(REFCOUNTS_FILE)).unwrap();
+
+        manager.delete_archive(&a).unwrap();
+        assert!(manager.chunk_path(&chunk_of("shared")).exists());
+        assert!(manager.chunk_path(&chunk_of("only in a")).exists());
+        // The counts were rebuilt from the remaining snapshot
+        assert_eq!(refcounts(manager).get(&chunk_of("shared")), Some(&1));
+
+        // own.txt and session.json of the deleted snapshot
+        let report = manager.gc_archives(false).unwrap();
+        assert_eq!(report.snapshots, 1);
+        assert_eq!(report.removed_chunks, 2);
+        assert!(!manager.chunk_path(&chunk_of("only in a")).exists());
+        assert!(manager.chunk_path(&chunk_of("only in b")).exists());
+    }
+
+    #[test]
+    fn missing_refcounts_are_rebuilt_before_counting_a_new_snapshot() {
+        let (_dir, manager) = manager();
+        let manager = &manager;
+        snapshot(manager, "a");
+        snapshot(manager, "b");
+        fs::remove_file(manager.store_dir().join(REFCOUNTS_FILE)).unwrap();
+
+        snapshot(manager, "c");
+        let counts = refcounts(manager);
+        assert_eq!(counts.get(&chunk_of("shared")), Some(&3));
+        assert_eq!(counts.get(&chunk_of("only in a")), Some(&1));
+    }
+
+    #[test]
+    fn gc_repairs_counts_and_removes_orphans() {
+        let (_dir, manager) = manager();
+        let manager = &manager;
+        snapshot(manager, "a");
+        let orphan = manager.chunk_path(&chunk_of("orphan"));
+        write_file_atomic(&orphan, b"orphan").unwrap();
+        fs::write(manager.store_dir().join(REFCOUNTS_FILE), "{}").unwrap();
+
+        let preview = manager.gc_archives(true).unwrap();
+        assert_eq!(preview.removed_chunks, 1);
+        assert!(orphan.exists());
+
+        let report = manager.gc_archives(false).unwrap();
+        assert_eq!(report.removed_chunks, 1);
+        assert_eq!(report.corrected_counts, 3);
+        assert!(!orphan.exists());
+        assert_eq!(refcounts(manager).get(&chunk_of("shared")), Some(&1));
+    }
+}
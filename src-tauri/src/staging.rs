@@ -0,0 +1,94 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use tauri::Runtime;
+use crate::activity::current_config;
+use crate::CursorManager;
+
+// Hidden names that only exist while an archive, restore or clone is running. Anything still carrying
+// one of these suffixes at startup was left behind by a crash
+pub const STAGING_SUFFIX: &str = ".staging";
+pub const PARTIAL_SUFFIX: &str = ".partial";
+const LEFTOVER_SUFFIXES: [&str; 3] = [STAGING_SUFFIX, PARTIAL_SUFFIX, ".tmp"];
+
+// A hidden sibling of `parent/name`, on the same filesystem so it can be renamed into place
+pub fn staging_path(parent: &Path, name: &str) -> PathBuf {
+    parent.join(format!(".{}.{}{}", name, uuid::Uuid::new_v4(), STAGING_SUFFIX))
+}
+
+#[cfg(unix)]
+pub fn sync_dir(path: &Path) -> io::Result<()> {
+    File::open(path)?.sync_all()
+}
+
+// Directories cannot be opened for syncing here; the file contents are what matters
+#[cfg(not(unix))]
+pub fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+// Flush every file below `path` to disk, then the directories holding them
+pub fn sync_tree(path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_file() {
+        return File::open(path)?.sync_all();
+    }
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            sync_tree(&entry?.path())?;
+        }
+        sync_dir(path)?;
+    }
+    Ok(())
+}
+
+// Sync a finished staging directory and rename it to its final name
+pub fn commit_staged(staging: &Path, target: &Path) -> io::Result<()> {
+    sync_tree(staging)?;
+    fs::rename(staging, target)?;
+    match target.parent() {
+        Some(parent) => sync_dir(parent),
+        None => Ok(()),
+    }
+}
+
+fn is_leftover(name: &str) -> bool {
+    name.starts_with('.') && LEFTOVER_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+impl CursorManager {
+    // Remove staging directories and partial files under the profile and archive bases. Only safe
+    // while nothing else is running, i.e. at startup
+    pub fn clean_staging(&self) -> Vec<PathBuf> {
+        let mut removed = Vec::new();
+        for base in [&self.config.profile_base, &self.config.archive_base] {
+            let entries = match fs::read_dir(base) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                if !is_leftover(&entry.file_name().to_string_lossy()) {
+                    continue;
+                }
+                let path = entry.path();
+                let result = match entry.file_type() {
+                    Ok(file_type) if file_type.is_dir() => fs::remove_dir_all(&path),
+                    _ => fs::remove_file(&path),
+                };
+                match result {
+                    Ok(()) => removed.push(path),
+                    Err(e) => tracing::warn!("Could not remove leftover {}: {}", path.to_string_lossy(), e),
+                }
+            }
+        }
+        removed
+    }
+}
+
+// Runs in setup, before any command can start a new operation
+pub fn clean_staging_on_startup<R: Runtime>(app: &tauri::AppHandle<R>) {
+    let removed = CursorManager::new(Some(current_config(app))).clean_staging();
+    if !removed.is_empty() {
+        tracing::info!("Removed {} leftover staging entries from an interrupted operation", removed.len());
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use crate::extensions::normalize_extension_id;
+use crate::profile::{write_file_atomic, EXTENSIONS_DIR, KEYBINDINGS_FILE, SETTINGS_FILE};
+use crate::vsix::install_vsix_into;
+use crate::{validate_session_name, CursorManager, CursorManagerError, ElectronApp, SessionInfo};
+
+// Reusable starting point for new sessions, stored as <templates dir>/<name>.json
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    // Used when create_session gets no name. Supports {template}, {date} and {n} (first free number)
+    #[serde(default)]
+    pub name_pattern: Option<String>,
+    #[serde(default)]
+    pub projects: Vec<String>,
+    // Raw file contents so comments in JSONC survive
+    #[serde(default)]
+    pub settings: Option<String>,
+    #[serde(default)]
+    pub keybindings: Option<String>,
+    // Extension ids such as "publisher.name"
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub launch_args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub electron_app: Option<ElectronApp>,
+    #[serde(default = "Local::now")]
+    pub created: DateTime<Local>,
+}
+
+impl SessionTemplate {
+    pub fn expand_name_pattern(&self, manager: &CursorManager) -> Option<String> {
+        let pattern = self.name_pattern.as_ref()?;
+        let base = pattern
+            .replace("{template}", &self.name)
+            .replace("{date}", &Local::now().format("%Y%m%d").to_string());
+
+        if !base.contains("{n}") {
+            return Some(base);
+        }
+        (1..10_000)
+            .map(|n| base.replace("{n}", &n.to_string()))
+            .find(|candidate| !manager.session_dir(candidate).exists())
+    }
+}
+
+impl CursorManager {
+    // Templates live next to the profile directory, e.g. ~/cursor-templates beside ~/cursor-profiles
+    pub fn templates_dir(&self) -> PathBuf {
+        let profile_base = Path::new(&self.config.profile_base);
+        profile_base
+            .parent()
+            .unwrap_or(profile_base)
+            .join("cursor-templates")
+    }
+
+    fn template_path(&self, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+        validate_session_name(name).map_err(|e| CursorManagerError::InvalidTemplateName { message: e.to_string() })?;
+        Ok(self.templates_dir().join(format!("{}.json", name)))
+    }
+
+    pub fn list_templates(&self) -> Result<Vec<SessionTemplate>, Box<dyn Error>> {
+        let templates_dir = self.templates_dir();
+        let mut templates = Vec::new();
+
+        if !templates_dir.exists() {
+            return Ok(templates);
+        }
+
+        for entry in fs::read_dir(&templates_dir)? {
+            let path = entry?.path();
+            if path.extension().map(|e| e == "json").unwrap_or(false) {
+                match fs::read_to_string(&path).map_err(Box::<dyn Error>::from).and_then(|data| Ok(serde_json::from_str::<SessionTemplate>(&data)?)) {
+                    Ok(template) => templates.push(template),
+                    Err(e) => tracing::warn!("Skipping unreadable template {}: {}", path.to_string_lossy(), e),
+                }
+            }
+        }
+
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(templates)
+    }
+
+    pub fn load_template(&self, name: &str) -> Result<SessionTemplate, Box<dyn Error>> {
+        let path = self.template_path(name)?;
+        if !path.exists() {
+            return Err(Box::new(CursorManagerError::TemplateNotFound { name: name.to_string() }));
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(&path)?)?)
+    }
+
+    // An existing template of the same name is only replaced when `overwrite` is set
+    pub fn save_template(&self, template: &SessionTemplate, overwrite: bool) -> Result<String, Box<dyn Error>> {
+        let path = self.template_path(&template.name)?;
+        if !overwrite && path.exists() {
+            return Err(Box::new(CursorManagerError::TemplateExists { name: template.name.clone() }));
+        }
+        write_file_atomic(&path, serde_json::to_string_pretty(template)?.as_bytes())?;
+        Ok(format!("Template '{}' saved", template.name))
+    }
+
+    pub fn create_template_from_session(&self, session: &str, template_name: &str, overwrite: bool) -> Result<SessionTemplate, Box<dyn Error>> {
+        let session_info = self.read_session_info(session)?;
+        let session_dir = self.session_dir(session);
+
+        let read_optional = |relative: &str| fs::read_to_string(session_dir.join(relative)).ok();
+        let mut extension_ids: Vec<String> = self.list_session_extensions(session)?
+            .into_iter()
+            .filter(|extension| extension.present)
+            .map(|extension| extension.id)
+            .collect();
+        // The same extension can be installed in several versions or for several platforms
+        extension_ids.sort();
+        extension_ids.dedup();
+
+        let template = SessionTemplate {
+            name: template_name.to_string(),
+            description: Some(format!("Created from session '{}'", session)),
+            name_pattern: Some(format!("{}-{{n}}", template_name)),
+            projects: session_info.projects.clone(),
+            settings: read_optional(SETTINGS_FILE),
+            keybindings: read_optional(KEYBINDINGS_FILE),
+            extensions: extension_ids,
+            launch_args: session_info.launch_args.clone(),
+            env: session_info.env.clone(),
+            electron_app: session_info.electron_app.clone(),
+            created: Local::now(),
+        };
+
+        self.save_template(&template, overwrite)?;
+        Ok(template)
+    }
+
+    pub fn delete_template(&self, name: &str) -> Result<String, Box<dyn Error>> {
+        let path = self.template_path(name)?;
+        if !path.exists() {
+            return Err(Box::new(CursorManagerError::TemplateNotFound { name: name.to_string() }));
+        }
+        fs::remove_file(&path)?;
+        Ok(format!("Template '{}' deleted", name))
+    }
+
+    // Write the template's files into a freshly created profile and copy its launch settings onto the session.
+    // Extensions found in the VSIX cache are installed, newest version first; all of them are also
+    // recommended through the workspace file, which is the only way to get the ones that are not cached
+    pub fn apply_template(&self, template: &SessionTemplate, session_dir: &Path, session_info: &mut SessionInfo) -> Result<(), Box<dyn Error>> {
+        for (relative, content) in [(SETTINGS_FILE, &template.settings), (KEYBINDINGS_FILE, &template.keybindings)] {
+            if let Some(content) = content {
+                let target = session_dir.join(relative);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&target, content)?;
+            }
+        }
+
+        let cached = self.list_vsix_cache()?;
+        for id in &template.extensions {
+            let id = normalize_extension_id(id);
+            // The cache is sorted by id and then version
+            if let Some(package) = cached.iter().rev().find(|package| package.id == id) {
+                install_vsix_into(&session_dir.join(EXTENSIONS_DIR), package)?;
+            }
+        }
+
+        session_info.projects = template.projects.clone();
+        session_info.recommended_extensions = template.extensions.clone();
+        session_info.launch_args = template.launch_args.clone();
+        session_info.env = template.env.clone();
+        session_info.template = Some(template.name.clone());
+        if session_info.electron_app.is_none() {
+            session_info.electron_app = template.electron_app.clone();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::Path;
+    use chrono::Local;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+    use super::SessionTemplate;
+    use crate::profile::{EXTENSIONS_DIR, SETTINGS_FILE};
+    use crate::test_support::{manager, session};
+    use crate::{CursorManager, CursorManagerError};
+
+    fn template(name: &str, name_pattern: Option<&str>) -> SessionTemplate {
+        SessionTemplate {
+            name: name.to_string(),
+            description: None,
+            name_pattern: name_pattern.map(|pattern| pattern.to_string()),
+            projects: Vec::new(),
+            settings: None,
+            keybindings: None,
+            extensions: Vec::new(),
+            launch_args: Vec::new(),
+            env: Default::default(),
+            electron_app: None,
+            created: Local::now(),
+        }
+    }
+
+    fn cache_vsix(manager: &CursorManager, publisher: &str, name: &str, version: &str) {
+        let cache = Path::new(&manager.config.vsix_cache);
+        fs::create_dir_all(cache).unwrap();
+        let mut zip = ZipWriter::new(File::create(cache.join(format!("{}.{}-{}.vsix", publisher, name, version))).unwrap());
+        zip.start_file("extension/package.json", SimpleFileOptions::default()).unwrap();
+        write!(zip, r#"{{ "publisher": "{}", "name": "{}", "version": "{}" }}"#, publisher, name, version).unwrap();
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn name_patterns_fill_in_the_template_date_and_first_free_number() {
+        let (_dir, manager) = manager();
+        assert_eq!(template("web", None).expand_name_pattern(&manager), None);
+        assert_eq!(template("web", Some("{template}-dev")).expand_name_pattern(&manager).unwrap(), "web-dev");
+        let dated = template("web", Some("{template}-{date}")).expand_name_pattern(&manager).unwrap();
+        assert_eq!(dated, format!("web-{}", Local::now().format("%Y%m%d")));
+
+        let numbered = template("web", Some("{template}-{n}"));
+        assert_eq!(numbered.expand_name_pattern(&manager).unwrap(), "web-1");
+        session(&manager, "web-1", &[]);
+        session(&manager, "web-2", &[]);
+        assert_eq!(numbered.expand_name_pattern(&manager).unwrap(), "web-3");
+    }
+
+    #[test]
+    fn a_session_created_from_a_template_gets_its_files_settings_and_cached_extensions() {
+        let (_dir, manager) = manager();
+        cache_vsix(&manager, "acme", "lint", "1.0.0");
+        cache_vsix(&manager, "acme", "lint", "1.2.0");
+        let template = SessionTemplate {
+            settings: Some("{\n    // from the template\n    \"editor.fontSize\": 13\n}".to_string()),
+            extensions: vec!["Acme.Lint".to_string(), "other.missing".to_string()],
+            launch_args: vec!["--disable-gpu".to_string()],
+            ..template("web", Some("{template}-{n}"))
+        };
+        manager.save_template(&template, false).unwrap();
+
+        manager.create_session("", None, Some("web")).unwrap();
+        let info = manager.read_session_info("web-1").unwrap();
+        assert_eq!(info.template.as_deref(), Some("web"));
+        assert_eq!(info.launch_args, vec!["--disable-gpu"]);
+        assert_eq!(info.recommended_extensions, vec!["Acme.Lint", "other.missing"]);
+        let session_dir = manager.session_dir("web-1");
+        assert!(fs::read_to_string(session_dir.join(SETTINGS_FILE)).unwrap().contains("// from the template"));
+        assert!(session_dir.join(EXTENSIONS_DIR).join("acme.lint-1.2.0").is_dir());
+        assert!(!session_dir.join(EXTENSIONS_DIR).join("acme.lint-1.0.0").exists());
+    }
+
+    #[test]
+    fn saving_over_an_existing_template_needs_overwrite() {
+        let (_dir, manager) = manager();
+        manager.save_template(&template("web", None), false).unwrap();
+        let error = manager.save_template(&template("web", Some("{n}")), false).unwrap_err();
+        assert!(matches!(error.downcast_ref::<CursorManagerError>(), Some(CursorManagerError::TemplateExists { .. })));
+        assert_eq!(manager.load_template("web").unwrap().name_pattern, None);
+
+        manager.save_template(&template("web", Some("{n}")), true).unwrap();
+        assert_eq!(manager.load_template("web").unwrap().name_pattern.as_deref(), Some("{n}"));
+        // No temporary files are left next to the templates
+        assert_eq!(fs::read_dir(manager.templates_dir()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,31 @@
+// Fixtures shared by the unit tests
+use std::fs;
+use std::path::PathBuf;
+use crate::{Config, CursorManager, SessionInfo};
+
+// A manager whose profile, archive, workspace and VSIX directories all live in a fresh temporary
+// directory, removed when the returned TempDir is dropped
+pub(crate) fn manager() -> (tempfile::TempDir, CursorManager) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+    let config = Config {
+        profile_base: path("profiles"),
+        archive_base: path("archives"),
+        workspace_base: path("projects"),
+        vsix_cache: path("vsix"),
+        ..Config::default()
+    };
+    (dir, CursorManager::new(Some(config)))
+}
+
+// Create a session with a session.json and the given files, paths relative to the profile root
+pub(crate) fn session(manager: &CursorManager, name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = manager.session_dir(name);
+    manager.write_session_info(&SessionInfo::new(name, &dir, None)).unwrap();
+    for (relative, content) in files {
+        let path = dir.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    dir
+}
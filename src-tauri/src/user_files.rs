@@ -0,0 +1,259 @@
+use std::error::Error;
+use std::fs;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use crate::jsonc::{self, JsoncError};
+use crate::profile::{write_file_atomic, KEYBINDINGS_FILE, SETTINGS_FILE};
+use crate::CursorManager;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UserFileKind {
+    Settings,
+    Keybindings,
+}
+
+impl UserFileKind {
+    pub fn relative_path(&self) -> &'static str {
+        match self {
+            UserFileKind::Settings => SETTINGS_FILE,
+            UserFileKind::Keybindings => KEYBINDINGS_FILE,
+        }
+    }
+
+    fn empty_document(&self) -> Value {
+        match self {
+            UserFileKind::Settings => Value::Object(Map::new()),
+            UserFileKind::Keybindings => Value::Array(Vec::new()),
+        }
+    }
+
+    // Parse and check the top-level shape: settings are an object, keybindings an array
+    fn parse(&self, content: &str) -> Result<Value, JsoncError> {
+        let parsed = jsonc::parse_or(content, self.empty_document())?;
+        let (shape_ok, expected) = match self {
+            UserFileKind::Settings => (parsed.is_object(), "object"),
+            UserFileKind::Keybindings => (parsed.is_array(), "array"),
+        };
+        if !shape_ok {
+            return Err(JsoncError {
+                message: format!("{} must contain a JSON {}", self.relative_path(), expected),
+            });
+        }
+        Ok(parsed)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserFile {
+    pub kind: UserFileKind,
+    pub path: String,
+    pub exists: bool,
+    // Raw text including comments
+    pub content: String,
+    pub parsed: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum UserFilePatch {
+    // Top-level settings keys to set; a null value removes the key
+    Settings { values: Map<String, Value> },
+    // Keybinding entries are matched on their "key" and "command" fields
+    Keybindings {
+        #[serde(default)]
+        upsert: Vec<Value>,
+        #[serde(default)]
+        remove: Vec<Value>,
+    },
+}
+
+impl UserFilePatch {
+    pub fn kind(&self) -> UserFileKind {
+        match self {
+            UserFilePatch::Settings { .. } => UserFileKind::Settings,
+            UserFilePatch::Keybindings { .. } => UserFileKind::Keybindings,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BulkPatchResult {
+    pub session: String,
+    pub success: bool,
+    pub message: String,
+}
+
+fn same_binding(a: &Value, b: &Value) -> bool {
+    a.get("key") == b.get("key") && a.get("command") == b.get("command")
+}
+
+fn patch_keybindings(text: &str, upsert: &[Value], remove: &[Value]) -> Result<String, JsoncError> {
+    for entry in upsert.iter().chain(remove.iter()) {
+        if entry.get("key").and_then(|k| k.as_str()).is_none() || entry.get("command").and_then(|c| c.as_str()).is_none() {
+            return Err(JsoncError { message: format!("keybinding needs string \"key\" and \"command\" fields: {}", entry) });
+        }
+    }
+
+    let mut text = if jsonc::strip(text).trim().is_empty() { "[]\n".to_string() } else { text.to_string() };
+
+    let find = |text: &str, wanted: &Value| -> Result<(jsonc::Container, Vec<usize>), JsoncError> {
+        let container = jsonc::top_level(text)?;
+        let mut matches = Vec::new();
+        for (index, item) in container.items.iter().enumerate() {
+            if same_binding(&jsonc::item_value(text, item)?, wanted) {
+                matches.push(index);
+            }
+        }
+        Ok((container, matches))
+    };
+
+    for entry in remove {
+        loop {
+            let (container, matches) = find(&text, entry)?;
+            match matches.last() {
+                Some(index) => text = jsonc::remove_item(&text, &container, *index),
+                None => break,
+            }
+        }
+    }
+    for entry in upsert {
+        // Duplicates of the binding go, so the updated entry is the only one left
+        let (mut container, mut matches) = find(&text, entry)?;
+        while matches.len() > 1 {
+            text = jsonc::remove_item(&text, &container, matches[matches.len() - 1]);
+            (container, matches) = find(&text, entry)?;
+        }
+        text = match matches.first() {
+            Some(index) => jsonc::replace_item_value(&text, &container.items[*index], entry),
+            None => jsonc::append_item(&text, &container, None, entry),
+        };
+    }
+
+    jsonc::parse_or(&text, Value::Null)?;
+    Ok(text)
+}
+
+impl CursorManager {
+    pub fn read_user_file(&self, session: &str, kind: UserFileKind) -> Result<UserFile, Box<dyn Error>> {
+        // Fails with SessionNotFound for unknown sessions
+        self.read_session_info(session)?;
+
+        let path = self.session_dir(session).join(kind.relative_path());
+        let exists = path.exists();
+        let content = if exists { fs::read_to_string(&path)? } else { String::new() };
+        let parsed = kind.parse(&content)?;
+
+        Ok(UserFile {
+            kind,
+            path: path.to_string_lossy().to_string(),
+            exists,
+            content,
+            parsed,
+        })
+    }
+
+    // Replace the whole file. The content must parse, so a typo cannot break the editor's settings
+    pub fn write_user_file(&self, session: &str, kind: UserFileKind, content: &str) -> Result<UserFile, Box<dyn Error>> {
+        self.read_session_info(session)?;
+        kind.parse(content)?;
+
+        let path = self.session_dir(session).join(kind.relative_path());
+        write_file_atomic(&path, content.as_bytes())?;
+        self.read_user_file(session, kind)
+    }
+
+    pub fn patch_user_file(&self, session: &str, patch: &UserFilePatch) -> Result<UserFile, Box<dyn Error>> {
+        let current = self.read_user_file(session, patch.kind())?;
+
+        let patched = match patch {
+            UserFilePatch::Settings { values } => jsonc::patch_object(&current.content, values)?,
+            UserFilePatch::Keybindings { upsert, remove } => patch_keybindings(&current.content, upsert, remove)?,
+        };
+
+        if patched != current.content {
+            write_file_atomic(std::path::Path::new(&current.path), patched.as_bytes())?;
+        }
+        self.read_user_file(session, patch.kind())
+    }
+
+    // Apply one patch to several sessions (all of them when none are named); failures do not stop the rest
+    pub fn patch_user_file_bulk(&self, sessions: Option<Vec<String>>, patch: &UserFilePatch) -> Result<Vec<BulkPatchResult>, Box<dyn Error>> {
+        let sessions = match sessions {
+            Some(sessions) => sessions,
+            None => self.list_sessions()?.into_iter().map(|s| s.name).collect(),
+        };
+
+        Ok(sessions
+            .into_iter()
+            .map(|session| match self.patch_user_file(&session, patch) {
+                Ok(_) => BulkPatchResult {
+                    message: format!("{} updated", patch.kind().relative_path()),
+                    session,
+                    success: true,
+                },
+                Err(e) => BulkPatchResult {
+                    message: e.to_string(),
+                    session,
+                    success: false,
+                },
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use super::patch_keybindings;
+
+    const KEYBINDINGS: &str = "// Place your key bindings in this file\n[\n    { \"key\": \"ctrl+k\", \"command\": \"editor.fold\" }, // fold\n    { \"key\": \"ctrl+j\", \"command\": \"editor.unfold\" }\n]\n";
+
+    fn parsed(text: &str) -> Value {
+        crate::jsonc::parse_or(text, Value::Null).unwrap()
+    }
+
+    #[test]
+    fn upsert_replaces_a_matching_binding_and_appends_new_ones() {
+        let upsert = [
+            json!({ "key": "ctrl+k", "command": "editor.fold", "when": "editorFocus" }),
+            json!({ "key": "ctrl+l", "command": "editor.action.selectAll" }),
+        ];
+        let patched = patch_keybindings(KEYBINDINGS, &upsert, &[]).unwrap();
+        assert!(patched.starts_with("// Place your key bindings in this file\n"));
+        assert!(patched.contains("// fold"));
+        assert_eq!(parsed(&patched), json!([
+            { "key": "ctrl+k", "command": "editor.fold", "when": "editorFocus" },
+            { "key": "ctrl+j", "command": "editor.unfold" },
+            { "key": "ctrl+l", "command": "editor.action.selectAll" },
+        ]));
+    }
+
+    #[test]
+    fn remove_drops_the_binding_and_keeps_other_comments() {
+        let patched = patch_keybindings(KEYBINDINGS, &[], &[json!({ "key": "ctrl+j", "command": "editor.unfold" })]).unwrap();
+        assert!(patched.contains("// fold"));
+        assert_eq!(parsed(&patched), json!([{ "key": "ctrl+k", "command": "editor.fold" }]));
+        // Removing something that is not there changes nothing
+        assert_eq!(patch_keybindings(KEYBINDINGS, &[], &[json!({ "key": "f1", "command": "none" })]).unwrap(), KEYBINDINGS);
+    }
+
+    #[test]
+    fn duplicate_bindings_are_all_removed_or_collapsed_into_one() {
+        let text = "[\n    { \"key\": \"f5\", \"command\": \"run\" },\n    { \"key\": \"f6\", \"command\": \"debug\" },\n    { \"key\": \"f5\", \"command\": \"run\", \"when\": \"old\" }\n]";
+        let binding = json!({ "key": "f5", "command": "run" });
+
+        let removed = patch_keybindings(text, &[], std::slice::from_ref(&binding)).unwrap();
+        assert_eq!(parsed(&removed), json!([{ "key": "f6", "command": "debug" }]));
+
+        let updated = json!({ "key": "f5", "command": "run", "when": "new" });
+        let upserted = patch_keybindings(text, std::slice::from_ref(&updated), &[]).unwrap();
+        assert_eq!(parsed(&upserted), json!([updated, { "key": "f6", "command": "debug" }]));
+    }
+
+    #[test]
+    fn bindings_need_a_key_and_a_command() {
+        assert!(patch_keybindings("[]", &[json!({ "key": "f5" })], &[]).is_err());
+        assert!(patch_keybindings("[]", &[], &[json!({ "command": "run" })]).is_err());
+    }
+}
@@ -0,0 +1,435 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::extensions::{normalize_extension_id, register_extension, remove_extension, scan_extensions_dir, ExtensionInfo, PackageJson};
+use crate::profile::{copy_profile_entry, CopyStats, EXTENSIONS_DIR};
+use crate::CursorManager;
+
+// Everything an extension ships lives under this folder inside the .vsix zip
+pub(crate) const VSIX_EXTENSION_PREFIX: &str = "extension/";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VsixPackage {
+    pub file_name: String,
+    pub path: String,
+    pub id: String,
+    pub publisher: String,
+    pub name: String,
+    pub display_name: Option<String>,
+    pub version: String,
+    pub target_platform: Option<String>,
+    pub size: u64,
+}
+
+impl VsixPackage {
+    // Folder name the editor uses for an installed extension
+    pub fn folder_name(&self) -> String {
+        match &self.target_platform {
+            Some(platform) if platform != "universal" => format!("{}-{}-{}", self.id, self.version, platform),
+            _ => format!("{}-{}", self.id, self.version),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VsixInstallResult {
+    pub session: String,
+    pub package: String,
+    pub success: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtensionAction {
+    Install,
+    Upgrade,
+    Downgrade,
+    Remove,
+    Unchanged,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExtensionChange {
+    pub id: String,
+    pub action: ExtensionAction,
+    pub from_version: Option<String>,
+    pub to_version: Option<String>,
+    // "vsix:<file>" or "session:<name>", None when nothing needs to be copied
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExtensionSyncPlan {
+    pub source: String,
+    pub target: String,
+    pub dry_run: bool,
+    pub changes: Vec<ExtensionChange>,
+    pub errors: Vec<String>,
+}
+
+// Numeric-aware comparison of dotted versions such as 1.10.0 vs 1.9.3
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Vec<u64> {
+        v.split(['.', '-'])
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    parts(a).cmp(&parts(b)).then_with(|| a.cmp(b))
+}
+
+pub fn read_vsix(path: &Path) -> Result<VsixPackage, Box<dyn Error>> {
+    let size = fs::metadata(path)?.len();
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+
+    let package: PackageJson = {
+        let mut entry = archive.by_name("extension/package.json")?;
+        let mut data = String::new();
+        entry.read_to_string(&mut data)?;
+        serde_json::from_str(&data)?
+    };
+
+    // The manifest is XML; the only thing needed from it is the optional TargetPlatform attribute
+    let target_platform = archive.by_name("extension.vsixmanifest").ok().and_then(|mut entry| {
+        let mut data = String::new();
+        entry.read_to_string(&mut data).ok()?;
+        let start = data.find("TargetPlatform=\"")? + "TargetPlatform=\"".len();
+        let end = data[start..].find('"')? + start;
+        Some(data[start..end].to_string())
+    });
+
+    let invalid = || format!("{} is not a valid VSIX: package.json lacks publisher, name or version", path.to_string_lossy());
+    let publisher = package.publisher.ok_or_else(invalid)?;
+    let name = package.name.ok_or_else(invalid)?;
+    let version = package.version.ok_or_else(invalid)?;
+
+    Ok(VsixPackage {
+        file_name: path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+        path: path.to_string_lossy().to_string(),
+        id: normalize_extension_id(&format!("{}.{}", publisher, name)),
+        publisher,
+        name,
+        display_name: package.display_name,
+        version,
+        target_platform,
+        size,
+    })
+}
+
+// Unpack a VSIX into an extensions folder and register it in extensions.json
+pub fn install_vsix_into(extensions_dir: &Path, package: &VsixPackage) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(extensions_dir)?;
+    let folder = package.folder_name();
+    let staging = extensions_dir.join(format!(".{}.{}.installing", folder, uuid::Uuid::new_v4()));
+
+    let unpacked = (|| -> Result<(), Box<dyn Error>> {
+        let mut archive = zip::ZipArchive::new(File::open(&package.path)?)?;
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index)?;
+            // enclosed_name rejects absolute paths and names that climb out of the archive root
+            let relative = match entry.enclosed_name() {
+                Some(name) => name,
+                None => continue,
+            };
+            let relative = match relative.to_string_lossy().replace('\\', "/").strip_prefix(VSIX_EXTENSION_PREFIX) {
+                Some(rest) if !rest.is_empty() => PathBuf::from(rest),
+                _ => continue,
+            };
+            // enclosed_name still allows "extension/../x", which would land beside the extension folder
+            if !relative.components().all(|c| matches!(c, std::path::Component::Normal(_))) {
+                continue;
+            }
+            let target = staging.join(&relative);
+            if entry.is_dir() {
+                fs::create_dir_all(&target)?;
+                continue;
+            }
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut output = File::create(&target)?;
+            std::io::copy(&mut entry, &mut output)?;
+        }
+        Ok(())
+    })();
+    if let Err(e) = unpacked {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    // Only one version of an extension stays installed. The old folders are moved aside rather than
+    // deleted, so a failed install puts them back and leaves the extension as it was
+    let final_dir = extensions_dir.join(&folder);
+    let id = normalize_extension_id(&package.id);
+    let mut replaced: Vec<PathBuf> = scan_extensions_dir(extensions_dir, &[])
+        .into_iter()
+        .filter(|extension| extension.id == id && extension.present)
+        .map(|extension| PathBuf::from(extension.path))
+        .collect();
+    if final_dir.exists() && !replaced.contains(&final_dir) {
+        replaced.push(final_dir.clone());
+    }
+
+    let mut moved: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut placed = false;
+    let installed = (|| -> Result<(), Box<dyn Error>> {
+        for original in &replaced {
+            let name = original.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let aside = extensions_dir.join(format!(".{}.{}.replaced", name, uuid::Uuid::new_v4()));
+            fs::rename(original, &aside)?;
+            moved.push((original.clone(), aside));
+        }
+        fs::rename(&staging, &final_dir)?;
+        placed = true;
+        register_extension(extensions_dir, &package.id, &package.version, &folder)
+    })();
+
+    if let Err(e) = installed {
+        let _ = fs::remove_dir_all(if placed { &final_dir } else { &staging });
+        for (original, aside) in moved.iter().rev() {
+            if let Err(restore) = fs::rename(aside, original) {
+                tracing::error!("Could not restore {} from {}: {}", original.to_string_lossy(), aside.to_string_lossy(), restore);
+            }
+        }
+        return Err(e);
+    }
+    for (_, aside) in moved {
+        if let Err(e) = fs::remove_dir_all(&aside) {
+            tracing::warn!("Could not remove replaced extension {}: {}", aside.to_string_lossy(), e);
+        }
+    }
+    Ok(())
+}
+
+impl CursorManager {
+    pub fn list_vsix_cache(&self) -> Result<Vec<VsixPackage>, Box<dyn Error>> {
+        let cache_dir = Path::new(&self.config.vsix_cache);
+        let mut packages = Vec::new();
+
+        if !cache_dir.exists() {
+            return Ok(packages);
+        }
+
+        for entry in fs::read_dir(cache_dir)? {
+            let path = entry?.path();
+            if path.extension().map(|e| e.eq_ignore_ascii_case("vsix")).unwrap_or(false) {
+                match read_vsix(&path) {
+                    Ok(package) => packages.push(package),
+                    Err(e) => tracing::warn!("Skipping unreadable VSIX {}: {}", path.to_string_lossy(), e),
+                }
+            }
+        }
+
+        packages.sort_by(|a, b| a.id.cmp(&b.id).then_with(|| compare_versions(&a.version, &b.version)));
+        Ok(packages)
+    }
+
+    // Copy a .vsix into the cache under its canonical <id>-<version>.vsix name
+    pub fn add_to_vsix_cache(&self, path: &str) -> Result<VsixPackage, Box<dyn Error>> {
+        let package = read_vsix(Path::new(path))?;
+        let cache_dir = Path::new(&self.config.vsix_cache);
+        fs::create_dir_all(cache_dir)?;
+
+        let target = cache_dir.join(format!("{}.vsix", package.folder_name()));
+        fs::copy(path, &target)?;
+        read_vsix(&target)
+    }
+
+    // A package may be named by file name inside the cache or by absolute path
+    fn resolve_vsix(&self, package: &str) -> Result<VsixPackage, Box<dyn Error>> {
+        let direct = Path::new(package);
+        let path = if direct.is_absolute() {
+            direct.to_path_buf()
+        } else {
+            Path::new(&self.config.vsix_cache).join(package)
+        };
+        if !path.is_file() {
+            return Err(format!("VSIX package '{}' not found", package).into());
+        }
+        read_vsix(&path)
+    }
+
+    pub fn install_vsix(&self, sessions: &[String], packages: &[String]) -> Result<Vec<VsixInstallResult>, Box<dyn Error>> {
+        let resolved: Vec<VsixPackage> = packages.iter().map(|p| self.resolve_vsix(p)).collect::<Result<_, _>>()?;
+        let mut results = Vec::new();
+
+        for session in sessions {
+            let ready = self.read_session_info(session).and_then(|_| self.ensure_not_running(session));
+            for package in &resolved {
+                let outcome = match &ready {
+                    Ok(_) => install_vsix_into(&self.session_dir(session).join(EXTENSIONS_DIR), package),
+                    Err(e) => Err(e.to_string().into()),
+                };
+                results.push(VsixInstallResult {
+                    session: session.clone(),
+                    package: package.file_name.clone(),
+                    success: outcome.is_ok(),
+                    message: match outcome {
+                        Ok(_) => format!("Installed {} {}", package.id, package.version),
+                        Err(e) => e.to_string(),
+                    },
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    // Make `target`'s extensions match `source`. With dry_run only the plan is returned
+    pub fn sync_extensions(&self, source: &str, target: &str, dry_run: bool) -> Result<ExtensionSyncPlan, Box<dyn Error>> {
+        let wanted: Vec<ExtensionInfo> = self.list_session_extensions(source)?.into_iter().filter(|e| e.present).collect();
+        let installed: HashMap<String, ExtensionInfo> = self.list_session_extensions(target)?
+            .into_iter()
+            .filter(|e| e.present)
+            .map(|e| (e.id.clone(), e))
+            .collect();
+        let cache: HashMap<(String, String), VsixPackage> = self.list_vsix_cache()?
+            .into_iter()
+            .map(|p| ((p.id.clone(), p.version.clone()), p))
+            .collect();
+
+        let mut changes = Vec::new();
+        for extension in &wanted {
+            let current = installed.get(&extension.id);
+            let action = match current {
+                None => ExtensionAction::Install,
+                Some(existing) => match compare_versions(&existing.version, &extension.version) {
+                    Ordering::Less => ExtensionAction::Upgrade,
+                    Ordering::Greater => ExtensionAction::Downgrade,
+                    Ordering::Equal => ExtensionAction::Unchanged,
+                },
+            };
+            // Prefer the cached VSIX, fall back to copying the folder out of the source profile
+            let change_source = match action {
+                ExtensionAction::Unchanged => None,
+                _ => Some(match cache.get(&(extension.id.clone(), extension.version.clone())) {
+                    Some(package) => format!("vsix:{}", package.file_name),
+                    None => format!("session:{}", source),
+                }),
+            };
+            changes.push(ExtensionChange {
+                id: extension.id.clone(),
+                action,
+                from_version: current.map(|e| e.version.clone()),
+                to_version: Some(extension.version.clone()),
+                source: change_source,
+            });
+        }
+        for (id, existing) in &installed {
+            if !wanted.iter().any(|e| &e.id == id) {
+                changes.push(ExtensionChange {
+                    id: id.clone(),
+                    action: ExtensionAction::Remove,
+                    from_version: Some(existing.version.clone()),
+                    to_version: None,
+                    source: None,
+                });
+            }
+        }
+        changes.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut plan = ExtensionSyncPlan {
+            source: source.to_string(),
+            target: target.to_string(),
+            dry_run,
+            changes,
+            errors: Vec::new(),
+        };
+        if dry_run {
+            return Ok(plan);
+        }
+
+        self.ensure_not_running(target)?;
+        let target_extensions = self.session_dir(target).join(EXTENSIONS_DIR);
+        for change in &plan.changes {
+            let outcome: Result<(), Box<dyn Error>> = match (change.action, &change.source) {
+                (ExtensionAction::Unchanged, _) => Ok(()),
+                (ExtensionAction::Remove, _) => remove_extension(&target_extensions, &change.id),
+                (_, Some(source_ref)) if source_ref.starts_with("vsix:") => {
+                    let version = change.to_version.clone().unwrap_or_default();
+                    match cache.get(&(change.id.clone(), version)) {
+                        Some(package) => install_vsix_into(&target_extensions, package),
+                        None => Err(format!("{} vanished from the VSIX cache", change.id).into()),
+                    }
+                }
+                _ => self.copy_extension_folder(source, &target_extensions, &change.id),
+            };
+            if let Err(e) = outcome {
+                plan.errors.push(format!("{}: {}", change.id, e));
+            }
+        }
+        Ok(plan)
+    }
+
+    fn copy_extension_folder(&self, source: &str, target_extensions: &Path, id: &str) -> Result<(), Box<dyn Error>> {
+        let source_extensions = self.session_dir(source).join(EXTENSIONS_DIR);
+        let extension = scan_extensions_dir(&source_extensions, &[])
+            .into_iter()
+            .find(|e| e.id == id && e.present)
+            .ok_or_else(|| format!("{} is no longer installed in '{}'", id, source))?;
+        let folder = Path::new(&extension.path).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+
+        remove_extension(target_extensions, id)?;
+        copy_profile_entry(&source_extensions, target_extensions, Path::new(&folder), &[], &mut CopyStats::default())?;
+        register_extension(target_extensions, id, &extension.version, &folder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn vsix(dir: &Path, version: &str) -> VsixPackage {
+        let path = dir.join(format!("acme.tool-{}.vsix", version));
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        zip.start_file("extension/package.json", SimpleFileOptions::default()).unwrap();
+        write!(zip, r#"{{"publisher":"acme","name":"tool","version":"{}"}}"#, version).unwrap();
+        zip.finish().unwrap();
+        read_vsix(&path).unwrap()
+    }
+
+    fn folders(extensions_dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(extensions_dir)
+            .unwrap()
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn installing_a_new_version_replaces_the_old_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let extensions_dir = dir.path().join("extensions");
+        install_vsix_into(&extensions_dir, &vsix(dir.path(), "1.0.0")).unwrap();
+        install_vsix_into(&extensions_dir, &vsix(dir.path(), "1.1.0")).unwrap();
+
+        assert_eq!(folders(&extensions_dir), vec!["acme.tool-1.1.0"]);
+        let installed = scan_extensions_dir(&extensions_dir, &[]);
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].version, "1.1.0");
+        assert!(installed[0].registered);
+    }
+
+    #[test]
+    fn a_failed_install_keeps_the_old_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let extensions_dir = dir.path().join("extensions");
+        install_vsix_into(&extensions_dir, &vsix(dir.path(), "1.0.0")).unwrap();
+        // extensions.json cannot be replaced by a file, so registering fails after the swap
+        let manifest = extensions_dir.join("extensions.json");
+        fs::remove_file(&manifest).unwrap();
+        fs::create_dir(&manifest).unwrap();
+
+        assert!(install_vsix_into(&extensions_dir, &vsix(dir.path(), "2.0.0")).is_err());
+        assert_eq!(folders(&extensions_dir), vec!["acme.tool-1.0.0", "extensions.json"]);
+        assert!(extensions_dir.join("acme.tool-1.0.0/package.json").exists());
+    }
+}
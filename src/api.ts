@@ -1,4 +1,5 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
 
 export interface Config {
   cursor_app: string;
@@ -9,10 +10,282 @@ export interface Config {
 }
 
 export interface SessionInfo {
+  id: string;
   name: string;
   path: string;
   created: string;
+  modified: string | null;
+  last_launched: string | null;
   electron_app: ElectronApp;
+  projects: string[];
+  tags: string[];
+  notes: string | null;
+  color: string | null;
+  icon: string | null;
+  pinned: boolean;
+  previous_names: string[];
+  template: string | null;
+  recommended_extensions: string[];
+  disabled_extensions: string[];
+  launch_args: string[];
+  env: Record<string, string>;
+  disk_usage: CachedDiskUsage | null;
+  git_status?: ProjectGitStatus[];
+}
+
+export type IdleAction = 'notify' | 'stop' | 'stopAndArchive';
+
+export interface SessionActivity {
+  session: string;
+  pid: number | null;
+  launched_at: string;
+  last_activity: string;
+  last_signal: string;
+  idle_handled: boolean;
+}
+
+export interface IdleEvent {
+  session: string;
+  action: IdleAction;
+  idle_minutes: number;
+  timestamp: string;
+  success: boolean;
+  message: string;
+}
+
+export interface CachedDiskUsage {
+  total_bytes: number;
+  scanned_at: string;
+}
+
+export type UsageCategory =
+  | 'cache'
+  | 'codeCache'
+  | 'gpuCache'
+  | 'cachedData'
+  | 'logs'
+  | 'workspaceStorage'
+  | 'extensions'
+  | 'globalStorage'
+  | 'other';
+
+export interface CategoryUsage {
+  category: UsageCategory;
+  path: string | null;
+  bytes: number;
+  files: number;
+}
+
+export interface UsageEntry {
+  path: string;
+  category: UsageCategory;
+  bytes: number;
+}
+
+export interface DiskUsageReport {
+  session: string;
+  total_bytes: number;
+  total_files: number;
+  categories: CategoryUsage[];
+  largest: UsageEntry[];
+  scanned_at: string;
+}
+
+export interface CleanedEntry {
+  path: string;
+  category: UsageCategory;
+  bytes: number;
+}
+
+export interface CleanReport {
+  session: string;
+  dry_run: boolean;
+  freed_bytes: number;
+  removed: CleanedEntry[];
+  errors: string[];
+}
+
+export interface BulkCleanResult {
+  session: string;
+  report: CleanReport | null;
+  error: string | null;
+}
+
+export interface DiskUsageProgress {
+  session: string;
+  files: number;
+  bytes: number;
+  current_path: string;
+  done: boolean;
+}
+
+export interface SessionMetadataUpdate {
+  tags?: string[];
+  notes?: string;
+  color?: string;
+  icon?: string;
+  pinned?: boolean;
+}
+
+export interface SessionTemplate {
+  name: string;
+  description?: string | null;
+  name_pattern?: string | null;
+  projects: string[];
+  settings?: string | null;
+  keybindings?: string | null;
+  extensions: string[];
+  launch_args: string[];
+  env: Record<string, string>;
+  electron_app?: ElectronApp | null;
+  created?: string;
+}
+
+export type UserFileKind = 'settings' | 'keybindings';
+
+export interface UserFile {
+  kind: UserFileKind;
+  path: string;
+  exists: boolean;
+  content: string;
+  parsed: unknown;
+}
+
+export type UserFilePatch =
+  | { kind: 'settings'; values: Record<string, unknown> }
+  | { kind: 'keybindings'; upsert?: Record<string, unknown>[]; remove?: Record<string, unknown>[] };
+
+export interface BulkPatchResult {
+  session: string;
+  success: boolean;
+  message: string;
+}
+
+export interface ExtensionInfo {
+  id: string;
+  publisher: string;
+  name: string;
+  display_name: string | null;
+  version: string;
+  // Launched with --disable-extension; being disabled from inside the editor is not reflected here
+  disabled_by_manager: boolean;
+  size: number;
+  path: string;
+  registered: boolean;
+  present: boolean;
+}
+
+export interface ExtensionUsage {
+  session: string;
+  version: string;
+  disabled_by_manager: boolean;
+}
+
+export interface ExtensionSummary {
+  id: string;
+  display_name: string | null;
+  versions: Record<string, string[]>;
+}
+
+export interface VsixPackage {
+  file_name: string;
+  path: string;
+  id: string;
+  publisher: string;
+  name: string;
+  display_name: string | null;
+  version: string;
+  target_platform: string | null;
+  size: number;
+}
+
+export interface VsixInstallResult {
+  session: string;
+  package: string;
+  success: boolean;
+  message: string;
+}
+
+export type ExtensionAction = 'install' | 'upgrade' | 'downgrade' | 'remove' | 'unchanged';
+
+export interface ExtensionChange {
+  id: string;
+  action: ExtensionAction;
+  from_version: string | null;
+  to_version: string | null;
+  source: string | null;
+}
+
+export interface ExtensionSyncPlan {
+  source: string;
+  target: string;
+  dry_run: boolean;
+  changes: ExtensionChange[];
+  errors: string[];
+}
+
+export interface CloneOptions {
+  include?: string[];
+  exclude?: string[];
+  copy_metadata?: boolean;
+}
+
+export interface ExportOptions {
+  include_vsix?: boolean;
+  include_workspace?: boolean;
+  include_secrets?: boolean;
+}
+
+export interface BundleSessionInfo {
+  projects: string[];
+  tags: string[];
+  notes: string | null;
+  color: string | null;
+  icon: string | null;
+  recommended_extensions: string[];
+  disabled_extensions: string[];
+  launch_args: string[];
+  env: Record<string, string>;
+}
+
+export interface BundledExtension {
+  id: string;
+  version: string;
+  vsix: string | null;
+}
+
+export interface BundleManifest {
+  format_version: number;
+  min_reader_version: number;
+  app_version: string;
+  created: string;
+  session: string;
+  session_info: BundleSessionInfo;
+  files: string[];
+  extensions: BundledExtension[];
+  workspace: boolean;
+  secrets_excluded: boolean;
+  redacted: string[];
+}
+
+export interface CommitSummary {
+  hash: string;
+  summary: string;
+  author: string;
+  date: string;
+}
+
+export interface ProjectGitStatus {
+  path: string;
+  is_repo: boolean;
+  branch: string | null;
+  upstream: string | null;
+  ahead: number;
+  behind: number;
+  dirty_files: number;
+  stash_count: number;
+  last_commit: CommitSummary | null;
+  error: string | null;
 }
 
 export interface ArchiveInfo {
@@ -20,6 +293,209 @@ export interface ArchiveInfo {
   path: string;
   created: string;
   original_session: string;
+  pinned: boolean;
+  format: ArchiveFormat;
+  size: number;
+  manifest: ArchiveManifest | null;
+  snapshot: SnapshotStats | null;
+}
+
+export interface SnapshotStats {
+  chunk_count: number;
+  logical_bytes: number;
+  unique_bytes: number;
+  shared_bytes: number;
+}
+
+export interface GcReport {
+  dry_run: boolean;
+  snapshots: number;
+  chunks_kept: number;
+  removed_chunks: number;
+  freed_bytes: number;
+  corrected_counts: number;
+  errors: string[];
+}
+
+export interface ArchiveManifest {
+  manifest_version: number;
+  archive: string;
+  session: string;
+  session_info: SessionInfo | null;
+  created: string;
+  app_version: string;
+  file_count: number;
+  total_bytes: number;
+  archive_bytes: number;
+  compression: string;
+  compression_level: number;
+  content_hash: string;
+  files: ArchivedFile[];
+  encryption: EncryptionKind | null;
+}
+
+export interface ArchivedFile {
+  path: string;
+  size: number;
+  sha256: string;
+}
+
+export interface ModifiedFile {
+  path: string;
+  expected_size: number;
+  actual_size: number;
+}
+
+export interface ArchiveVerification {
+  archive: string;
+  verified_at: string;
+  ok: boolean;
+  content_hash_ok: boolean | null;
+  per_file: boolean;
+  checked_files: number;
+  missing: string[];
+  extra: string[];
+  modified: ModifiedFile[];
+  errors: string[];
+}
+
+export interface ArchiveContentEntry {
+  path: string;
+  size: number;
+  is_dir: boolean;
+  modified: string | null;
+}
+
+export interface ExtractResult {
+  destination: string;
+  extracted: string[];
+  skipped: string[];
+  not_found: string[];
+  bytes: number;
+}
+
+export type DiffSource = { archive: string } | { session: string };
+
+export type ChangeKind = 'added' | 'removed' | 'modified';
+
+export interface DiffFile {
+  path: string;
+  size: number;
+}
+
+export interface ChangedFile {
+  path: string;
+  old_size: number;
+  new_size: number;
+}
+
+export interface KeyChange {
+  key: string;
+  change: ChangeKind;
+  old: unknown;
+  new: unknown;
+}
+
+export interface StructuredDiff {
+  path: string;
+  changes: KeyChange[];
+  error: string | null;
+}
+
+export interface ArchiveDiff {
+  a: string;
+  b: string;
+  added: DiffFile[];
+  removed: DiffFile[];
+  modified: ChangedFile[];
+  unchanged: number;
+  structured: StructuredDiff[];
+}
+
+export type ArchiveFormat = 'directory' | 'tarZst' | 'snapshot' | 'encryptedTarZst';
+
+export type ArchiveKey = { passphrase: string } | { keyFile: string };
+
+export type EncryptionKind = 'passphrase' | 'keyFile';
+
+export type ArchiveMode = 'full' | 'snapshot';
+
+export type RestoreMode = 'new' | 'replace' | 'merge';
+
+export type MergePolicy = 'preferArchive' | 'preferLive' | 'newestWins';
+
+export type RestoreCategory = 'settings' | 'keybindings' | 'snippets' | 'extensions' | 'state';
+
+export interface RestoreOptions {
+  mode?: RestoreMode;
+  merge_policy?: MergePolicy;
+  categories?: RestoreCategory[];
+}
+
+export interface ArchiveOptions {
+  mode?: ArchiveMode;
+  compression_level?: number;
+  exclude?: string[];
+  encrypt?: boolean;
+  key?: ArchiveKey;
+}
+
+export type JobRequest =
+  | { kind: 'archive'; session: string; options?: ArchiveOptions }
+  | { kind: 'restore'; archive: string; new_session_name?: string; skip_verify?: boolean; key?: ArchiveKey; options?: RestoreOptions }
+  | { kind: 'clone'; source: string; new_name: string; options?: CloneOptions }
+  | { kind: 'clean'; sessions?: string[]; categories?: UsageCategory[]; dry_run?: boolean }
+  | { kind: 'verify'; archive?: string; key?: ArchiveKey }
+  | { kind: 'diskUsage'; session: string };
+
+export type JobKind = JobRequest['kind'];
+
+export type JobState = 'running' | 'cancelling' | 'completed' | 'failed' | 'cancelled';
+
+export interface JobProgress {
+  bytes: number;
+  files: number;
+  total_bytes: number | null;
+  total_files: number | null;
+  current_path: string | null;
+  eta_seconds: number | null;
+}
+
+export interface JobInfo {
+  id: string;
+  kind: JobKind;
+  target: string | null;
+  state: JobState;
+  started: string;
+  finished: string | null;
+  progress: JobProgress;
+  result: unknown;
+  error: string | null;
+}
+
+export interface RetentionPolicy {
+  keep_last?: number | null;
+  keep_daily?: number | null;
+  keep_weekly?: number | null;
+  keep_monthly?: number | null;
+  max_total_bytes?: number | null;
+  max_age_days?: number | null;
+}
+
+export interface RetentionDecision {
+  archive: string;
+  session: string;
+  created: string;
+  size: number;
+  reason: string;
+}
+
+export interface RetentionReport {
+  dry_run: boolean;
+  kept: RetentionDecision[];
+  deleted: RetentionDecision[];
+  freed_bytes: number;
+  errors: string[];
 }
 
 export interface CursorSession {
@@ -65,9 +541,14 @@ export async function updateConfig(config: Config): Promise<Config> {
   }
 }
 
-export async function listSessions(): Promise<SessionInfo[]> {
+// Emitted by update_config, e.g. when max_sessions drops below the number of existing sessions
+export function onConfigWarning(handler: (warning: string) => void): Promise<UnlistenFn> {
+  return listen<string>('config-warning', (event) => handler(event.payload));
+}
+
+export async function listSessions(includeGitStatus: boolean = false, includeDiskUsage: boolean = false): Promise<SessionInfo[]> {
   try {
-    return await invoke<SessionInfo[]>('list_sessions');
+    return await invoke<SessionInfo[]>('list_sessions', { includeGitStatus, includeDiskUsage });
   } catch (error) {
     console.error('Error listing sessions:', error);
     return [];
@@ -83,15 +564,159 @@ export async function listArchives(): Promise<ArchiveInfo[]> {
   }
 }
 
-export async function createSession(name: string): Promise<string> {
+export async function createSession(name: string, template?: string): Promise<string> {
   try {
-    return await invoke<string>('create_session', { name });
+    return await invoke<string>('create_session', { name, template });
   } catch (error) {
     console.error('Error creating session:', error);
     throw error;
   }
 }
 
+export async function listTemplates(): Promise<SessionTemplate[]> {
+  try {
+    return await invoke<SessionTemplate[]>('list_templates');
+  } catch (error) {
+    console.error('Error listing templates:', error);
+    return [];
+  }
+}
+
+export async function saveTemplate(template: SessionTemplate, overwrite = false): Promise<string> {
+  try {
+    return await invoke<string>('save_template', { template, overwrite });
+  } catch (error) {
+    console.error('Error saving template:', error);
+    throw error;
+  }
+}
+
+export async function createTemplateFromSession(session: string, templateName: string, overwrite = false): Promise<SessionTemplate> {
+  try {
+    return await invoke<SessionTemplate>('create_template_from_session', { session, templateName, overwrite });
+  } catch (error) {
+    console.error('Error creating template from session:', error);
+    throw error;
+  }
+}
+
+export async function deleteTemplate(name: string): Promise<string> {
+  try {
+    return await invoke<string>('delete_template', { name });
+  } catch (error) {
+    console.error('Error deleting template:', error);
+    throw error;
+  }
+}
+
+export async function readUserFile(session: string, kind: UserFileKind): Promise<UserFile> {
+  try {
+    return await invoke<UserFile>('read_user_file', { session, kind });
+  } catch (error) {
+    console.error(`Error reading ${kind}:`, error);
+    throw error;
+  }
+}
+
+export async function writeUserFile(session: string, kind: UserFileKind, content: string): Promise<UserFile> {
+  try {
+    return await invoke<UserFile>('write_user_file', { session, kind, content });
+  } catch (error) {
+    console.error(`Error writing ${kind}:`, error);
+    throw error;
+  }
+}
+
+export async function patchUserFile(session: string, patch: UserFilePatch): Promise<UserFile> {
+  try {
+    return await invoke<UserFile>('patch_user_file', { session, patch });
+  } catch (error) {
+    console.error(`Error patching ${patch.kind}:`, error);
+    throw error;
+  }
+}
+
+export async function patchUserFileBulk(sessions: string[] | null, patch: UserFilePatch): Promise<BulkPatchResult[]> {
+  try {
+    return await invoke<BulkPatchResult[]>('patch_user_file_bulk', { sessions, patch });
+  } catch (error) {
+    console.error(`Error patching ${patch.kind} across sessions:`, error);
+    throw error;
+  }
+}
+
+export async function listSessionExtensions(session: string): Promise<ExtensionInfo[]> {
+  try {
+    return await invoke<ExtensionInfo[]>('list_session_extensions', { session });
+  } catch (error) {
+    console.error('Error listing session extensions:', error);
+    throw error;
+  }
+}
+
+export async function findExtensionSessions(extensionId: string, version?: string): Promise<ExtensionUsage[]> {
+  try {
+    return await invoke<ExtensionUsage[]>('find_extension_sessions', { extensionId, version });
+  } catch (error) {
+    console.error('Error finding extension sessions:', error);
+    throw error;
+  }
+}
+
+export async function extensionOverview(): Promise<ExtensionSummary[]> {
+  try {
+    return await invoke<ExtensionSummary[]>('extension_overview');
+  } catch (error) {
+    console.error('Error building extension overview:', error);
+    return [];
+  }
+}
+
+export async function setExtensionEnabled(session: string, extensionId: string, enabled: boolean): Promise<string> {
+  try {
+    return await invoke<string>('set_extension_enabled', { session, extensionId, enabled });
+  } catch (error) {
+    console.error('Error updating extension state:', error);
+    throw error;
+  }
+}
+
+export async function listVsixCache(): Promise<VsixPackage[]> {
+  try {
+    return await invoke<VsixPackage[]>('list_vsix_cache');
+  } catch (error) {
+    console.error('Error listing VSIX cache:', error);
+    throw error;
+  }
+}
+
+export async function addToVsixCache(path: string): Promise<VsixPackage> {
+  try {
+    return await invoke<VsixPackage>('add_to_vsix_cache', { path });
+  } catch (error) {
+    console.error('Error adding VSIX to cache:', error);
+    throw error;
+  }
+}
+
+export async function installVsix(sessions: string[], packages: string[]): Promise<VsixInstallResult[]> {
+  try {
+    return await invoke<VsixInstallResult[]>('install_vsix', { sessions, packages });
+  } catch (error) {
+    console.error('Error installing VSIX:', error);
+    throw error;
+  }
+}
+
+export async function syncExtensions(source: string, target: string, dryRun: boolean = true): Promise<ExtensionSyncPlan> {
+  try {
+    return await invoke<ExtensionSyncPlan>('sync_extensions', { source, target, dryRun });
+  } catch (error) {
+    console.error('Error syncing extensions:', error);
+    throw error;
+  }
+}
+
 export async function launchSession(session: CursorSession, spoofMac: boolean = false, connectVpn: boolean = false): Promise<string> {
   console.log(`[API] launchSession called for: ${session.name}`, { spoofMac, connectVpn });
   try {
@@ -133,20 +758,23 @@ export async function spoofMac(interfaceName?: string): Promise<string> {
   }
 }
 
-export async function archiveSession(session: string): Promise<string> {
+export async function archiveSession(session: string, options?: ArchiveOptions): Promise<string> {
   try {
-    return await invoke<string>('archive_session', { session });
+    return await invoke<string>('archive_session', { session, options });
   } catch (error) {
     console.error('Error archiving session:', error);
     throw error;
   }
 }
 
-export async function restoreArchive(archive: string, newSessionName?: string): Promise<string> {
+export async function restoreArchive(archive: string, newSessionName?: string, skipVerify = false, key?: ArchiveKey, options?: RestoreOptions): Promise<string> {
   try {
     return await invoke<string>('restore_archive', { 
       archive, 
-      newSessionName: newSessionName 
+      newSessionName: newSessionName,
+      skipVerify,
+      key,
+      options
     });
   } catch (error) {
     console.error('Error restoring archive:', error);
@@ -154,6 +782,51 @@ export async function restoreArchive(archive: string, newSessionName?: string):
   }
 }
 
+export async function renameSession(oldName: string, newName: string): Promise<string> {
+  try {
+    return await invoke<string>('rename_session', { oldName, newName });
+  } catch (error) {
+    console.error('Error renaming session:', error);
+    throw error;
+  }
+}
+
+export async function cloneSession(source: string, newName: string, options?: CloneOptions): Promise<string> {
+  try {
+    return await invoke<string>('clone_session', { source, newName, options });
+  } catch (error) {
+    console.error('Error cloning session:', error);
+    throw error;
+  }
+}
+
+export async function exportSession(session: string, path: string, options?: ExportOptions): Promise<string> {
+  try {
+    return await invoke<string>('export_session', { session, path, options });
+  } catch (error) {
+    console.error('Error exporting session:', error);
+    throw error;
+  }
+}
+
+export async function readSessionBundle(path: string): Promise<BundleManifest> {
+  try {
+    return await invoke<BundleManifest>('read_session_bundle', { path });
+  } catch (error) {
+    console.error('Error reading session bundle:', error);
+    throw error;
+  }
+}
+
+export async function importSession(path: string, name?: string): Promise<string> {
+  try {
+    return await invoke<string>('import_session', { path, name });
+  } catch (error) {
+    console.error('Error importing session:', error);
+    throw error;
+  }
+}
+
 export async function deleteSession(session: string): Promise<string> {
   try {
     return await invoke<string>('delete_session', { session });
@@ -172,6 +845,185 @@ export async function deleteArchive(archive: string): Promise<string> {
   }
 }
 
+export async function setArchivePinned(archive: string, pinned: boolean): Promise<string> {
+  try {
+    return await invoke<string>('set_archive_pinned', { archive, pinned });
+  } catch (error) {
+    console.error('Error pinning archive:', error);
+    throw error;
+  }
+}
+
+export async function applyArchiveRetention(policy?: RetentionPolicy, dryRun: boolean = true): Promise<RetentionReport> {
+  try {
+    return await invoke<RetentionReport>('apply_archive_retention', { policy, dryRun });
+  } catch (error) {
+    console.error('Error applying archive retention:', error);
+    throw error;
+  }
+}
+
+export function onArchiveRetention(handler: (report: RetentionReport) => void): Promise<UnlistenFn> {
+  return listen<RetentionReport>('archive-retention', (event) => handler(event.payload));
+}
+
+export async function gcArchives(dryRun = false): Promise<GcReport> {
+  try {
+    return await invoke<GcReport>('gc_archives', { dryRun });
+  } catch (error) {
+    console.error('Error collecting archive garbage:', error);
+    throw error;
+  }
+}
+
+export async function verifyArchive(archive: string, key?: ArchiveKey): Promise<ArchiveVerification> {
+  try {
+    return await invoke<ArchiveVerification>('verify_archive', { archive, key });
+  } catch (error) {
+    console.error('Error verifying archive:', error);
+    throw error;
+  }
+}
+
+export async function verifyArchives(): Promise<ArchiveVerification[]> {
+  try {
+    return await invoke<ArchiveVerification[]>('verify_archives');
+  } catch (error) {
+    console.error('Error verifying archives:', error);
+    throw error;
+  }
+}
+
+export async function listArchiveContents(archive: string, prefix?: string, key?: ArchiveKey): Promise<ArchiveContentEntry[]> {
+  try {
+    return await invoke<ArchiveContentEntry[]>('list_archive_contents', { archive, prefix, key });
+  } catch (error) {
+    console.error('Error listing archive contents:', error);
+    throw error;
+  }
+}
+
+export async function extractFromArchive(archive: string, paths: string[], destination: string, key?: ArchiveKey): Promise<ExtractResult> {
+  try {
+    return await invoke<ExtractResult>('extract_from_archive', { archive, paths, destination, key });
+  } catch (error) {
+    console.error('Error extracting from archive:', error);
+    throw error;
+  }
+}
+
+export async function diffArchive(a: DiffSource, b: DiffSource, key?: ArchiveKey): Promise<ArchiveDiff> {
+  try {
+    return await invoke<ArchiveDiff>('diff_archive', { a, b, key });
+  } catch (error) {
+    console.error('Error diffing archives:', error);
+    throw error;
+  }
+}
+
+export async function generateArchiveKey(path: string): Promise<string> {
+  try {
+    return await invoke<string>('generate_archive_key', { path });
+  } catch (error) {
+    console.error('Error generating archive key:', error);
+    throw error;
+  }
+}
+
+export function onArchiveVerified(handler: (verification: ArchiveVerification) => void): Promise<UnlistenFn> {
+  return listen<ArchiveVerification>('archive-verified', (event) => handler(event.payload));
+}
+
+export async function startJob(request: JobRequest): Promise<string> {
+  try {
+    return await invoke<string>('start_job', { request });
+  } catch (error) {
+    console.error('Error starting job:', error);
+    throw error;
+  }
+}
+
+// Background versions of the long running operations; each resolves to the job id right away
+export async function startArchiveJob(session: string, options?: ArchiveOptions): Promise<string> {
+  try {
+    return await invoke<string>('start_archive_job', { session, options });
+  } catch (error) {
+    console.error('Error starting archive job:', error);
+    throw error;
+  }
+}
+
+export async function startRestoreJob(archive: string, newSessionName?: string, skipVerify = false, key?: ArchiveKey, options?: RestoreOptions): Promise<string> {
+  try {
+    return await invoke<string>('start_restore_job', { archive, newSessionName, skipVerify, key, options });
+  } catch (error) {
+    console.error('Error starting restore job:', error);
+    throw error;
+  }
+}
+
+export async function startCloneJob(source: string, newName: string, options?: CloneOptions): Promise<string> {
+  try {
+    return await invoke<string>('start_clone_job', { source, newName, options });
+  } catch (error) {
+    console.error('Error starting clone job:', error);
+    throw error;
+  }
+}
+
+export async function startCleanJob(sessions?: string[], categories?: UsageCategory[], dryRun = false): Promise<string> {
+  try {
+    return await invoke<string>('start_clean_job', { sessions, categories, dryRun });
+  } catch (error) {
+    console.error('Error starting clean job:', error);
+    throw error;
+  }
+}
+
+export async function startVerifyJob(archive?: string, key?: ArchiveKey): Promise<string> {
+  try {
+    return await invoke<string>('start_verify_job', { archive, key });
+  } catch (error) {
+    console.error('Error starting verify job:', error);
+    throw error;
+  }
+}
+
+export async function startDiskUsageJob(session: string): Promise<string> {
+  try {
+    return await invoke<string>('start_disk_usage_job', { session });
+  } catch (error) {
+    console.error('Error starting disk usage job:', error);
+    throw error;
+  }
+}
+
+export async function cancelJob(id: string): Promise<JobInfo> {
+  try {
+    return await invoke<JobInfo>('cancel_job', { id });
+  } catch (error) {
+    console.error('Error cancelling job:', error);
+    throw error;
+  }
+}
+
+export async function listJobs(): Promise<JobInfo[]> {
+  try {
+    return await invoke<JobInfo[]>('list_jobs');
+  } catch (error) {
+    console.error('Error listing jobs:', error);
+    throw error;
+  }
+}
+
+export function onJobProgress(handler: (job: JobInfo) => void): Promise<UnlistenFn> {
+  return listen<JobInfo>('job-progress', (event) => handler(event.payload));
+}
+
+export function onJobFinished(handler: (job: JobInfo) => void): Promise<UnlistenFn> {
+  return listen<JobInfo>('job-finished', (event) => handler(event.payload));
+}
+
 export async function randomMac(): Promise<string> {
   try {
     return await invoke<string>('random_mac');
@@ -190,8 +1042,8 @@ export async function getSessions(): Promise<CursorSession[]> {
       id: session.name,
       name: session.name,
       created_date: session.created,
-      modified_date: session.created,
-      projects: [],
+      modified_date: session.modified ?? session.created,
+      projects: session.projects ?? [],
       electron_app: session.electron_app,
     }));
     console.log(`[API] Mapped sessions:`, mappedSessions);
@@ -219,6 +1071,95 @@ export async function saveSession(name: string, projects: string[], electron_app
   }
 }
 
+export async function setSessionProjects(session: string, projects: string[]): Promise<string> {
+  try {
+    return await invoke<string>('set_session_projects', { session, projects });
+  } catch (error) {
+    console.error('Error setting session projects:', error);
+    throw error;
+  }
+}
+
+export async function updateSessionMetadata(session: string, update: SessionMetadataUpdate): Promise<SessionInfo> {
+  try {
+    return await invoke<SessionInfo>('update_session_metadata', { session, update });
+  } catch (error) {
+    console.error('Error updating session metadata:', error);
+    throw error;
+  }
+}
+
+export async function sessionGitStatus(session: string): Promise<ProjectGitStatus[]> {
+  try {
+    return await invoke<ProjectGitStatus[]>('session_git_status', { session });
+  } catch (error) {
+    console.error('Error reading session git status:', error);
+    throw error;
+  }
+}
+
+export async function touchSession(session: string): Promise<boolean> {
+  try {
+    return await invoke<boolean>('touch_session', { session });
+  } catch (error) {
+    console.error('Error touching session:', error);
+    return false;
+  }
+}
+
+export async function listSessionActivity(): Promise<SessionActivity[]> {
+  try {
+    return await invoke<SessionActivity[]>('list_session_activity');
+  } catch (error) {
+    console.error('Error listing session activity:', error);
+    return [];
+  }
+}
+
+export async function listIdleEvents(): Promise<IdleEvent[]> {
+  try {
+    return await invoke<IdleEvent[]>('list_idle_events');
+  } catch (error) {
+    console.error('Error listing idle events:', error);
+    return [];
+  }
+}
+
+export function onSessionIdle(handler: (event: IdleEvent) => void): Promise<UnlistenFn> {
+  return listen<IdleEvent>('session-idle', (event) => handler(event.payload));
+}
+
+export async function sessionDiskUsage(session: string): Promise<DiskUsageReport> {
+  try {
+    return await invoke<DiskUsageReport>('session_disk_usage', { session });
+  } catch (error) {
+    console.error('Error measuring session disk usage:', error);
+    throw error;
+  }
+}
+
+export function onDiskUsageProgress(handler: (progress: DiskUsageProgress) => void): Promise<UnlistenFn> {
+  return listen<DiskUsageProgress>('disk-usage-progress', (event) => handler(event.payload));
+}
+
+export async function cleanSession(session: string, categories?: UsageCategory[], dryRun: boolean = false): Promise<CleanReport> {
+  try {
+    return await invoke<CleanReport>('clean_session', { session, categories, dryRun });
+  } catch (error) {
+    console.error('Error cleaning session:', error);
+    throw error;
+  }
+}
+
+export async function cleanSessions(sessions?: string[], categories?: UsageCategory[], dryRun: boolean = false): Promise<BulkCleanResult[]> {
+  try {
+    return await invoke<BulkCleanResult[]>('clean_sessions', { sessions, categories, dryRun });
+  } catch (error) {
+    console.error('Error cleaning sessions:', error);
+    throw error;
+  }
+}
+
 export async function deleteSessionById(id: string): Promise<boolean> {
   console.log(`[API] deleteSessionById called for: ${id}`);
   try {